@@ -1,4 +1,5 @@
 use crate::backend::events::event_handler::EventHandler;
+use crate::backend::sftp;
 use crate::backend::AppBackend;
 use anyhow::{Context, Result};
 use std::{env, path::PathBuf, thread, time::Duration};
@@ -7,15 +8,22 @@ pub fn run() -> Result<()> {
     let args: Vec<String> = env::args().collect();
     let mut initial_path = env::current_dir().unwrap();
 
-    if args.len() > 1 {
+    if args.len() > 1 && sftp::is_url(&args[1]) {
+        initial_path = sftp::open(&args[1])
+            .with_context(|| format!("[app.run()] Failed to open {}", args[1]))?;
+    } else if args.len() > 1 {
         initial_path = PathBuf::from(&args[1]);
     }
 
-    let mut app_backend =
-        AppBackend::new(initial_path).context("[app.run()] Failed to create AppBackend")?;
-
     let event_handler = EventHandler::new().context("Failed to make event handler")?;
 
+    let mut app_backend = AppBackend::new(
+        initial_path,
+        event_handler.app_event_sender(),
+        event_handler.input_control(),
+    )
+    .context("[app.run()] Failed to create AppBackend")?;
+
     loop {
         event_handler
             .process_events(&mut app_backend)
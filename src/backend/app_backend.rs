@@ -1,37 +1,166 @@
+use super::archive;
+use super::bulk_rename::BulkRename;
+use super::clipboard::{Clipboard, ClipboardIntent, ConflictPolicy, PastePlan};
+use super::columns::{self, Column};
 use super::dir_list::FileSystemItem;
-use super::{DirList, Tab, Tabs};
+use super::dir_loader::{DirBatch, DirLoader};
+use super::events::{InputControl, RstuifmEvent};
+use super::file_operations::{self, FileOperationError};
+use super::filter::{Filter, FilterKind};
+use super::finder::{Finder, FinderResults};
+use super::grep::{GrepBatch, Search};
+use super::highlighter::{
+    HighlightKey, HighlightRequest, HighlightResult, HighlightedLines, Highlighter,
+};
+use super::jobs::{JobEvent, JobManager, JobTask};
+use super::journal::{Journal, JournalEntry, JournalJob, Operation};
+use super::opener::{OpenWithMenu, OpenerRule, Openers};
+use super::preferences::Preferences;
+use super::preview::{
+    FilePreview, HexDump, LoadedPreview, PreviewContent, PreviewKey, PreviewMode, PreviewResult,
+    Previewer,
+};
+use super::prompt::{describe_paths, Prompt, PromptKind};
+use super::sftp::{self, Connected};
+use super::sort::{SortOptions, SortSettings};
+use super::trash;
+use super::vfs;
+use super::watcher::Watcher;
+use super::{DirList, Mode, Tab, Tabs};
 use crate::helper_functions;
 use crate::ui;
-use crate::ui::widgets::{RightPane, ThreePaneLayoutState};
+use crate::ui::widgets::{
+    right_pane_inner_area, DirSelectionList, FinderPopup, GrepView, OpenWithPopup, RenamePreview,
+    RightPane, StatusLine, ThreePaneLayoutState, TrashView,
+};
 use anyhow::{Context, Result};
-use ratatui::{backend::CrosstermBackend, Terminal};
-use std::path::PathBuf;
-use std::{collections::HashMap, io::Stdout};
+use ratatui::{backend::CrosstermBackend, layout::Rect, Terminal};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::Arc;
+use std::{collections::HashMap, env, fs, io, io::Stdout, io::Write};
+
+const HIGHLIGHT_CACHE_SIZE: usize = 64;
 
 pub struct AppBackend {
     dirlist_cache: HashMap<PathBuf, DirList>,
+    dir_loader: DirLoader,
+    // Directories that could not be read, kept while they are on screen so they are not read again
+    // on every redraw
+    dirlist_errors: HashMap<PathBuf, io::Error>,
+    // Rereads of listings that stay on screen until the new listing is complete
+    reloads: HashMap<PathBuf, DirList>,
+    // Item to put the cursor on once its directory was read again, after creating or renaming it
+    pending_selection: Option<(PathBuf, String)>,
     pub tabs: Tabs,
     pub terminal: Terminal<CrosstermBackend<Stdout>>,
+    pub mode: Mode,
+    status: StatusLine,
+    clipboard: Option<Clipboard>,
+    // First key of a two key binding such as `yy`
+    pub pending_key: Option<char>,
+    pub jobs: JobManager,
+    trash_view: Option<TrashView>,
+    journal: Journal,
+    journal_jobs: HashMap<usize, JournalJob>,
+    input_control: Arc<InputControl>,
+    // Last preview, kept so redraws do not read the item again. It is loaded again when the
+    // selection moves or the item's directory changes
+    preview_cache: Option<(PreviewKey, LoadedPreview)>,
+    previewer: Previewer,
+    preview_requested: Option<PreviewKey>,
+    highlighter: Highlighter,
+    // None marks files without a matching syntax, so they are not sent again
+    highlight_cache: HashMap<HighlightKey, Option<HighlightedLines>>,
+    highlight_requested: Option<HighlightKey>,
+    preview_mode: PreviewMode,
+    // Hex dump position, only kept while the same file stays selected
+    hex_position: Option<(PathBuf, u64)>,
+    watcher: Watcher,
+    sort_settings: SortSettings,
+    // Details shown next to the names in the middle pane
+    columns: Vec<Column>,
+    preferences: Preferences,
+    // Fuzzy finder, open while its query is typed
+    finder: Option<Finder>,
+    // Content search, its results stay open until closed
+    search: Option<Search>,
+    // Prompts waiting for their sftp:// url to connect
+    connecting: Vec<(String, PromptKind)>,
+    app_event_tx: crossbeam::channel::Sender<RstuifmEvent>,
 }
 
 impl AppBackend {
-    pub fn new(initial_path: PathBuf) -> Result<AppBackend> {
+    pub fn new(
+        initial_path: PathBuf,
+        app_event_tx: crossbeam::channel::Sender<RstuifmEvent>,
+        input_control: Arc<InputControl>,
+    ) -> Result<AppBackend> {
         let terminal = crate::ui::functions::setup_terminal()
             .context("[AppBackend.new()] Failed to setup terminal")?;
-        let main_dir_list = DirList::new(&initial_path)?;
         let dirlist_cache = HashMap::new();
-        let tab = Tab::new(initial_path, main_dir_list);
+        let (sort_settings, sort_error) = match SortSettings::load() {
+            Ok(sort_settings) => (sort_settings, None),
+            Err(e) => (SortSettings::default(), Some(e)),
+        };
+        let sort = sort_settings.for_directory(sort_settings.default, &initial_path);
+        let (columns, columns_error) = match columns::load() {
+            Ok(columns) => (columns, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        let preferences = Preferences::load();
+        let tab = Tab::new(
+            initial_path.clone(),
+            DirList::loading(sort),
+            sort,
+            preferences.show_hidden,
+        );
 
-        let mut tabs_vec: Vec<Tab> = Vec::new();
-        tabs_vec.push(tab);
+        let tabs_vec: Vec<Tab> = vec![tab];
         let tabs = Tabs::new(tabs_vec).context("Failed to create tabs")?;
 
         let mut backend = AppBackend {
             dirlist_cache,
+            dir_loader: DirLoader::new(app_event_tx.clone()),
+            dirlist_errors: HashMap::new(),
+            reloads: HashMap::new(),
+            pending_selection: None,
             tabs,
             terminal,
+            mode: Mode::Normal,
+            status: StatusLine::Empty,
+            clipboard: None,
+            pending_key: None,
+            jobs: JobManager::new(app_event_tx.clone()),
+            trash_view: None,
+            journal: Journal::load(),
+            journal_jobs: HashMap::new(),
+            input_control,
+            preview_cache: None,
+            previewer: Previewer::new(app_event_tx.clone()),
+            preview_requested: None,
+            watcher: Watcher::new(app_event_tx.clone()),
+            highlighter: Highlighter::new(app_event_tx.clone()),
+            highlight_cache: HashMap::new(),
+            highlight_requested: None,
+            preview_mode: PreviewMode::Text,
+            hex_position: None,
+            sort_settings,
+            columns,
+            preferences,
+            finder: None,
+            search: None,
+            connecting: Vec::new(),
+            app_event_tx,
         };
+        if let Some(e) = sort_error.or(columns_error) {
+            backend.set_status_error(format!("{:#}", e));
+        }
 
+        backend
+            .get_dirlist(&initial_path)
+            .context("[AppBackend.new()] Failed to read the initial directory")?;
         let mut state = backend.get_new_state();
 
         backend
@@ -68,112 +197,2061 @@ impl AppBackend {
         self.draw(three_pane_layout_state);
     }
 
-    // Cached way to get dirlist
+    // Cached way to get dirlist, a directory not read yet comes back empty and loading
     pub fn get_dirlist(&mut self, path: &PathBuf) -> Result<&DirList> {
+        if let Some(e) = self.dirlist_errors.get(path) {
+            return Err(io::Error::new(e.kind(), e.to_string()))
+                .context("[get_dirlist] Unable to create DirList");
+        }
         if !self.dirlist_cache.contains_key(path) {
-            let dirlist = DirList::new(path).context("[get_dirlist] Unable to create DirList")?;
-            self.dirlist_cache.insert(path.clone(), dirlist);
+            let sort = self.sort_for(path);
+            self.dir_loader.load(path, sort);
+            self.dirlist_cache
+                .insert(path.clone(), DirList::loading(sort));
         }
 
         Ok(self.dirlist_cache.get(path).unwrap())
     }
 
+    // How the selected tab would sort `path`
+    fn sort_for(&self, path: &Path) -> SortOptions {
+        self.sort_settings
+            .for_directory(self.tabs.selected_tab_ref().sort, path)
+    }
+
+    // A side pane listing, sorted like the selected tab would sort it. The cached listing is sorted
+    // in place, so redraws do not sort it again
+    fn sorted_dirlist(&mut self, path: &PathBuf) -> Result<DirList> {
+        let sort = self.sort_for(path);
+        self.get_dirlist(path)?;
+        let dir_list = self.dirlist_cache.get_mut(path).unwrap();
+        dir_list.sort(sort);
+        dir_list.set_show_hidden(self.preferences.show_hidden);
+        Ok(dir_list.clone())
+    }
+
+    // Changes how the selected tab sorts, remembered for its directory when configured
+    pub fn change_sort(&mut self, change: impl FnOnce(&mut SortOptions)) {
+        let selected_tab = self.tabs.selected_tab_ref_mut();
+        let mut sort = selected_tab.sort;
+        change(&mut sort);
+        selected_tab.set_sort(sort);
+
+        let working_directory = self.working_directory();
+        match self.sort_settings.remember(&working_directory, sort) {
+            Ok(()) => self.set_status_message(format!("Sorted by {}", sort.describe())),
+            Err(e) => self.set_status_error(format!("{:#}", e)),
+        }
+        self.redraw();
+    }
+
+    // Shows or hides dotfiles in every tab, the choice is kept for later sessions
+    pub fn toggle_hidden(&mut self) {
+        let show_hidden = !self.preferences.show_hidden;
+        self.preferences.show_hidden = show_hidden;
+        for tab in self.tabs.iter_mut() {
+            tab.set_show_hidden(show_hidden);
+        }
+
+        match self.preferences.save() {
+            Ok(()) if show_hidden => self.set_status_message("Showing hidden files".to_string()),
+            Ok(()) => self.set_status_message("Hiding hidden files".to_string()),
+            Err(e) => self.set_status_error(format!("{:#}", e)),
+        }
+        self.redraw();
+    }
+
+    // Read-only providers like archives refuse changes, this says so before a job is started
+    fn ensure_writable(&mut self, path: &Path) -> bool {
+        if vfs::resolve_directory(path).is_ok_and(|provider| provider.is_read_only()) {
+            self.set_status_error(format!(
+                "{} is read-only",
+                describe_paths(&[path.to_path_buf()])
+            ));
+            self.redraw();
+            false
+        } else {
+            true
+        }
+    }
+
+    // Rereads a directory after it changed, tabs showing it get the new listing once it is complete
+    pub fn refresh_dirlist(&mut self, path: &Path) {
+        self.dirlist_errors.remove(path);
+        self.reloads.remove(path);
+        // A read that is still filling the list starts over
+        let mut sort = self.sort_for(path);
+        if let Some(dir_list) = self.dirlist_cache.get_mut(path) {
+            sort = dir_list.sort_options();
+            if dir_list.is_loading() {
+                *dir_list = DirList::loading(sort);
+            }
+        }
+        self.dir_loader.load(path, sort);
+    }
+
+    // Puts the cursor on `name` once `directory` was read again
+    fn select_after_refresh(&mut self, directory: &Path, name: &str) {
+        self.pending_selection = Some((directory.to_path_buf(), name.to_string()));
+    }
+
+    fn refresh_tabs(&mut self, path: &Path, dir_list: &DirList) {
+        for tab in self.tabs.iter_mut() {
+            if tab.working_directory == path {
+                tab.refresh(dir_list.clone());
+            }
+        }
+    }
+
+    pub fn handle_dir_batch(&mut self, batch: DirBatch) {
+        if !self.dir_loader.accept(&batch) {
+            return;
+        }
+        let path = batch.path;
+        let items = match batch.items {
+            Ok(items) => items,
+            Err(e) => return self.handle_dirlist_error(&path, e),
+        };
+
+        let dir_list = match self.dirlist_cache.get_mut(&path) {
+            Some(dir_list) if dir_list.is_loading() => {
+                dir_list.append(items);
+                if batch.done {
+                    dir_list.finish_loading();
+                }
+                dir_list.clone()
+            }
+            _ => {
+                match self.reloads.get_mut(&path) {
+                    Some(reload) => reload.append(items),
+                    None => {
+                        self.reloads.insert(path.clone(), items);
+                    }
+                }
+                if !batch.done {
+                    return;
+                }
+                let dir_list = self.reloads.remove(&path).unwrap_or_default();
+                self.dirlist_cache.insert(path.clone(), dir_list.clone());
+                dir_list
+            }
+        };
+        self.refresh_tabs(&path, &dir_list);
+
+        if batch.done {
+            match self.pending_selection.take() {
+                Some((directory, name)) if directory == path => {
+                    let selected_tab = self.tabs.selected_tab_ref_mut();
+                    if selected_tab.working_directory == directory {
+                        selected_tab.select_name(&name);
+                    }
+                }
+                pending_selection => self.pending_selection = pending_selection,
+            }
+        }
+    }
+
+    // Tabs that entered a directory before reading it failed go back up. A failed reread keeps
+    // the old listing, a directory that is gone is handled by the watcher
+    fn handle_dirlist_error(&mut self, path: &Path, e: io::Error) {
+        self.reloads.remove(path);
+        if self
+            .dirlist_cache
+            .get(path)
+            .is_some_and(|dir_list| !dir_list.is_loading())
+        {
+            return;
+        }
+
+        self.dirlist_cache.remove(path);
+        let entered = self.tabs.iter().any(|tab| tab.working_directory == path);
+        if entered {
+            self.set_status_error(e.to_string());
+        }
+        self.dirlist_errors.insert(path.to_path_buf(), e);
+
+        if let Some(parent) = vfs::parent(path) {
+            self.move_tabs(path, parent);
+            if let Some(name) = path.file_name() {
+                for tab in self.tabs.iter_mut() {
+                    if tab.working_directory == parent {
+                        tab.select_name(&name.to_string_lossy());
+                    }
+                }
+            }
+        }
+    }
+
+    // Moves the tabs inside `path` up to `ancestor`
+    fn move_tabs(&mut self, path: &Path, ancestor: &Path) {
+        if !self
+            .tabs
+            .iter()
+            .any(|tab| tab.working_directory.starts_with(path))
+        {
+            return;
+        }
+
+        let ancestor = ancestor.to_path_buf();
+        let dir_list = match self.get_dirlist(&ancestor) {
+            Ok(dir_list) => dir_list.clone(),
+            Err(_) => return,
+        };
+        for tab in self.tabs.iter_mut() {
+            if tab.working_directory.starts_with(path) {
+                let sort = self.sort_settings.for_directory(tab.sort, &ancestor);
+                tab.select(ancestor.clone(), dir_list.clone(), sort);
+            }
+        }
+    }
+
+    // A watched directory changed on disk, tabs inside it move up when it is gone
+    pub fn handle_directory_change(&mut self, path: &Path) {
+        self.forget_preview(path);
+        if vfs::metadata(path).is_ok_and(|metadata| metadata.is_dir()) {
+            self.refresh_dirlist(path);
+            return;
+        }
+
+        self.evict_dirlist(path);
+        let ancestor = path
+            .ancestors()
+            .skip(1)
+            .find(|ancestor| vfs::metadata(ancestor).is_ok_and(|m| m.is_dir()));
+        if let Some(ancestor) = ancestor {
+            self.move_tabs(path, ancestor);
+        }
+    }
+
+    // Drops cached listings of a directory that was removed or renamed, including its children
+    fn evict_dirlist(&mut self, path: &Path) {
+        self.dirlist_cache
+            .retain(|cached_path, _| !cached_path.starts_with(path));
+        self.dirlist_errors
+            .retain(|cached_path, _| !cached_path.starts_with(path));
+        self.reloads
+            .retain(|cached_path, _| !cached_path.starts_with(path));
+    }
+
     pub fn select_right(&mut self) {
         let selected_tab = self.tabs.selected_tab_ref_mut();
         let selected_item = selected_tab.selected_item();
+        // Links to sockets, FIFOs and devices are Symlink items as well
+        let broken_link = selected_tab
+            .ui
+            .mid_pane
+            .as_ref()
+            .and_then(|mid_pane| mid_pane.link(mid_pane.state))
+            .is_some_and(|link| link.broken);
 
-        match selected_item {
-            Some(selected_item) => {
-                let new_path = match selected_item {
-                    FileSystemItem::Folder(folder) => Some(folder.path),
-                    _ => None,
-                };
-
-                if let Some(path) = new_path {
-                    let new_dir_list = self.get_dirlist(&path);
-                    match new_dir_list {
-                        Ok(dir_list_ref) => {
-                            let new_dir_list = dir_list_ref.clone();
-                            let selected_tab = self.tabs.selected_tab_ref_mut();
-                            selected_tab.select(path, new_dir_list)
-                        }
+        if let Some(selected_item) = selected_item {
+            let new_path = match selected_item {
+                FileSystemItem::Folder(folder) => Some(folder.path),
+                FileSystemItem::File(file) if archive::is_archive(&file.path) => Some(file.path),
+                FileSystemItem::File(file)
+                    if vfs::resolve(&file.path).is_ok_and(|provider| !provider.is_local()) =>
+                {
+                    self.set_status_error(format!(
+                        "Copy {} to a local directory to open it",
+                        describe_paths(&[file.path])
+                    ));
+                    None
+                }
+                FileSystemItem::File(file) => {
+                    self.open_file(&file.path);
+                    None
+                }
+                FileSystemItem::Symlink(symlink) if broken_link => {
+                    self.set_status_error(format!("{} is a broken link", symlink.name()));
+                    None
+                }
+                FileSystemItem::Symlink(symlink) => {
+                    self.set_status_error(format!("Cannot open {}", symlink.name()));
+                    None
+                }
+            };
 
-                        Err(_) => {
-                            return;
-                        }
+            if let Some(path) = new_path {
+                let new_dir_list = self.get_dirlist(&path);
+                match new_dir_list {
+                    Ok(dir_list_ref) => {
+                        let new_dir_list = dir_list_ref.clone();
+                        let selected_tab = self.tabs.selected_tab_ref_mut();
+                        let sort = self.sort_settings.for_directory(selected_tab.sort, &path);
+                        selected_tab.select(path, new_dir_list, sort)
                     }
-                }
 
-                let new_state = self.get_new_state();
-                self.draw(new_state);
+                    Err(e) => {
+                        self.set_status_error(e.root_cause().to_string());
+                    }
+                }
             }
-            None => (),
+
+            let new_state = self.get_new_state();
+            self.draw(new_state);
         }
     }
 
+    // Goes up with the cursor on the directory left. Directories entered through a link keep the
+    // link's path, so this returns next to the link rather than its target
     pub fn select_left(&mut self) {
-        let selected_tab = self.tabs.selected_tab_ref_mut();
-        let working_dir = selected_tab.working_directory.clone();
+        let working_dir = self.tabs.selected_tab_ref().working_directory.clone();
         //TODO: Deal with the error of working dir being root
-        let new_path = working_dir.parent();
+        if vfs::parent(&working_dir).is_some() {
+            self.reveal(&working_dir);
+            self.redraw();
+        }
+    }
+
+    // Jumps to where the link under the cursor points, with the cursor on the target in its
+    // directory
+    pub fn follow_link(&mut self) {
+        let selected = self
+            .tabs
+            .selected_tab_ref()
+            .ui
+            .mid_pane
+            .as_ref()
+            .and_then(|mid_pane| {
+                let item = mid_pane.selected_item()?;
+                Some((item, mid_pane.link(mid_pane.state).cloned()))
+            });
+        let (path, target) = match selected {
+            Some((item, Some(target))) if !target.broken => (item.path().to_path_buf(), target),
+            Some((item, Some(target))) => {
+                self.set_status_error(format!(
+                    "{} points to {}, which does not exist",
+                    item.name(),
+                    target.path.display()
+                ));
+                self.redraw();
+                return;
+            }
+            Some((item, None)) => {
+                self.set_status_error(format!("{} is not a link", item.name()));
+                self.redraw();
+                return;
+            }
+            None => return,
+        };
+
+        // Relative targets start at the link's directory, local ones also resolve further links
+        let target = path
+            .parent()
+            .map_or(target.path.clone(), |parent| parent.join(&target.path));
+        let is_local = vfs::resolve(&target).is_ok_and(|provider| provider.is_local());
+        let target = match fs::canonicalize(&target) {
+            Ok(canonical) if is_local => canonical,
+            _ => helper_functions::normalize_path(&target),
+        };
+        self.reveal(&target);
+        self.redraw();
+    }
 
-        match new_path {
-            Some(path) => {
-                let new_path = path.to_path_buf();
+    pub fn set_status_message(&mut self, message: String) {
+        self.status = StatusLine::Message(message);
+    }
 
-                let new_dir_list = self.get_dirlist(&new_path).unwrap().clone();
+    pub fn set_status_error(&mut self, error: String) {
+        self.status = StatusLine::Error(error);
+    }
 
-                let selected_tab = self.tabs.selected_tab_ref_mut();
-                selected_tab.select(new_path, new_dir_list);
+    pub fn clear_status(&mut self) {
+        self.status = StatusLine::Empty;
+    }
 
-                let new_state = self.get_new_state();
-                self.draw(new_state);
+    fn status_line(&self) -> StatusLine {
+        match &self.mode {
+            Mode::Prompt(prompt) => StatusLine::Prompt {
+                label: prompt.kind.label(),
+                input: prompt.input.clone(),
+                cursor: prompt.cursor,
+            },
+            Mode::Normal if self.pending_key.is_some() => {
+                StatusLine::Message(self.pending_key.map(String::from).unwrap_or_default())
             }
+            Mode::JobPanel(_) | Mode::Trash | Mode::Grep | Mode::OpenWith(_) => self.status.clone(),
+            Mode::Normal => match (&self.status, &self.tabs.selected_tab_ref().ui.mid_pane) {
+                (StatusLine::Empty, Some(mid_pane)) if mid_pane.is_visual() => {
+                    StatusLine::Message(format!("-- VISUAL -- {} marked", mid_pane.marked_count()))
+                }
+                (StatusLine::Empty, Some(mid_pane)) if !mid_pane.marked.is_empty() => {
+                    StatusLine::Message(format!("{} marked", mid_pane.marked_count()))
+                }
+                // Stays up while ssh connects, even when moving around clears other messages
+                (StatusLine::Empty, _) if !self.connecting.is_empty() => {
+                    StatusLine::Message(format!("Connecting to {}", self.connecting[0].0))
+                }
+                (status, _) => status.clone(),
+            },
+        }
+    }
 
-            None => {
-                return;
+    // Paths that file operations act on: the marked set if there is one, otherwise the cursor
+    pub fn selected_paths(&mut self) -> Vec<PathBuf> {
+        let paths = self
+            .tabs
+            .selected_tab_ref()
+            .target_items()
+            .iter()
+            .map(|item| item.path().to_path_buf())
+            .collect();
+
+        // Starting an operation ends visual mode like it does in vim
+        if let Some(mid_pane) = &mut self.tabs.selected_tab_ref_mut().ui.mid_pane {
+            mid_pane.end_visual(true);
+        }
+        paths
+    }
+
+    fn mid_pane_mut(&mut self) -> Option<&mut DirSelectionList> {
+        self.tabs.selected_tab_ref_mut().ui.mid_pane.as_mut()
+    }
+
+    pub fn toggle_mark(&mut self) {
+        if let Some(mid_pane) = self.mid_pane_mut() {
+            mid_pane.toggle_mark();
+            mid_pane.select_next();
+        }
+        self.redraw();
+    }
+
+    pub fn invert_marks(&mut self) {
+        if let Some(mid_pane) = self.mid_pane_mut() {
+            mid_pane.invert_marks();
+        }
+        self.redraw();
+    }
+
+    pub fn mark_all(&mut self) {
+        if let Some(mid_pane) = self.mid_pane_mut() {
+            mid_pane.mark_all();
+        }
+        self.redraw();
+    }
+
+    pub fn toggle_visual(&mut self) {
+        if let Some(mid_pane) = self.mid_pane_mut() {
+            if mid_pane.is_visual() {
+                mid_pane.end_visual(true);
+            } else {
+                mid_pane.start_visual();
             }
         }
+        self.redraw();
     }
 
-    pub fn get_new_state(&mut self) -> ThreePaneLayoutState {
-        let working_dir = {
-            let selected_tab_mut = self.tabs.selected_tab_ref_mut();
-            selected_tab_mut.working_directory.clone()
+    // Esc leaves visual mode without keeping the range, then clears the filter and then the marks
+    pub fn cancel_selection(&mut self) {
+        if let Some(mid_pane) = self.mid_pane_mut() {
+            if mid_pane.is_visual() {
+                mid_pane.end_visual(false);
+            } else if mid_pane.filter().is_some() {
+                mid_pane.clear_filter();
+            } else {
+                mid_pane.clear_marks();
+            }
+        }
+        self.redraw();
+    }
+
+    // Opens the filter prompt, a filter already applied can be refined
+    pub fn begin_filter(&mut self) {
+        let (kind, pattern) = self
+            .tabs
+            .selected_tab_ref()
+            .ui
+            .mid_pane
+            .as_ref()
+            .and_then(|mid_pane| mid_pane.filter())
+            .map(|filter| (filter.kind(), filter.pattern().to_string()))
+            .unwrap_or_default();
+        self.open_prompt(Prompt::with_input(PromptKind::Filter(kind), pattern));
+    }
+
+    // Filters with what was typed so far. A pattern that does not compile yet, like an unclosed
+    // regex group, leaves the last filter in place
+    pub fn update_filter(&mut self) {
+        let (kind, pattern) = match self.prompt_mut() {
+            Some(Prompt {
+                kind: PromptKind::Filter(kind),
+                input,
+                ..
+            }) => (*kind, input.clone()),
+            _ => return,
         };
-        let parent_dir = match working_dir.parent() {
-            Some(path) => Some(path.to_path_buf()),
-            None => None,
+
+        if pattern.is_empty() {
+            if let Some(mid_pane) = self.mid_pane_mut() {
+                mid_pane.set_filter(None);
+            }
+        } else if let Ok(filter) = Filter::new(kind, &pattern) {
+            if let Some(mid_pane) = self.mid_pane_mut() {
+                mid_pane.set_filter(Some(filter));
+            }
+        }
+        self.redraw();
+    }
+
+    pub fn cycle_filter_kind(&mut self) {
+        if let Some(Prompt {
+            kind: PromptKind::Filter(kind),
+            ..
+        }) = self.prompt_mut()
+        {
+            *kind = kind.next();
+        }
+        self.update_filter();
+    }
+
+    // Esc while typing a filter drops it, the cursor goes back to where it was
+    pub fn cancel_filter(&mut self) {
+        if let Some(mid_pane) = self.mid_pane_mut() {
+            mid_pane.clear_filter();
+        }
+        self.cancel_prompt();
+    }
+
+    // Opens the fuzzy finder on the files below the working directory
+    pub fn begin_find(&mut self) {
+        let root = self.working_directory();
+        if !vfs::resolve(&root).is_ok_and(|provider| provider.is_local()) {
+            self.set_status_error(String::from("Finding files needs a local directory"));
+            self.redraw();
+            return;
+        }
+
+        let show_hidden = self.preferences.show_hidden;
+        self.finder = Some(Finder::start(root, show_hidden, self.app_event_tx.clone()));
+        self.open_prompt(Prompt::new(PromptKind::Find));
+    }
+
+    pub fn update_find(&mut self) {
+        let query = match self.prompt_mut() {
+            Some(Prompt {
+                kind: PromptKind::Find,
+                input,
+                ..
+            }) => input.clone(),
+            _ => return,
         };
+        if let Some(finder) = &mut self.finder {
+            finder.set_query(&query);
+            finder.selected = 0;
+        }
+        self.redraw();
+    }
+
+    pub fn handle_finder_results(&mut self, results: FinderResults) {
+        if let Some(finder) = &mut self.finder {
+            finder.accept(results);
+        }
+    }
+
+    pub fn finder_mut(&mut self) -> Option<&mut Finder> {
+        self.finder.as_mut()
+    }
+
+    pub fn cancel_find(&mut self) {
+        self.finder = None;
+        self.cancel_prompt();
+    }
+
+    fn finish_find(&mut self) {
+        if let Some(path) = self.finder.take().and_then(|finder| finder.selected_path()) {
+            self.reveal(&path);
+        }
+    }
 
-        let left_pane = match parent_dir {
-            Some(path) => Some(self.get_dirlist(&path).unwrap().clone()),
-            None => None,
+    // Moves the selected tab to the directory holding `path`, with the cursor on it
+    fn reveal(&mut self, path: &Path) {
+        let (directory, name) = match (path.parent(), path.file_name()) {
+            (Some(directory), Some(name)) => (directory.to_path_buf(), name.to_string_lossy()),
+            _ => return,
+        };
+        let dir_list = match self.get_dirlist(&directory) {
+            Ok(dir_list) => dir_list.clone(),
+            Err(e) => {
+                self.set_status_error(e.root_cause().to_string());
+                return;
+            }
         };
 
-        let fs_item = self.tabs.selected_tab_ref().selected_item();
-        let mut right_pane = RightPane::DirList(None);
+        let loading = dir_list.is_loading();
+        let selected_tab = self.tabs.selected_tab_ref_mut();
+        let sort = self
+            .sort_settings
+            .for_directory(selected_tab.sort, &directory);
+        selected_tab.select(directory.clone(), dir_list, sort);
+        if loading {
+            self.select_after_refresh(&directory, &name);
+        } else {
+            selected_tab.select_name(&name);
+        }
+    }
 
-        if let Some(fs_item) = fs_item {
-            right_pane = match fs_item {
-                FileSystemItem::Folder(folder) => {
-                    if helper_functions::can_read_directory(&folder.path) {
-                        RightPane::DirList(Some(self.get_dirlist(&folder.path).unwrap().clone()))
-                    } else {
-                        RightPane::PermissionDenied
-                    }
-                }
-                _ => RightPane::DirList(None),
+    // Asks for the regex to search the files below the working directory for
+    pub fn begin_grep(&mut self) {
+        if !vfs::resolve(&self.working_directory()).is_ok_and(|provider| provider.is_local()) {
+            self.set_status_error(String::from("Searching contents needs a local directory"));
+            self.redraw();
+            return;
+        }
+        self.open_prompt(Prompt::new(PromptKind::Grep));
+    }
+
+    fn start_grep(&mut self, pattern: &str) {
+        if pattern.is_empty() {
+            return;
+        }
+        let root = self.working_directory();
+        let show_hidden = self.preferences.show_hidden;
+        match Search::start(root, pattern, show_hidden, self.app_event_tx.clone()) {
+            Ok(search) => {
+                self.search = Some(search);
+                self.mode = Mode::Grep;
+            }
+            Err(e) => self.set_status_error(format!("Invalid regex: {}", e)),
+        }
+    }
+
+    pub fn handle_grep_batch(&mut self, batch: GrepBatch) {
+        let search = match &mut self.search {
+            Some(search) => search,
+            None => return,
+        };
+        if search.accept(batch) && !search.running {
+            let message = if search.is_truncated() {
+                format!("Stopped after {} hits", search.hits.len())
+            } else {
+                format!("{} hits", search.hits.len())
             };
+            self.set_status_message(message);
         }
+    }
 
-        ThreePaneLayoutState::new(left_pane, right_pane)
+    pub fn search_mut(&mut self) -> Option<&mut Search> {
+        self.search.as_mut()
     }
 
-    pub fn draw(&mut self, mut state: ThreePaneLayoutState) {
-        let terminal = &mut self.terminal;
-        let ui = self.tabs.selected_tab_ref().ui.clone();
+    // Stops a running search, keeping the hits found so far
+    pub fn stop_grep(&mut self) {
+        if let Some(search) = self.search.as_mut().filter(|search| search.running) {
+            search.cancel();
+            let message = format!("Search stopped, {} hits", search.hits.len());
+            self.set_status_message(message);
+        }
+        self.redraw();
+    }
 
-        let _ = terminal.draw(|f| f.render_stateful_widget(ui, f.size(), &mut state));
+    // Esc stops a running search first, the results close once it is done
+    pub fn close_grep(&mut self) {
+        if self.search.as_ref().is_some_and(|search| search.running) {
+            self.stop_grep();
+            return;
+        }
+        self.search = None;
+        self.mode = Mode::Normal;
+        self.redraw();
+    }
+
+    // Opens the selected hit in $EDITOR at its line
+    pub fn edit_grep_hit(&mut self) {
+        let (path, line) = match self
+            .search
+            .as_ref()
+            .and_then(|search| search.selected_hit())
+        {
+            Some(hit) => (hit.path.clone(), hit.line),
+            None => return,
+        };
+
+        // $EDITOR may carry arguments, so let the shell split it. Editors commonly take +N as
+        // the line to start on
+        let editor = env::var("EDITOR").unwrap_or_else(|_| String::from("vi"));
+        let status = self.run_foreground(
+            Command::new("sh")
+                .arg("-c")
+                .arg(format!("{} +{} \"$1\"", editor, line))
+                .arg("sh")
+                .arg(&path),
+        );
+        match status {
+            Ok(status) if !status.success() => {
+                self.set_status_error(format!("Editor exited with {}", status))
+            }
+            Err(e) => self.set_status_error(format!("{:#}", e)),
+            Ok(_) => (),
+        }
+        if let Some(search) = &mut self.search {
+            search.forget_context();
+        }
+        self.redraw();
+    }
+
+    fn apply_filter(&mut self, kind: FilterKind, pattern: &str) {
+        if pattern.is_empty() {
+            if let Some(mid_pane) = self.mid_pane_mut() {
+                mid_pane.clear_filter();
+            }
+            return;
+        }
+
+        match Filter::new(kind, pattern) {
+            Ok(filter) => {
+                if let Some(mid_pane) = self.mid_pane_mut() {
+                    mid_pane.set_filter(Some(filter));
+                    let message =
+                        format!("{} of {} items match", mid_pane.len(), mid_pane.items.len());
+                    self.set_status_message(message);
+                }
+            }
+            Err(e) => {
+                self.set_status_error(format!("Invalid {} '{}': {}", kind.label(), pattern, e))
+            }
+        }
+    }
+
+    pub fn begin_mark_matching(&mut self) {
+        self.open_prompt(Prompt::new(PromptKind::MarkMatching));
+    }
+
+    pub fn mark_matching(&mut self, pattern: &str) {
+        let pattern = match glob::Pattern::new(pattern) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                self.set_status_error(format!("Invalid glob '{}': {}", pattern, e));
+                return;
+            }
+        };
+
+        if let Some(mid_pane) = self.mid_pane_mut() {
+            let count = mid_pane.mark_matching(&pattern);
+            self.set_status_message(format!("Marked {} items", count));
+        }
+    }
+
+    fn working_directory(&self) -> PathBuf {
+        self.tabs.selected_tab_ref().working_directory.clone()
+    }
+
+    fn open_prompt(&mut self, prompt: Prompt) {
+        self.mode = Mode::Prompt(prompt);
+        self.redraw();
+    }
+
+    pub fn begin_rename(&mut self) {
+        if !self.ensure_writable(&self.working_directory()) {
+            return;
+        }
+        if let Some(item) = self.tabs.selected_tab_ref().selected_item() {
+            let kind = PromptKind::Rename(item.path().to_path_buf());
+            self.open_prompt(Prompt::with_input(kind, item.name().to_string()));
+        }
+    }
+
+    pub fn begin_make_directory(&mut self) {
+        if !self.ensure_writable(&self.working_directory()) {
+            return;
+        }
+        self.open_prompt(Prompt::new(PromptKind::MakeDirectory));
+    }
+
+    pub fn begin_make_file(&mut self) {
+        if !self.ensure_writable(&self.working_directory()) {
+            return;
+        }
+        self.open_prompt(Prompt::new(PromptKind::MakeFile));
+    }
+
+    pub fn begin_copy(&mut self) {
+        let paths = self.selected_paths();
+        if !paths.is_empty() {
+            let input = self.working_directory().display().to_string();
+            self.open_prompt(Prompt::with_input(PromptKind::CopyTo(paths), input));
+        }
+    }
+
+    pub fn begin_move(&mut self) {
+        if !self.ensure_writable(&self.working_directory()) {
+            return;
+        }
+        let paths = self.selected_paths();
+        if !paths.is_empty() {
+            let input = self.working_directory().display().to_string();
+            self.open_prompt(Prompt::with_input(PromptKind::MoveTo(paths), input));
+        }
+    }
+
+    // Proposes a zip named after the item, or after the directory when several are selected
+    pub fn begin_compress(&mut self) {
+        if !self.ensure_writable(&self.working_directory()) {
+            return;
+        }
+        let paths = self.selected_paths();
+        let name = match paths.as_slice() {
+            [] => return,
+            [path] => path.file_name(),
+            _ => self.tabs.selected_tab_ref().working_directory.file_name(),
+        };
+        let input = format!(
+            "{}.zip",
+            name.map_or(String::from("archive"), |name| name
+                .to_string_lossy()
+                .to_string())
+        );
+        self.open_prompt(Prompt::with_input(PromptKind::CompressTo(paths), input));
+    }
+
+    pub fn begin_extract(&mut self) {
+        let archive = match self.tabs.selected_tab_ref().selected_item() {
+            Some(FileSystemItem::File(file)) if archive::is_archive(&file.path) => file.path,
+            Some(item) => {
+                self.set_status_error(format!(
+                    "{} is not an archive",
+                    describe_paths(&[item.path().to_path_buf()])
+                ));
+                self.redraw();
+                return;
+            }
+            None => return,
+        };
+        let input = archive::default_extract_target(&archive)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.open_prompt(Prompt::with_input(PromptKind::ExtractTo(archive), input));
+    }
+
+    pub fn begin_delete(&mut self) {
+        if !self.ensure_writable(&self.working_directory()) {
+            return;
+        }
+        let paths = self.selected_paths();
+        if !paths.is_empty() {
+            self.open_prompt(Prompt::new(PromptKind::ConfirmDelete(paths)));
+        }
+    }
+
+    pub fn trash_selected(&mut self) {
+        let working_directory = self.working_directory();
+        if !self.ensure_writable(&working_directory) {
+            return;
+        }
+        // The trash is a local directory, remote items can only be deleted
+        if vfs::resolve(&working_directory).is_ok_and(|provider| !provider.is_local()) {
+            self.set_status_error(format!(
+                "{} is not local, use Delete to remove items permanently",
+                describe_paths(&[working_directory])
+            ));
+            self.redraw();
+            return;
+        }
+        let paths = self.selected_paths();
+        if paths.is_empty() {
+            return;
+        }
+
+        let tasks = paths.iter().cloned().map(JobTask::Trash).collect();
+        self.jobs
+            .submit(format!("Trash {}", describe_paths(&paths)), tasks);
+        self.redraw();
     }
+
+    pub fn open_trash(&mut self) {
+        match load_trash_view() {
+            Ok(trash_view) => {
+                self.trash_view = Some(trash_view);
+                self.mode = Mode::Trash;
+            }
+            Err(e) => self.set_status_error(format!("Failed to open trash: {}", e)),
+        }
+        self.redraw();
+    }
+
+    pub fn close_trash(&mut self) {
+        self.trash_view = None;
+        self.mode = Mode::Normal;
+        self.redraw();
+    }
+
+    // Rereads the trash while keeping the cursor position and marks
+    fn reload_trash_view(&mut self) {
+        let old_view = match self.trash_view.take() {
+            Some(trash_view) => trash_view,
+            None => return,
+        };
+
+        if let Ok(mut trash_view) = load_trash_view() {
+            trash_view.list.marked = old_view.list.marked;
+            trash_view.list.retain_existing_marks();
+            trash_view.list.select(
+                old_view
+                    .list
+                    .state
+                    .min(trash_view.list.items.len().saturating_sub(1)),
+            );
+            self.trash_view = Some(trash_view);
+        } else {
+            self.trash_view = Some(old_view);
+        }
+    }
+
+    pub fn trash_list_mut(&mut self) -> Option<&mut DirSelectionList> {
+        self.trash_view
+            .as_mut()
+            .map(|trash_view| &mut trash_view.list)
+    }
+
+    fn trash_target_names(&mut self) -> Vec<String> {
+        match &mut self.trash_view {
+            Some(trash_view) => {
+                let names = trash_view.target_names();
+                trash_view.list.end_visual(false);
+                names
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn restore_from_trash(&mut self) {
+        let tasks: Vec<JobTask> = self
+            .trash_target_names()
+            .into_iter()
+            .filter_map(|name| {
+                let original_path = trash::read_entry(&name).original_path?;
+                Some(JobTask::Restore {
+                    name,
+                    original_path,
+                })
+            })
+            .collect();
+
+        if tasks.is_empty() {
+            self.set_status_error(String::from("Nothing to restore"));
+        } else {
+            let count = tasks.len();
+            self.jobs
+                .submit(format!("Restore {} items from trash", count), tasks);
+        }
+        self.redraw();
+    }
+
+    pub fn begin_purge(&mut self) {
+        let names = self.trash_target_names();
+        if !names.is_empty() {
+            self.open_prompt(Prompt::new(PromptKind::ConfirmPurge(names)));
+        }
+    }
+
+    fn purge_from_trash(&mut self, names: Vec<String>) {
+        let description = format!("Purge {} items from trash", names.len());
+        let tasks = names.into_iter().map(JobTask::Purge).collect();
+        self.jobs.submit(description, tasks);
+    }
+
+    pub fn prompt_mut(&mut self) -> Option<&mut Prompt> {
+        match &mut self.mode {
+            Mode::Prompt(prompt) => Some(prompt),
+            _ => None,
+        }
+    }
+
+    // The mode to fall back to once a prompt is done
+    fn idle_mode(&self) -> Mode {
+        if self.trash_view.is_some() {
+            Mode::Trash
+        } else {
+            Mode::Normal
+        }
+    }
+
+    pub fn cancel_prompt(&mut self) {
+        self.mode = self.idle_mode();
+        self.redraw();
+    }
+
+    pub fn submit_prompt(&mut self) {
+        let idle_mode = self.idle_mode();
+        let prompt = match std::mem::replace(&mut self.mode, idle_mode) {
+            Mode::Prompt(prompt) => prompt,
+            mode => {
+                self.mode = mode;
+                return;
+            }
+        };
+
+        let working_directory = self.working_directory();
+        let input = prompt.input.trim().to_string();
+
+        match prompt.kind {
+            PromptKind::Rename(path) => self.rename_item(&path, &input),
+            PromptKind::MakeDirectory => self.make_directory(&working_directory, &input),
+            PromptKind::MakeFile => self.make_file(&working_directory, &input),
+            kind @ (PromptKind::CopyTo(_)
+            | PromptKind::MoveTo(_)
+            | PromptKind::CompressTo(_)
+            | PromptKind::ExtractTo(_)
+            | PromptKind::OpenTab) => self.submit_destination(kind, &input),
+            PromptKind::ConfirmDelete(paths) => self.delete_items(&paths),
+            PromptKind::ConfirmPurge(names) => self.purge_from_trash(names),
+            PromptKind::MarkMatching => self.mark_matching(&input),
+            PromptKind::Filter(kind) => self.apply_filter(kind, &input),
+            PromptKind::Find => self.finish_find(),
+            PromptKind::Grep => self.start_grep(&prompt.input),
+            PromptKind::PasteConflict(plan) => self.continue_paste(plan),
+            PromptKind::ConfirmBulkRename(bulk_rename) => self.apply_bulk_rename(bulk_rename),
+        }
+
+        self.redraw();
+    }
+
+    pub fn yank(&mut self, intent: ClipboardIntent) {
+        if intent == ClipboardIntent::Cut && !self.ensure_writable(&self.working_directory()) {
+            return;
+        }
+        let paths = self.selected_paths();
+        if paths.is_empty() {
+            return;
+        }
+
+        let verb = match intent {
+            ClipboardIntent::Copy => "Yanked",
+            ClipboardIntent::Cut => "Cut",
+        };
+        self.set_status_message(format!("{} {}", verb, describe_paths(&paths)));
+        self.clipboard = Some(Clipboard { paths, intent });
+        self.redraw();
+    }
+
+    pub fn paste(&mut self, policy: ConflictPolicy) {
+        if !self.ensure_writable(&self.working_directory()) {
+            return;
+        }
+        let clipboard = match &self.clipboard {
+            Some(clipboard) => clipboard,
+            None => {
+                self.set_status_error(String::from("Clipboard is empty"));
+                self.redraw();
+                return;
+            }
+        };
+
+        let plan = PastePlan::new(clipboard, &self.working_directory(), policy);
+        self.continue_paste(plan);
+        self.redraw();
+    }
+
+    // Asks about the next conflict, or runs the paste once every conflict is resolved
+    fn continue_paste(&mut self, plan: PastePlan) {
+        if plan.next_conflict().is_some() {
+            self.mode = Mode::Prompt(Prompt::new(PromptKind::PasteConflict(plan)));
+        } else {
+            self.execute_paste(plan);
+        }
+    }
+
+    pub fn resolve_paste_conflict(&mut self, policy: ConflictPolicy, apply_to_all: bool) {
+        let mut plan = match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Prompt(Prompt {
+                kind: PromptKind::PasteConflict(plan),
+                ..
+            }) => plan,
+            mode => {
+                self.mode = mode;
+                return;
+            }
+        };
+
+        if apply_to_all {
+            plan.resolve_all(policy);
+        } else {
+            plan.resolve_next(policy);
+        }
+        self.continue_paste(plan);
+        self.redraw();
+    }
+
+    fn execute_paste(&mut self, plan: PastePlan) {
+        let (verb, tasks): (&str, Vec<JobTask>) = match plan.intent {
+            ClipboardIntent::Copy => (
+                "Paste",
+                plan.entries
+                    .into_iter()
+                    .map(|entry| JobTask::Copy {
+                        source: entry.source,
+                        target: entry.target,
+                        overwrite: entry.overwrite,
+                    })
+                    .collect(),
+            ),
+            ClipboardIntent::Cut => {
+                // Moved items are gone from their old location, so they cannot be pasted again
+                self.clipboard = None;
+                (
+                    "Move",
+                    plan.entries
+                        .into_iter()
+                        .map(|entry| JobTask::Move {
+                            source: entry.source,
+                            target: entry.target,
+                            overwrite: entry.overwrite,
+                        })
+                        .collect(),
+                )
+            }
+        };
+
+        if tasks.is_empty() {
+            self.set_status_message(String::from("Nothing to paste"));
+            return;
+        }
+
+        let description = format!(
+            "{} {} into '{}'",
+            verb,
+            describe_task_sources(&tasks),
+            plan.destination.display()
+        );
+        self.jobs.submit(description, tasks);
+    }
+
+    pub fn new_tab(&mut self) {
+        let working_directory = self.working_directory();
+        let sort = self.tabs.selected_tab_ref().sort;
+        if let Ok(dir_list) = self.get_dirlist(&working_directory) {
+            let dir_list = dir_list.clone();
+            let show_hidden = self.preferences.show_hidden;
+            self.tabs
+                .open(Tab::new(working_directory, dir_list, sort, show_hidden));
+        }
+        self.redraw();
+    }
+
+    pub fn begin_open_tab(&mut self) {
+        self.open_prompt(Prompt::new(PromptKind::OpenTab));
+    }
+
+    // Opens a directory or a connected sftp:// url in a new tab
+    fn open_tab(&mut self, input: &str, path: PathBuf) {
+        match self.get_dirlist(&path) {
+            Ok(dir_list) => {
+                let dir_list = dir_list.clone();
+                let sort = self
+                    .sort_settings
+                    .for_directory(self.sort_settings.default, &path);
+                let show_hidden = self.preferences.show_hidden;
+                self.tabs.open(Tab::new(path, dir_list, sort, show_hidden));
+            }
+            Err(e) => {
+                self.set_status_error(format!("Failed to open {}: {}", input, e.root_cause()))
+            }
+        }
+    }
+
+    // Prompt input naming a path, an sftp:// url connects to its host on a worker first and the
+    // prompt's action goes on once it did
+    fn submit_destination(&mut self, kind: PromptKind, input: &str) {
+        if !sftp::is_url(input) {
+            let path = helper_functions::resolve_path(&self.working_directory(), input);
+            self.continue_with_destination(kind, input, path);
+            return;
+        }
+
+        self.clear_status();
+        if !self.connecting.iter().any(|(url, _)| url == input) {
+            sftp::open_in_background(input, self.app_event_tx.clone());
+        }
+        self.connecting.push((input.to_string(), kind));
+    }
+
+    fn continue_with_destination(&mut self, kind: PromptKind, input: &str, path: PathBuf) {
+        match kind {
+            PromptKind::CopyTo(paths) => self.copy_items(&paths, &path),
+            PromptKind::MoveTo(paths) => self.move_items(&paths, &path),
+            PromptKind::CompressTo(paths) => self.compress_items(paths, path),
+            PromptKind::ExtractTo(archive) => self.extract_archive(archive, path),
+            PromptKind::OpenTab => self.open_tab(input, path),
+            _ => {}
+        }
+    }
+
+    pub fn handle_connected(&mut self, connected: Connected) {
+        let (pending, connecting) = std::mem::take(&mut self.connecting)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(url, _)| *url == connected.url);
+        self.connecting = connecting;
+
+        match connected.result {
+            Ok(path) => {
+                for (_, kind) in pending {
+                    self.continue_with_destination(kind, &connected.url, path.clone());
+                }
+            }
+            Err(e) => self.set_status_error(format!("Failed to open {}: {}", connected.url, e)),
+        }
+    }
+
+    pub fn next_tab(&mut self) {
+        self.tabs.select_next();
+        self.redraw();
+    }
+
+    pub fn previous_tab(&mut self) {
+        self.tabs.select_previous();
+        self.redraw();
+    }
+
+    pub fn close_tab(&mut self) {
+        if let Err(e) = self.tabs.close_selected() {
+            self.set_status_error(e.to_string());
+        }
+        self.redraw();
+    }
+
+    // Hands the terminal to `command` until it exits
+    fn run_foreground(&mut self, command: &mut Command) -> Result<ExitStatus> {
+        self.input_control.suspend();
+        ui::functions::restore_terminal(&mut self.terminal)
+            .context("[AppBackend.run_foreground()] Failed to restore terminal")?;
+
+        let status = command.status();
+
+        self.terminal = ui::functions::setup_terminal()
+            .context("[AppBackend.run_foreground()] Failed to setup terminal")?;
+        self.input_control.resume();
+        status.context("[AppBackend.run_foreground()] Failed to run command")
+    }
+
+    pub fn open_file(&mut self, path: &Path) {
+        let openers = Openers::load().unwrap_or_else(|e| {
+            self.set_status_error(format!("{:#}", e));
+            Openers::default()
+        });
+
+        let mut rules = openers.matching(path);
+        if rules.len() > 1 {
+            rules.push(OpenerRule::fallback());
+            self.mode = Mode::OpenWith(OpenWithMenu {
+                path: path.to_path_buf(),
+                rules,
+                selected: 0,
+            });
+        } else {
+            let rule = rules.pop().unwrap_or_else(OpenerRule::fallback);
+            self.run_opener(&rule, path);
+        }
+    }
+
+    fn run_opener(&mut self, rule: &OpenerRule, path: &Path) {
+        let result = if rule.terminal {
+            self.run_foreground(&mut rule.command(path))
+                .and_then(|status| {
+                    if status.success() {
+                        Ok(())
+                    } else {
+                        Err(anyhow::anyhow!("'{}' exited with {}", rule.command, status))
+                    }
+                })
+        } else {
+            rule.spawn_detached(path)
+        };
+
+        if let Err(e) = result {
+            self.set_status_error(format!("{:#}", e));
+        }
+        // Terminal programs may have edited, created or removed files
+        if let (true, Some(parent)) = (rule.terminal, path.parent()) {
+            self.refresh_dirlist(parent);
+        }
+    }
+
+    pub fn open_with_mut(&mut self) -> Option<&mut OpenWithMenu> {
+        match &mut self.mode {
+            Mode::OpenWith(menu) => Some(menu),
+            _ => None,
+        }
+    }
+
+    pub fn open_with_selected(&mut self) {
+        if let Mode::OpenWith(menu) = std::mem::replace(&mut self.mode, Mode::Normal) {
+            if let Some(rule) = menu.rules.get(menu.selected) {
+                self.run_opener(rule, &menu.path);
+            }
+        }
+        self.redraw();
+    }
+
+    pub fn close_open_with(&mut self) {
+        self.mode = Mode::Normal;
+        self.redraw();
+    }
+
+    // Marked items if there are any, otherwise everything in the current directory
+    pub fn begin_bulk_rename(&mut self) {
+        if !self.ensure_writable(&self.working_directory()) {
+            return;
+        }
+        let has_marks = self
+            .mid_pane_mut()
+            .is_some_and(|mid_pane| mid_pane.marked_count() > 0);
+        let directory = self.working_directory();
+        let names: Vec<String> = if has_marks {
+            self.selected_paths()
+                .iter()
+                .filter_map(|path| path.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .collect()
+        } else {
+            match self.get_dirlist(&directory) {
+                Ok(dir_list) if dir_list.is_loading() => {
+                    self.set_status_error(format!(
+                        "{} is still loading",
+                        describe_paths(std::slice::from_ref(&directory))
+                    ));
+                    Vec::new()
+                }
+                Ok(dir_list) => dir_list.names().map(str::to_string).collect(),
+                Err(_) => Vec::new(),
+            }
+        };
+
+        if !names.is_empty() {
+            match self.edit_names(&directory, &names) {
+                Ok(bulk_rename) if bulk_rename.is_empty() => {
+                    self.set_status_message(String::from("No names changed"))
+                }
+                Ok(bulk_rename) => {
+                    self.open_prompt(Prompt::new(PromptKind::ConfirmBulkRename(bulk_rename)))
+                }
+                Err(e) => self.set_status_error(format!("Bulk rename: {}", e)),
+            }
+        }
+        self.redraw();
+    }
+
+    fn edit_names(&mut self, directory: &Path, names: &[String]) -> Result<BulkRename> {
+        if let Some(name) = names.iter().find(|name| name.contains('\n')) {
+            anyhow::bail!("'{}' contains a newline", name.escape_debug());
+        }
+
+        // In a directory only we can enter and with an unpredictable name, others could otherwise
+        // plant a link at the name or swap the file while the editor runs. It is removed on drop
+        let private_directory = tempfile::Builder::new()
+            .prefix("rstuifm-bulk-rename-")
+            .tempdir()
+            .context("[AppBackend.edit_names()] Failed to create a temporary directory")?;
+        let file_path = private_directory.path().join("names.txt");
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&file_path)
+            .and_then(|mut file| file.write_all((names.join("\n") + "\n").as_bytes()))
+            .context("[AppBackend.edit_names()] Failed to write names")?;
+
+        // $EDITOR may carry arguments, so let the shell split it
+        let editor = env::var("EDITOR").unwrap_or_else(|_| String::from("vi"));
+        let status = self.run_foreground(
+            Command::new("sh")
+                .arg("-c")
+                .arg(format!("{} \"$1\"", editor))
+                .arg("sh")
+                .arg(&file_path),
+        );
+        let edited = fs::read_to_string(&file_path);
+        drop(private_directory);
+
+        if !status?.success() {
+            anyhow::bail!("editor exited with an error");
+        }
+        let edited = edited.context("[AppBackend.edit_names()] Failed to read names")?;
+        BulkRename::new(directory, names, &edited)
+    }
+
+    fn apply_bulk_rename(&mut self, bulk_rename: BulkRename) {
+        let (operations, error) = bulk_rename.apply();
+        let description = format!("Bulk rename {} items", bulk_rename.len());
+
+        if !operations.is_empty() {
+            self.journal.record(JournalEntry {
+                description: description.clone(),
+                operations,
+            });
+            self.save_journal();
+        }
+
+        if let Some(mid_pane) = self.mid_pane_mut() {
+            mid_pane.clear_marks();
+        }
+        self.evict_dirlist(&bulk_rename.directory);
+        self.refresh_dirlist(&bulk_rename.directory);
+
+        match error {
+            Some(e) => self.set_status_error(format!("{}: {}", description, e)),
+            None => self.set_status_message(format!("Done: {}", description)),
+        }
+    }
+
+    pub fn rename_item(&mut self, path: &Path, new_name: &str) {
+        match file_operations::rename(path, new_name) {
+            Ok(new_path) => {
+                self.record_operation(
+                    format!(
+                        "Rename {} to '{}'",
+                        describe_paths(&[path.to_path_buf()]),
+                        new_name
+                    ),
+                    Operation::rename(path.to_path_buf(), new_path.clone()),
+                );
+                self.evict_dirlist(path);
+                if let Some(parent) = new_path.parent() {
+                    self.refresh_dirlist(parent);
+                    self.select_after_refresh(parent, new_name);
+                }
+                self.set_status_message(format!(
+                    "Renamed {} to '{}'",
+                    describe_paths(&[path.to_path_buf()]),
+                    new_name
+                ));
+            }
+            Err(e) => self.set_status_error(e.to_string()),
+        }
+    }
+
+    pub fn make_directory(&mut self, parent: &Path, name: &str) {
+        match file_operations::make_directory(parent, name) {
+            Ok(path) => {
+                self.record_operation(
+                    format!("Create directory '{}'", name),
+                    Operation::make_directory(path),
+                );
+                self.refresh_dirlist(parent);
+                self.select_after_refresh(parent, name);
+                self.set_status_message(format!("Created directory '{}'", name));
+            }
+            Err(e) => self.set_status_error(e.to_string()),
+        }
+    }
+
+    pub fn make_file(&mut self, parent: &Path, name: &str) {
+        match file_operations::make_file(parent, name) {
+            Ok(path) => {
+                self.record_operation(
+                    format!("Create file '{}'", name),
+                    Operation::make_file(path),
+                );
+                self.refresh_dirlist(parent);
+                self.select_after_refresh(parent, name);
+                self.set_status_message(format!("Created file '{}'", name));
+            }
+            Err(e) => self.set_status_error(e.to_string()),
+        }
+    }
+
+    pub fn copy_items(&mut self, paths: &[PathBuf], destination: &Path) {
+        if !self.ensure_writable(destination) {
+            return;
+        }
+        let tasks: Vec<JobTask> = paths
+            .iter()
+            .filter_map(|path| {
+                path.file_name().map(|name| JobTask::Copy {
+                    source: path.clone(),
+                    target: destination.join(name),
+                    overwrite: false,
+                })
+            })
+            .collect();
+        let description = format!(
+            "Copy {} to '{}'",
+            describe_paths(paths),
+            destination.display()
+        );
+        self.jobs.submit(description, tasks);
+    }
+
+    pub fn move_items(&mut self, paths: &[PathBuf], destination: &Path) {
+        if !self.ensure_writable(destination) {
+            return;
+        }
+        let tasks: Vec<JobTask> = paths
+            .iter()
+            .filter_map(|path| {
+                path.file_name().map(|name| JobTask::Move {
+                    source: path.clone(),
+                    target: destination.join(name),
+                    overwrite: false,
+                })
+            })
+            .filter(
+                |task| !matches!(task, JobTask::Move { source, target, .. } if source == target),
+            )
+            .collect();
+        let description = format!(
+            "Move {} to '{}'",
+            describe_paths(paths),
+            destination.display()
+        );
+        self.jobs.submit(description, tasks);
+    }
+
+    pub fn compress_items(&mut self, sources: Vec<PathBuf>, target: PathBuf) {
+        if !self.ensure_writable(target.parent().unwrap_or(&target)) {
+            return;
+        }
+        let description = format!(
+            "Compress {} into '{}'",
+            describe_paths(&sources),
+            target.display()
+        );
+        self.jobs
+            .submit(description, vec![JobTask::Compress { sources, target }]);
+    }
+
+    pub fn extract_archive(&mut self, archive: PathBuf, target: PathBuf) {
+        if !self.ensure_writable(target.parent().unwrap_or(&target)) {
+            return;
+        }
+        let description = format!(
+            "Extract {} to '{}'",
+            describe_paths(std::slice::from_ref(&archive)),
+            target.display()
+        );
+        self.jobs
+            .submit(description, vec![JobTask::Extract { archive, target }]);
+    }
+
+    pub fn delete_items(&mut self, paths: &[PathBuf]) {
+        let tasks = paths.iter().cloned().map(JobTask::Delete).collect();
+        self.jobs
+            .submit(format!("Delete {}", describe_paths(paths)), tasks);
+    }
+
+    pub fn handle_job_event(&mut self, event: JobEvent) {
+        let finished = match self.jobs.handle_event(event) {
+            Some(finished) => finished,
+            None => return,
+        };
+
+        for task in &finished.tasks {
+            for path in task.invalidated_paths() {
+                self.forget_preview(&path);
+                self.evict_dirlist(&path);
+            }
+        }
+        let mut directories: Vec<PathBuf> = finished
+            .tasks
+            .iter()
+            .flat_map(|task| task.affected_directories())
+            .collect();
+        directories.sort();
+        directories.dedup();
+        for directory in directories {
+            self.refresh_dirlist(&directory);
+        }
+
+        if finished.tasks.iter().any(JobTask::touches_trash) {
+            self.reload_trash_view();
+        }
+
+        if let Mode::JobPanel(selected) = &mut self.mode {
+            *selected = (*selected).min(self.jobs.len().saturating_sub(1));
+        }
+
+        self.update_journal(finished.id, &finished.description, finished.operations);
+
+        match finished.error {
+            Some(FileOperationError::Cancelled) => {
+                self.set_status_message(format!("Cancelled: {}", finished.description))
+            }
+            Some(e) => self.set_status_error(format!("{}: {}", finished.description, e)),
+            None if !finished.skipped.is_empty() => self.set_status_error(format!(
+                "Done: {}, skipped {}, not a file, directory or link",
+                finished.description,
+                describe_paths(&finished.skipped)
+            )),
+            None => self.set_status_message(format!("Done: {}", finished.description)),
+        }
+    }
+
+    fn record_operation(&mut self, description: String, operation: Operation) {
+        self.journal.record(JournalEntry {
+            description,
+            operations: vec![operation],
+        });
+        self.save_journal();
+    }
+
+    fn save_journal(&mut self) {
+        if let Err(e) = self.journal.save() {
+            self.set_status_error(e.to_string());
+        }
+    }
+
+    // Operations that could not be undone or redone are dropped, their items changed underneath us
+    fn update_journal(&mut self, job_id: usize, description: &str, operations: Vec<Operation>) {
+        match self.journal_jobs.remove(&job_id) {
+            Some(JournalJob::Undo(description)) => {
+                if !operations.is_empty() {
+                    // Undo ran back to front, redo runs front to back again
+                    let operations = operations.into_iter().rev().collect();
+                    self.journal.push_redo(JournalEntry {
+                        description,
+                        operations,
+                    });
+                }
+            }
+            Some(JournalJob::Redo(description)) => {
+                if !operations.is_empty() {
+                    self.journal.push_undo(JournalEntry {
+                        description,
+                        operations,
+                    });
+                }
+            }
+            None if !operations.is_empty() => self.journal.record(JournalEntry {
+                description: description.to_string(),
+                operations,
+            }),
+            None => return,
+        }
+        self.save_journal();
+    }
+
+    pub fn undo(&mut self) {
+        let entry = match self.journal.pop_undo() {
+            Some(entry) => entry,
+            None => {
+                self.set_status_message("Nothing to undo".to_string());
+                return self.redraw();
+            }
+        };
+        self.save_journal();
+
+        let tasks = entry
+            .operations
+            .into_iter()
+            .rev()
+            .map(JobTask::Undo)
+            .collect();
+        let id = self
+            .jobs
+            .submit(format!("Undo {}", entry.description), tasks);
+        self.journal_jobs
+            .insert(id, JournalJob::Undo(entry.description));
+        self.redraw();
+    }
+
+    pub fn redo(&mut self) {
+        let entry = match self.journal.pop_redo() {
+            Some(entry) => entry,
+            None => {
+                self.set_status_message("Nothing to redo".to_string());
+                return self.redraw();
+            }
+        };
+        self.save_journal();
+
+        let tasks = entry.operations.into_iter().map(JobTask::Redo).collect();
+        let id = self
+            .jobs
+            .submit(format!("Redo {}", entry.description), tasks);
+        self.journal_jobs
+            .insert(id, JournalJob::Redo(entry.description));
+        self.redraw();
+    }
+
+    pub fn open_job_panel(&mut self) {
+        self.mode = Mode::JobPanel(0);
+        self.redraw();
+    }
+
+    pub fn close_job_panel(&mut self) {
+        self.mode = Mode::Normal;
+        self.redraw();
+    }
+
+    pub fn select_job(&mut self, offset: isize) {
+        let len = self.jobs.len();
+        if let Mode::JobPanel(selected) = &mut self.mode {
+            if len > 0 {
+                *selected = (*selected as isize + offset).rem_euclid(len as isize) as usize;
+            }
+        }
+        self.redraw();
+    }
+
+    fn selected_job_id(&self) -> Option<usize> {
+        match self.mode {
+            Mode::JobPanel(selected) => self.jobs.jobs().get(selected).map(|job| job.id),
+            _ => None,
+        }
+    }
+
+    pub fn toggle_pause_selected_job(&mut self) {
+        if let Some(id) = self.selected_job_id() {
+            self.jobs.toggle_pause(id);
+        }
+        self.redraw();
+    }
+
+    pub fn cancel_selected_job(&mut self) {
+        if let Some(id) = self.selected_job_id() {
+            self.jobs.cancel(id);
+        }
+        self.redraw();
+    }
+
+    // Keeps inotify on the local directories on screen, the other tabs included, and stops reading
+    // directories that went off screen. Listings that are no longer watched could go stale, so they
+    // are dropped from the cache
+    fn track_panes(&mut self, parent_dir: Option<&Path>) {
+        let selected_folder = match self.tabs.selected_tab_ref().selected_item() {
+            Some(FileSystemItem::Folder(folder)) => Some(folder.path),
+            _ => None,
+        };
+        let shown: Vec<PathBuf> = self
+            .tabs
+            .iter()
+            .map(|tab| tab.working_directory.clone())
+            .chain(parent_dir.map(Path::to_path_buf))
+            .chain(selected_folder)
+            .collect();
+
+        for path in self.dir_loader.retain(&shown) {
+            self.reloads.remove(&path);
+            if self
+                .dirlist_cache
+                .get(&path)
+                .is_some_and(|dir_list| dir_list.is_loading())
+            {
+                self.dirlist_cache.remove(&path);
+            }
+        }
+        self.dirlist_errors.retain(|path, _| shown.contains(path));
+
+        // A tab keeps showing a listing that was dropped from the cache until it is read again
+        let working_directories: Vec<PathBuf> = self
+            .tabs
+            .iter()
+            .map(|tab| tab.working_directory.clone())
+            .collect();
+        for path in working_directories {
+            if !self.dirlist_cache.contains_key(&path)
+                && !self.dirlist_errors.contains_key(&path)
+                && !self.dir_loader.is_loading(&path)
+            {
+                self.refresh_dirlist(&path);
+            }
+        }
+
+        let local: Vec<PathBuf> = shown
+            .into_iter()
+            .filter(|path| vfs::resolve(path).is_ok_and(|provider| provider.is_local()))
+            .collect();
+        for path in self.watcher.watch_only(&local) {
+            self.dirlist_cache.remove(&path);
+        }
+    }
+
+    pub fn get_new_state(&mut self) -> ThreePaneLayoutState {
+        let working_dir = {
+            let selected_tab_mut = self.tabs.selected_tab_ref_mut();
+            selected_tab_mut.working_directory.clone()
+        };
+        let parent_dir = vfs::parent(&working_dir).map(|path| path.to_path_buf());
+        self.track_panes(parent_dir.as_deref());
+
+        let left_pane = parent_dir
+            .as_ref()
+            .and_then(|path| self.sorted_dirlist(path).ok());
+
+        let fs_item = self.tabs.selected_tab_ref().selected_item();
+        let mut right_pane = RightPane::DirList(None);
+
+        if let Some(fs_item) = fs_item {
+            right_pane = match fs_item {
+                item if self.preview_mode == PreviewMode::Info => {
+                    match self.loaded_preview(PreviewKey::Info(item.path().to_path_buf())) {
+                        Some(LoadedPreview::Info(info)) => RightPane::Info(info),
+                        _ => RightPane::Loading,
+                    }
+                }
+                FileSystemItem::Folder(folder) => match self.sorted_dirlist(&folder.path) {
+                    Ok(dir_list) => RightPane::DirList(Some(dir_list)),
+                    Err(e) => match e.root_cause().downcast_ref::<io::Error>() {
+                        Some(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                            RightPane::PermissionDenied
+                        }
+                        _ => RightPane::Error(e.root_cause().to_string()),
+                    },
+                },
+                FileSystemItem::File(file) => {
+                    let key = match self.preview_mode {
+                        // Info is shown for every item above
+                        PreviewMode::Text | PreviewMode::Info => PreviewKey::Text(file.path),
+                        PreviewMode::Hex => PreviewKey::Hex {
+                            offset: self.hex_offset(&file.path),
+                            path: file.path,
+                            area: self.preview_area(),
+                        },
+                    };
+                    match self.loaded_preview(key) {
+                        Some(LoadedPreview::File(preview)) => {
+                            RightPane::File(self.highlighted(preview))
+                        }
+                        _ => RightPane::Loading,
+                    }
+                }
+                _ => RightPane::DirList(None),
+            };
+        }
+
+        let job_panel_selection = match self.mode {
+            Mode::JobPanel(selected) => Some(selected),
+            _ => None,
+        };
+
+        let tab_titles = self
+            .tabs
+            .iter()
+            .map(|tab| {
+                tab.working_directory
+                    .file_name()
+                    .map_or(String::from("/"), |name| name.to_string_lossy().to_string())
+            })
+            .collect();
+
+        let rename_preview = match &self.mode {
+            Mode::Prompt(Prompt {
+                kind: PromptKind::ConfirmBulkRename(bulk_rename),
+                ..
+            }) => Some(RenamePreview {
+                renames: bulk_rename.renames.clone(),
+            }),
+            _ => None,
+        };
+
+        let finder = match (&self.mode, &self.finder) {
+            (
+                Mode::Prompt(Prompt {
+                    kind: PromptKind::Find,
+                    ..
+                }),
+                Some(finder),
+            ) => {
+                let results = &finder.results;
+                Some(FinderPopup {
+                    title: format!(
+                        " Find in {} {}/{}{} ",
+                        finder.root.display(),
+                        results.matched,
+                        results.total,
+                        if results.walking {
+                            ", searching…"
+                        } else {
+                            ""
+                        }
+                    ),
+                    matches: results.matches.clone(),
+                    selected: finder.selected,
+                })
+            }
+            _ => None,
+        };
+
+        let open_with = match &self.mode {
+            Mode::OpenWith(menu) => Some(OpenWithPopup {
+                title: format!(
+                    " Open {} with ",
+                    describe_paths(std::slice::from_ref(&menu.path))
+                ),
+                labels: menu
+                    .rules
+                    .iter()
+                    .map(|rule| rule.label().to_string())
+                    .collect(),
+                selected: menu.selected,
+            }),
+            _ => None,
+        };
+
+        let height = self.preview_area().height as usize;
+        let grep_view = match (&self.mode, &mut self.search) {
+            (Mode::Grep, Some(search)) => {
+                let title = format!(
+                    " {} in {}: {} hits{} (Enter: edit, Esc: {}) ",
+                    search.pattern,
+                    search.root.display(),
+                    search.hits.len(),
+                    if search.running { ", searching…" } else { "" },
+                    if search.running { "stop" } else { "close" }
+                );
+                let context = search.context(height);
+                let root = search.root.clone();
+                let (hits, selected) = search.visible_hits(height);
+                Some(GrepView {
+                    title,
+                    root,
+                    hits: hits.to_vec(),
+                    selected,
+                    context,
+                })
+            }
+            _ => None,
+        };
+
+        ThreePaneLayoutState::new(left_pane, right_pane, self.status_line())
+            .with_tabs(tab_titles, self.tabs.selected_index())
+            .with_jobs(self.jobs.jobs(), job_panel_selection)
+            .with_trash_view(self.trash_view.clone())
+            .with_rename_preview(rename_preview)
+            .with_open_with(open_with)
+            .with_finder(finder)
+            .with_grep_view(grep_view)
+            .with_columns(self.columns.clone())
+    }
+
+    // The preview for `key`, None while it loads. Local items are read right away, only once per
+    // key, remote and archived ones on the previewer thread
+    fn loaded_preview(&mut self, key: PreviewKey) -> Option<LoadedPreview> {
+        if let Some((cached_key, preview)) = &self.preview_cache {
+            if *cached_key == key {
+                return Some(preview.clone());
+            }
+        }
+
+        if vfs::resolve(key.path()).is_ok_and(|provider| provider.is_local()) {
+            let preview = key.load();
+            self.preview_cache = Some((key, preview.clone()));
+            return Some(preview);
+        }
+        if self.preview_requested.as_ref() != Some(&key) {
+            self.preview_requested = Some(key.clone());
+            self.previewer.request(key);
+        }
+        None
+    }
+
+    fn highlighted(&mut self, mut preview: FilePreview) -> FilePreview {
+        if let PreviewContent::Text(lines) = &preview.content {
+            let key = preview.highlight_key();
+            match self.highlight_cache.get(&key) {
+                Some(highlighted) => preview.highlighted = highlighted.clone(),
+                None if self.highlight_requested.as_ref() != Some(&key) => {
+                    self.highlight_requested = Some(key.clone());
+                    self.highlighter.request(HighlightRequest {
+                        key,
+                        lines: lines.clone(),
+                    });
+                }
+                None => (),
+            }
+        }
+        preview
+    }
+
+    // Previews of items at or below `path` are read again on the next draw
+    fn forget_preview(&mut self, path: &Path) {
+        let cached = self.preview_cache.as_ref().map(|(key, _)| key.path());
+        if cached.is_some_and(|cached| cached.starts_with(path) || cached.parent() == Some(path)) {
+            self.preview_cache = None;
+        }
+    }
+
+    fn preview_area(&self) -> Rect {
+        let screen = self.terminal.size().unwrap_or_default();
+        right_pane_inner_area(screen, self.tabs.len() > 1)
+    }
+
+    fn hex_offset(&self, path: &Path) -> u64 {
+        match &self.hex_position {
+            Some((hex_path, offset)) if hex_path == path => *offset,
+            _ => 0,
+        }
+    }
+
+    pub fn cycle_preview_mode(&mut self) {
+        self.preview_mode = self.preview_mode.next();
+        self.redraw();
+    }
+
+    // Pages through the hex dump, other preview modes only show the start of the file
+    pub fn scroll_preview(&mut self, pages: i64) {
+        let path = match self.tabs.selected_tab_ref().selected_item() {
+            Some(FileSystemItem::File(file)) if self.preview_mode == PreviewMode::Hex => file.path,
+            _ => return,
+        };
+
+        // The loaded dump knows the size, asking again could mean a round trip to a remote host
+        let size = match &self.preview_cache {
+            Some((key, LoadedPreview::File(preview))) if key.path() == path => preview.size,
+            _ => vfs::metadata(&path).map_or(0, |metadata| metadata.len),
+        };
+        let area = self.preview_area();
+        let page_size = HexDump::page_size(area, size) as i64;
+        let offset = (self.hex_offset(&path) as i64 + pages * page_size).max(0) as u64;
+        let offset = HexDump::clamp_offset(offset, size, HexDump::bytes_per_row(area.width, size));
+
+        self.hex_position = Some((path, offset));
+        self.redraw();
+    }
+
+    pub fn handle_preview(&mut self, result: PreviewResult) {
+        // An answer to an older request is dropped, the newer one is on its way
+        if self.preview_requested.as_ref() == Some(&result.key) {
+            self.preview_requested = None;
+            self.preview_cache = Some((result.key, result.preview));
+        }
+    }
+
+    pub fn handle_highlight(&mut self, result: HighlightResult) {
+        if self.highlight_requested.as_ref() == Some(&result.key) {
+            self.highlight_requested = None;
+        }
+        if self.highlight_cache.len() >= HIGHLIGHT_CACHE_SIZE {
+            self.highlight_cache.clear();
+        }
+        self.highlight_cache.insert(result.key, result.lines);
+    }
+
+    pub fn redraw(&mut self) {
+        let state = self.get_new_state();
+        self.draw(state);
+    }
+
+    pub fn draw(&mut self, mut state: ThreePaneLayoutState) {
+        let terminal = &mut self.terminal;
+        let ui = self.tabs.selected_tab_ref().ui.clone();
+
+        let _ = terminal.draw(|f| f.render_stateful_widget(ui, f.size(), &mut state));
+    }
+}
+
+fn describe_task_sources(tasks: &[JobTask]) -> String {
+    let sources: Vec<PathBuf> = tasks
+        .iter()
+        .filter_map(|task| match task {
+            JobTask::Copy { source, .. } | JobTask::Move { source, .. } => Some(source.clone()),
+            _ => None,
+        })
+        .collect();
+    describe_paths(&sources)
+}
+
+fn load_trash_view() -> Result<TrashView> {
+    let files_directory = trash::files_directory();
+    std::fs::create_dir_all(&files_directory)
+        .context("[app_backend.load_trash_view()] Failed to create trash directory")?;
+    let mut dir_list = DirList::new(&files_directory)
+        .context("[app_backend.load_trash_view()] Failed to read trash")?;
+    // Trashed dotfiles are restored and purged like everything else
+    dir_list.set_show_hidden(true);
+    let entries = dir_list
+        .names()
+        .map(|name| (name.to_string(), trash::read_entry(name)))
+        .collect();
+
+    Ok(TrashView::new(dir_list, entries))
 }
@@ -0,0 +1,1105 @@
+// Archives are browsed as read-only virtual directories: `/x/photos.zip/2023/a.jpg` is the entry
+// `2023/a.jpg` inside `/x/photos.zip`
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::SystemTime;
+
+use crossbeam::channel::{self, Receiver};
+
+use super::clipboard;
+use super::file_operations::{self, FileOperationError, OperationResult, Progress};
+use super::vfs::{self, FileKind, Metadata, Provider};
+
+// Entries are streamed from the decompressing thread in chunks of this size
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+const STREAM_CHUNKS: usize = 4;
+const MAX_LINK_DEPTH: usize = 16;
+// Reading part of an entry, as previews do, gives up instead of decompressing more than this. Tar
+// entries can only be reached by decompressing all the ones before them
+const MAX_RANGE_SCAN_BYTES: u64 = 256 * 1024 * 1024;
+// For providers that do not know permissions
+const DEFAULT_FILE_MODE: u32 = 0o644;
+const DEFAULT_DIRECTORY_MODE: u32 = 0o755;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+const SUFFIXES: [(&str, ArchiveFormat); 8] = [
+    (".zip", ArchiveFormat::Zip),
+    (".tar", ArchiveFormat::Tar),
+    (".tar.gz", ArchiveFormat::TarGz),
+    (".tgz", ArchiveFormat::TarGz),
+    (".tar.xz", ArchiveFormat::TarXz),
+    (".txz", ArchiveFormat::TarXz),
+    (".tar.zst", ArchiveFormat::TarZst),
+    (".tzst", ArchiveFormat::TarZst),
+];
+
+impl ArchiveFormat {
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        split_suffix(&path.file_name()?.to_string_lossy()).map(|(_, format)| format)
+    }
+}
+
+fn split_suffix(name: &str) -> Option<(&str, ArchiveFormat)> {
+    let lowercase = name.to_lowercase();
+    SUFFIXES
+        .into_iter()
+        .find(|(suffix, _)| lowercase.ends_with(suffix) && lowercase.len() > suffix.len())
+        .map(|(suffix, format)| (&name[..name.len() - suffix.len()], format))
+}
+
+// The archive name without its suffix, `photos.tar.gz` becomes `photos`
+pub fn stem(archive: &Path) -> Option<String> {
+    let name = archive.file_name()?.to_string_lossy();
+    split_suffix(&name).map(|(stem, _)| stem.to_string())
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    ArchiveFormat::from_path(path).is_some() && path.is_file()
+}
+
+// Splits a path into the archive file and the path of an entry inside it. Only paths below an
+// archive name are looked up, plain local paths cost no system calls
+pub fn split_virtual_path(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let below_archive_name = path
+        .ancestors()
+        .skip(1)
+        .any(|ancestor| ArchiveFormat::from_path(ancestor).is_some());
+    if !below_archive_name || path.symlink_metadata().is_ok() {
+        return None;
+    }
+    path.ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.symlink_metadata().is_ok())
+        .filter(|archive| is_archive(archive))
+        .map(|archive| {
+            let inner = path.strip_prefix(archive).unwrap_or(path).to_path_buf();
+            (archive.to_path_buf(), inner)
+        })
+}
+
+struct IndexEntry {
+    kind: FileKind,
+    size: u64,
+    link: Option<PathBuf>,
+}
+
+// Serves the contents of an archive, read-only, from an index of its entries that also has the
+// directories only implied by file paths
+pub struct ArchiveFs {
+    archive: PathBuf,
+    format: ArchiveFormat,
+    modified: Option<SystemTime>,
+    entries: BTreeMap<PathBuf, IndexEntry>,
+    // Entries by the directory holding them, so listing one does not go through the whole index
+    children: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl ArchiveFs {
+    pub fn open(archive: &Path) -> io::Result<ArchiveFs> {
+        let format = ArchiveFormat::from_path(archive)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not an archive"))?;
+        let modified = fs::metadata(archive)?.modified().ok();
+        let mut entries = BTreeMap::new();
+
+        for_each_entry(archive, format, |path, entry| {
+            let (kind, link) = match &entry.kind {
+                EntryKind::Directory => (FileKind::Directory, None),
+                EntryKind::File => (FileKind::File, None),
+                EntryKind::Symlink(target) => (FileKind::Symlink, Some(target.clone())),
+                EntryKind::Special => return Ok(()),
+            };
+            add_with_parents(
+                &mut entries,
+                path,
+                IndexEntry {
+                    kind,
+                    size: entry.size,
+                    link,
+                },
+            );
+            Ok(())
+        })
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        let mut children: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        for path in entries.keys() {
+            if let Some(parent) = path.parent() {
+                children
+                    .entry(parent.to_path_buf())
+                    .or_default()
+                    .push(path.clone());
+            }
+        }
+
+        Ok(ArchiveFs {
+            archive: archive.to_path_buf(),
+            format,
+            modified,
+            entries,
+            children,
+        })
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    // Uncompressed bytes and number of entries, for progress reporting
+    pub fn measure(&self) -> (u64, u64) {
+        self.entries.values().fold((0, 0), |(bytes, items), entry| {
+            (bytes + entry.size, items + 1)
+        })
+    }
+
+    fn inner<'a>(&self, path: &'a Path) -> io::Result<&'a Path> {
+        path.strip_prefix(&self.archive)
+            .map_err(|_| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn entry_metadata(&self, inner: &Path) -> io::Result<Metadata> {
+        let (kind, len) = if inner.as_os_str().is_empty() {
+            (FileKind::Directory, 0)
+        } else {
+            let entry = self
+                .entries
+                .get(inner)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+            (entry.kind, entry.size)
+        };
+
+        // Entries change with the archive file, so they carry its modification time
+        Ok(Metadata {
+            kind,
+            len,
+            modified: self.modified,
+            accessed: None,
+            changed: None,
+            mode: None,
+            owner: None,
+            links: None,
+            blocks: None,
+            identity: None,
+        })
+    }
+
+    // Decompresses on a thread of its own, so the contents stream without a temporary file. With
+    // `skip_limit`, streaming fails once more bytes than that were decompressed to reach the entry
+    fn stream(&self, path: &Path, skip_limit: Option<u64>) -> io::Result<Box<dyn Read + Send>> {
+        let inner = self.follow(self.inner(path)?)?;
+        if !self.entry_metadata(&inner)?.is_file() {
+            return Err(io::Error::from(io::ErrorKind::IsADirectory));
+        }
+
+        let (chunk_tx, chunk_rx) = channel::bounded(STREAM_CHUNKS);
+        let archive = self.archive.clone();
+        let format = self.format;
+        thread::spawn(move || {
+            let mut found = false;
+            let mut skipped = 0;
+            let result = for_each_entry(&archive, format, |path, entry| {
+                if path != inner {
+                    // Zip archives seek past entries for free
+                    skipped += if format == ArchiveFormat::Zip {
+                        0
+                    } else {
+                        entry.size
+                    };
+                    return match skip_limit {
+                        Some(limit) if skipped > limit => Err(FileOperationError::Io {
+                            path: archive.join(&inner),
+                            source: too_far_error(),
+                        }),
+                        _ => Ok(()),
+                    };
+                }
+                found = true;
+                loop {
+                    let mut chunk = vec![0; STREAM_CHUNK_SIZE];
+                    let read = match entry.reader.read(&mut chunk) {
+                        Ok(0) => break,
+                        Ok(read) => read,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => {
+                            let _ = chunk_tx.send(Err(e));
+                            break;
+                        }
+                    };
+                    chunk.truncate(read);
+                    if chunk_tx.send(Ok(chunk)).is_err() {
+                        break;
+                    }
+                }
+                // Nothing after the entry is needed
+                Err(FileOperationError::Cancelled)
+            });
+
+            match result {
+                Err(FileOperationError::Cancelled) | Ok(()) if found => (),
+                Err(e) => {
+                    let _ = chunk_tx.send(Err(io::Error::other(e.to_string())));
+                }
+                Ok(()) => {
+                    let _ = chunk_tx.send(Err(io::Error::from(io::ErrorKind::NotFound)));
+                }
+            }
+        });
+
+        Ok(Box::new(ChunkReader {
+            chunks: chunk_rx,
+            chunk: Vec::new(),
+            position: 0,
+        }))
+    }
+
+    // Follows links between entries, links leaving the archive count as broken
+    fn follow(&self, inner: &Path) -> io::Result<PathBuf> {
+        let mut current = inner.to_path_buf();
+        for _ in 0..MAX_LINK_DEPTH {
+            let link = match self
+                .entries
+                .get(&current)
+                .and_then(|entry| entry.link.as_ref())
+            {
+                Some(link) => link,
+                None => return Ok(current),
+            };
+            let joined = current.parent().unwrap_or(Path::new("")).join(link);
+            current = normalize(&joined).ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "too many levels of symbolic links",
+        ))
+    }
+}
+
+impl Provider for ArchiveFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let inner = self.inner(path)?;
+        if !self.entry_metadata(inner)?.is_dir() {
+            return Err(io::Error::from(io::ErrorKind::NotADirectory));
+        }
+        Ok(self
+            .children
+            .get(inner)
+            .into_iter()
+            .flatten()
+            .map(|entry_path| self.archive.join(entry_path))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let inner = self.follow(self.inner(path)?)?;
+        self.entry_metadata(&inner)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.entry_metadata(self.inner(path)?)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.entries
+            .get(self.inner(path)?)
+            .and_then(|entry| entry.link.clone())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        self.stream(path, None)
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+        if offset > MAX_RANGE_SCAN_BYTES {
+            return Err(too_far_error());
+        }
+        let mut reader = self.stream(path, Some(MAX_RANGE_SCAN_BYTES - offset))?;
+        io::copy(&mut (&mut reader).take(offset), &mut io::sink())?;
+        let mut buffer = Vec::new();
+        reader.take(length).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn create(&self, _path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        Err(vfs::read_only_error())
+    }
+
+    fn create_dir(&self, _path: &Path) -> io::Result<()> {
+        Err(vfs::read_only_error())
+    }
+
+    fn symlink(&self, _target: &Path, _path: &Path) -> io::Result<()> {
+        Err(vfs::read_only_error())
+    }
+
+    fn set_permissions(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Err(vfs::read_only_error())
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Err(vfs::read_only_error())
+    }
+
+    fn remove_file(&self, _path: &Path) -> io::Result<()> {
+        Err(vfs::read_only_error())
+    }
+
+    fn remove_dir(&self, _path: &Path) -> io::Result<()> {
+        Err(vfs::read_only_error())
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+fn too_far_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::FileTooLarge,
+        "too far into the archive to read a part of it",
+    )
+}
+
+// Reads what the decompressing thread sends, an error or a closed channel ends the stream
+struct ChunkReader {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.chunks.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let read = buffer.len().min(self.chunk.len() - self.position);
+        buffer[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+fn add_with_parents(entries: &mut BTreeMap<PathBuf, IndexEntry>, path: PathBuf, entry: IndexEntry) {
+    for parent in path.ancestors().skip(1) {
+        if parent.as_os_str().is_empty() {
+            break;
+        }
+        entries.entry(parent.to_path_buf()).or_insert(IndexEntry {
+            kind: FileKind::Directory,
+            size: 0,
+            link: None,
+        });
+    }
+    entries.insert(path, entry);
+}
+
+// Resolves `..` inside the archive, None when the path leaves it
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => (),
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+// Drops `.` and leading `/`, rejects `..` so entries cannot escape the extraction directory
+fn sanitize_entry_path(path: &Path) -> Option<PathBuf> {
+    let mut sanitized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => sanitized.push(name),
+            Component::CurDir | Component::RootDir => (),
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!sanitized.as_os_str().is_empty()).then_some(sanitized)
+}
+
+enum EntryKind {
+    Directory,
+    File,
+    Symlink(PathBuf),
+    // Hard links, fifos and devices, which are not extracted
+    Special,
+}
+
+struct Entry<'a> {
+    size: u64,
+    kind: EntryKind,
+    mode: Option<u32>,
+    reader: &'a mut dyn Read,
+}
+
+fn open_tar(archive: &Path, format: ArchiveFormat) -> io::Result<tar::Archive<Box<dyn Read>>> {
+    let file = io::BufReader::new(fs::File::open(archive)?);
+    let reader: Box<dyn Read> = match format {
+        ArchiveFormat::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+        ArchiveFormat::TarXz => Box::new(xz2::read::XzDecoder::new(file)),
+        ArchiveFormat::TarZst => Box::new(zstd::stream::read::Decoder::with_buffer(file)?),
+        _ => Box::new(file),
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+// Walks the archive once, in storage order, handing each entry with its contents to `visit`
+fn for_each_entry(
+    archive: &Path,
+    format: ArchiveFormat,
+    mut visit: impl FnMut(PathBuf, &mut Entry) -> OperationResult<()>,
+) -> OperationResult<()> {
+    let io_error = |e: io::Error| FileOperationError::from_io(archive, e);
+
+    if format == ArchiveFormat::Zip {
+        let file = fs::File::open(archive).map_err(io_error)?;
+        let mut zip = zip::ZipArchive::new(io::BufReader::new(file))
+            .map_err(|e| io_error(io::Error::other(e)))?;
+        for index in 0..zip.len() {
+            let mut zip_file = zip
+                .by_index(index)
+                .map_err(|e| io_error(io::Error::other(e)))?;
+            let path = match zip_file.enclosed_name() {
+                Some(path) => path,
+                None => continue,
+            };
+            let size = zip_file.size();
+            let mode = zip_file.unix_mode();
+            let kind = if zip_file.is_dir() {
+                EntryKind::Directory
+            } else if zip_file.is_symlink() {
+                // Zip keeps the link target as the entry contents
+                let mut target = String::new();
+                zip_file.read_to_string(&mut target).map_err(io_error)?;
+                EntryKind::Symlink(PathBuf::from(target))
+            } else {
+                EntryKind::File
+            };
+            visit(
+                path,
+                &mut Entry {
+                    size,
+                    kind,
+                    mode,
+                    reader: &mut zip_file,
+                },
+            )?;
+        }
+        return Ok(());
+    }
+
+    let mut tar = open_tar(archive, format).map_err(io_error)?;
+    for tar_entry in tar.entries().map_err(io_error)? {
+        let mut tar_entry = tar_entry.map_err(io_error)?;
+        let path = match tar_entry
+            .path()
+            .ok()
+            .and_then(|path| sanitize_entry_path(&path))
+        {
+            Some(path) => path,
+            None => continue,
+        };
+        let header = tar_entry.header();
+        let entry_type = header.entry_type();
+        let kind = if entry_type.is_dir() {
+            EntryKind::Directory
+        } else if entry_type.is_symlink() {
+            match tar_entry.link_name().ok().flatten() {
+                Some(target) => EntryKind::Symlink(target.into_owned()),
+                None => continue,
+            }
+        } else if entry_type.is_file() {
+            EntryKind::File
+        } else {
+            EntryKind::Special
+        };
+        let size = header.size().unwrap_or(0);
+        let mode = header.mode().ok();
+        visit(
+            path,
+            &mut Entry {
+                size,
+                kind,
+                mode,
+                reader: &mut tar_entry,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+// Extracts the whole archive into the new directory `target`, in one pass over the archive
+pub fn extract(archive: &Path, target: &Path, progress: &mut dyn Progress) -> OperationResult<()> {
+    let format = ArchiveFormat::from_path(archive)
+        .ok_or_else(|| FileOperationError::NotFound(archive.to_path_buf()))?;
+    let target_fs = file_operations::provider(target)?;
+    target_fs
+        .create_dir(target)
+        .map_err(|e| FileOperationError::from_io(target, e))?;
+
+    let result = for_each_entry(archive, format, |path, entry| {
+        if let EntryKind::Special = entry.kind {
+            progress.skip(&archive.join(&path));
+            return Ok(());
+        }
+        let output = target.join(&path);
+        let io_error = |e: io::Error| FileOperationError::from_io(&output, e);
+        // Implied directories are created on the way, there may be no entry for them. A link
+        // extracted earlier must not lead an entry out of `target`, so none may be on the way
+        let ancestors: Vec<&Path> = path.ancestors().collect();
+        for ancestor in ancestors.into_iter().rev().skip(1) {
+            let existing = target.join(ancestor);
+            match target_fs.symlink_metadata(&existing) {
+                Ok(metadata) if metadata.is_symlink() => {
+                    return Err(io_error(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("'{}' is a link in the archive", existing.display()),
+                    )))
+                }
+                Ok(_) => (),
+                // The entry itself is created below
+                Err(_) if existing == output => (),
+                Err(_) => target_fs
+                    .create_dir(&existing)
+                    .map_err(|e| FileOperationError::from_io(&existing, e))?,
+            }
+        }
+
+        match &entry.kind {
+            EntryKind::Directory => match target_fs.create_dir(&output) {
+                Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(io_error(e)),
+                _ => (),
+            },
+            EntryKind::Symlink(link_target) => {
+                target_fs.symlink(link_target, &output).map_err(io_error)?
+            }
+            EntryKind::Special => (),
+            EntryKind::File => {
+                let mut writer = target_fs.create(&output).map_err(io_error)?;
+                file_operations::write_stream(
+                    entry.reader,
+                    &archive.join(&path),
+                    &mut writer,
+                    &output,
+                    progress,
+                )?;
+                // Only files, a read-only directory would stop the entries that follow it. No
+                // setuid, setgid or sticky bits from someone else's archive
+                if let Some(mode) = entry.mode {
+                    target_fs
+                        .set_permissions(&output, mode & 0o777)
+                        .map_err(io_error)?;
+                }
+            }
+        }
+        progress.add_item()
+    });
+
+    if result.is_err() {
+        // Like a failed copy, do not leave half an extraction behind
+        let _ = file_operations::delete(target, &mut file_operations::NoProgress);
+    }
+    result
+}
+
+// Uncompressed bytes and entries of a whole archive
+pub fn measure_contents(archive: &Path) -> (u64, u64) {
+    ArchiveFs::open(archive)
+        .map(|archive_fs| archive_fs.measure())
+        .unwrap_or((0, 0))
+}
+
+// Where `archive` is extracted to when no directory is given, next to the archive itself
+pub fn default_extract_target(archive: &Path) -> PathBuf {
+    let name = stem(archive).unwrap_or_else(|| String::from("extracted"));
+    let target = archive.with_file_name(name);
+    if vfs::exists(&target) {
+        clipboard::unique_target(&target)
+    } else {
+        target
+    }
+}
+
+// Counts bytes as they are read into the archive, keeping the error that stopped reading
+struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a mut dyn Progress,
+    error: Option<FileOperationError>,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        if let Err(e) = self.progress.add_bytes(read as u64) {
+            self.error = Some(e);
+            return Err(io::Error::other("aborted"));
+        }
+        Ok(read)
+    }
+}
+
+// The part both zip and tar archives have in common, so the tree walk is shared
+trait ArchiveWriter {
+    fn add_directory(&mut self, name: &str, metadata: &Metadata) -> io::Result<()>;
+    fn add_symlink(&mut self, name: &str, target: &Path, metadata: &Metadata) -> io::Result<()>;
+    fn add_file(
+        &mut self,
+        name: &str,
+        metadata: &Metadata,
+        reader: &mut dyn Read,
+    ) -> io::Result<()>;
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+type Output = Box<dyn Write + Send>;
+
+// Streaming, so archives can be written where seeking is not possible
+impl ArchiveWriter for zip::ZipWriter<zip::write::StreamWriter<Output>> {
+    fn add_directory(&mut self, name: &str, metadata: &Metadata) -> io::Result<()> {
+        let options = zip::write::SimpleFileOptions::default()
+            .unix_permissions(metadata.mode.unwrap_or(DEFAULT_DIRECTORY_MODE));
+        zip::ZipWriter::add_directory(self, name, options).map_err(io::Error::other)
+    }
+
+    fn add_symlink(&mut self, name: &str, target: &Path, _metadata: &Metadata) -> io::Result<()> {
+        let options = zip::write::SimpleFileOptions::default();
+        zip::ZipWriter::add_symlink(self, name, target.to_string_lossy(), options)
+            .map_err(io::Error::other)
+    }
+
+    fn add_file(
+        &mut self,
+        name: &str,
+        metadata: &Metadata,
+        reader: &mut dyn Read,
+    ) -> io::Result<()> {
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .unix_permissions(metadata.mode.unwrap_or(DEFAULT_FILE_MODE))
+            .large_file(metadata.len >= u32::MAX as u64);
+        self.start_file(name, options).map_err(io::Error::other)?;
+        io::copy(reader, self).map(|_| ())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        zip::ZipWriter::finish(*self)
+            .map_err(io::Error::other)?
+            .into_inner()
+            .flush()
+    }
+}
+
+// Compressors need to write their trailer before the archive is complete
+trait FinishStream: Write {
+    fn finish_stream(self) -> io::Result<()>;
+}
+
+impl FinishStream for Output {
+    fn finish_stream(mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl FinishStream for flate2::write::GzEncoder<Output> {
+    fn finish_stream(self) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+impl FinishStream for xz2::write::XzEncoder<Output> {
+    fn finish_stream(self) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+impl FinishStream for zstd::stream::write::Encoder<'static, Output> {
+    fn finish_stream(self) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+fn tar_header(metadata: &Metadata, entry_type: tar::EntryType, size: u64) -> tar::Header {
+    let default_mode = match entry_type {
+        tar::EntryType::Directory => DEFAULT_DIRECTORY_MODE,
+        _ => DEFAULT_FILE_MODE,
+    };
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(entry_type);
+    header.set_size(size);
+    header.set_mode(metadata.mode.unwrap_or(default_mode) & 0o7777);
+    if let Some((uid, gid)) = metadata.owner {
+        header.set_uid(uid as u64);
+        header.set_gid(gid as u64);
+    }
+    let modified = metadata
+        .modified
+        .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok());
+    header.set_mtime(modified.map_or(0, |modified| modified.as_secs()));
+    header
+}
+
+impl<W: FinishStream> ArchiveWriter for tar::Builder<W> {
+    fn add_directory(&mut self, name: &str, metadata: &Metadata) -> io::Result<()> {
+        let mut header = tar_header(metadata, tar::EntryType::Directory, 0);
+        self.append_data(&mut header, name, io::empty())
+    }
+
+    fn add_symlink(&mut self, name: &str, target: &Path, metadata: &Metadata) -> io::Result<()> {
+        let mut header = tar_header(metadata, tar::EntryType::Symlink, 0);
+        self.append_link(&mut header, name, target)
+    }
+
+    fn add_file(
+        &mut self,
+        name: &str,
+        metadata: &Metadata,
+        reader: &mut dyn Read,
+    ) -> io::Result<()> {
+        let mut header = tar_header(metadata, tar::EntryType::Regular, metadata.len);
+        // The header promises exactly this many bytes, a file that grows or shrinks while it is
+        // read would otherwise corrupt the archive
+        let body = ExactReader {
+            inner: reader,
+            remaining: metadata.len,
+        };
+        self.append_data(&mut header, name, body)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.into_inner()?.finish_stream()
+    }
+}
+
+// Reads `remaining` bytes and stops, failing if the reader ends before that
+struct ExactReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for ExactReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+        let length = buffer.len().min(self.remaining as usize);
+        let read = self.inner.read(&mut buffer[..length])?;
+        if read == 0 && length > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file shrank while it was archived",
+            ));
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+fn archive_writer(output: Output, format: ArchiveFormat) -> io::Result<Box<dyn ArchiveWriter>> {
+    Ok(match format {
+        ArchiveFormat::Zip => Box::new(zip::ZipWriter::new_stream(output)),
+        ArchiveFormat::Tar => Box::new(tar::Builder::new(output)),
+        ArchiveFormat::TarGz => Box::new(tar::Builder::new(flate2::write::GzEncoder::new(
+            output,
+            flate2::Compression::default(),
+        ))),
+        ArchiveFormat::TarXz => Box::new(tar::Builder::new(xz2::write::XzEncoder::new(output, 6))),
+        ArchiveFormat::TarZst => Box::new(tar::Builder::new(zstd::stream::write::Encoder::new(
+            output, 0,
+        )?)),
+    })
+}
+
+// Packs `sources` into a new archive at `target`, its format is picked by the file name
+pub fn compress(
+    sources: &[PathBuf],
+    target: &Path,
+    progress: &mut dyn Progress,
+) -> OperationResult<()> {
+    let format = ArchiveFormat::from_path(target).ok_or_else(|| {
+        FileOperationError::InvalidName(
+            target
+                .file_name()
+                .map_or(String::new(), |name| name.to_string_lossy().to_string()),
+        )
+    })?;
+    if let Some(source) = sources.iter().find(|source| target.starts_with(source)) {
+        return Err(FileOperationError::IntoItself(source.clone()));
+    }
+
+    let target_fs = file_operations::provider(target)?;
+    let output = target_fs
+        .create(target)
+        .map_err(|e| FileOperationError::from_io(target, e))?;
+    let result = archive_writer(output, format)
+        .map_err(|e| FileOperationError::from_io(target, e))
+        .and_then(|mut writer| {
+            for source in sources {
+                let name = source
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .ok_or_else(|| FileOperationError::InvalidName(source.display().to_string()))?;
+                let source_fs = file_operations::provider(source)?;
+                add_recursive(writer.as_mut(), source_fs.as_ref(), source, &name, progress)?;
+            }
+            writer
+                .finish()
+                .map_err(|e| FileOperationError::from_io(target, e))
+        });
+
+    // Do not leave a truncated archive behind
+    if result.is_err() {
+        let _ = target_fs.remove_file(target);
+    }
+    result
+}
+
+fn add_recursive(
+    writer: &mut dyn ArchiveWriter,
+    source_fs: &dyn Provider,
+    source: &Path,
+    name: &str,
+    progress: &mut dyn Progress,
+) -> OperationResult<()> {
+    let io_error = |e: io::Error| FileOperationError::from_io(source, e);
+    let metadata = source_fs.symlink_metadata(source).map_err(io_error)?;
+
+    match metadata.kind {
+        FileKind::Symlink => {
+            let link_target = source_fs.read_link(source).map_err(io_error)?;
+            writer
+                .add_symlink(name, &link_target, &metadata)
+                .map_err(io_error)?;
+        }
+        FileKind::Directory => {
+            writer.add_directory(name, &metadata).map_err(io_error)?;
+            let mut entries = source_fs.read_dir(source).map_err(io_error)?;
+            entries.sort();
+            for entry in entries {
+                let entry_name = match entry.file_name() {
+                    Some(entry_name) => format!("{}/{}", name, entry_name.to_string_lossy()),
+                    None => continue,
+                };
+                add_recursive(writer, source_fs, &entry, &entry_name, progress)?;
+            }
+        }
+        FileKind::File => {
+            let mut reader = ProgressReader {
+                inner: source_fs.open(source).map_err(io_error)?,
+                progress: &mut *progress,
+                error: None,
+            };
+            let result = writer.add_file(name, &metadata, &mut reader);
+            if let Some(e) = reader.error {
+                return Err(e);
+            }
+            result.map_err(io_error)?;
+        }
+        // Sockets, fifos and devices cannot be archived
+        FileKind::Other => progress.skip(source),
+    }
+    progress.add_item()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Skips(Vec<PathBuf>);
+
+    impl Progress for Skips {
+        fn add_bytes(&mut self, _bytes: u64) -> OperationResult<()> {
+            Ok(())
+        }
+
+        fn add_item(&mut self) -> OperationResult<()> {
+            Ok(())
+        }
+
+        fn skip(&mut self, path: &Path) {
+            self.0.push(path.to_path_buf());
+        }
+    }
+
+    fn header(entry_type: tar::EntryType, size: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(size);
+        header.set_mode(0o644);
+        header
+    }
+
+    // Builds a tar archive from (path, entry type, link target or contents) triples
+    fn write_tar(archive: &Path, entries: &[(&str, tar::EntryType, &str)]) {
+        let mut builder = tar::Builder::new(fs::File::create(archive).unwrap());
+        for (path, entry_type, data) in entries {
+            if entry_type.is_symlink() {
+                let mut header = header(*entry_type, 0);
+                builder.append_link(&mut header, path, data).unwrap();
+            } else {
+                let mut header = header(*entry_type, data.len() as u64);
+                builder
+                    .append_data(&mut header, path, data.as_bytes())
+                    .unwrap();
+            }
+        }
+        builder.finish().unwrap();
+    }
+
+    #[test]
+    fn lists_directories_from_the_index() {
+        let directory = tempfile::tempdir().unwrap();
+        let archive = directory.path().join("a.tar");
+        write_tar(
+            &archive,
+            &[
+                ("top.txt", tar::EntryType::Regular, "top"),
+                ("docs/b.txt", tar::EntryType::Regular, "b"),
+                // `deep` only exists implied by the path below it
+                ("docs/deep/c.txt", tar::EntryType::Regular, "c"),
+                ("docs/a.txt", tar::EntryType::Regular, "a"),
+            ],
+        );
+
+        let archive_fs = ArchiveFs::open(&archive).unwrap();
+        let list = |inner: &str| -> Vec<PathBuf> {
+            archive_fs
+                .read_dir(&archive.join(inner))
+                .unwrap()
+                .into_iter()
+                .map(|path| path.strip_prefix(&archive).unwrap().to_path_buf())
+                .collect()
+        };
+        assert_eq!(list(""), [Path::new("docs"), Path::new("top.txt")]);
+        assert_eq!(
+            list("docs"),
+            [
+                Path::new("docs/a.txt"),
+                Path::new("docs/b.txt"),
+                Path::new("docs/deep")
+            ]
+        );
+        assert_eq!(list("docs/deep"), [Path::new("docs/deep/c.txt")]);
+        assert!(archive_fs.read_dir(&archive.join("top.txt")).is_err());
+        assert!(archive_fs.read_dir(&archive.join("missing")).is_err());
+    }
+
+    #[test]
+    fn sanitizing_keeps_entries_inside_the_target() {
+        let sanitize = |path: &str| sanitize_entry_path(Path::new(path));
+        assert_eq!(sanitize("a/./b"), Some(PathBuf::from("a/b")));
+        assert_eq!(sanitize("/etc/passwd"), Some(PathBuf::from("etc/passwd")));
+        assert_eq!(sanitize("../evil"), None);
+        assert_eq!(sanitize("a/../../evil"), None);
+        assert_eq!(sanitize("a/../b"), None);
+        assert_eq!(sanitize("/"), None);
+        assert_eq!(sanitize("."), None);
+    }
+
+    #[test]
+    fn extracts_files_and_links() {
+        let directory = tempfile::tempdir().unwrap();
+        let archive = directory.path().join("a.tar");
+        write_tar(
+            &archive,
+            &[
+                ("docs/readme.txt", tar::EntryType::Regular, "hello"),
+                ("docs/link", tar::EntryType::Symlink, "readme.txt"),
+            ],
+        );
+
+        let target = directory.path().join("a");
+        extract(&archive, &target, &mut Skips::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(target.join("docs/readme.txt")).unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            fs::read_link(target.join("docs/link")).unwrap(),
+            Path::new("readme.txt")
+        );
+    }
+
+    #[test]
+    fn refuses_to_extract_through_links() {
+        let directory = tempfile::tempdir().unwrap();
+        let outside = directory.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        let archive = directory.path().join("evil.tar");
+        write_tar(
+            &archive,
+            &[
+                ("link", tar::EntryType::Symlink, outside.to_str().unwrap()),
+                ("link/evil", tar::EntryType::Regular, "gotcha"),
+            ],
+        );
+
+        let target = directory.path().join("evil");
+        let e = extract(&archive, &target, &mut Skips::default()).unwrap_err();
+        assert!(e.to_string().contains("is a link in the archive"), "{}", e);
+        assert!(!outside.join("evil").exists());
+        // A failed extraction is removed again
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn reports_special_entries_it_skips() {
+        let directory = tempfile::tempdir().unwrap();
+        let archive = directory.path().join("special.tar");
+        write_tar(
+            &archive,
+            &[
+                ("file", tar::EntryType::Regular, "data"),
+                ("pipe", tar::EntryType::Fifo, ""),
+            ],
+        );
+
+        let target = directory.path().join("special");
+        let mut skips = Skips::default();
+        extract(&archive, &target, &mut skips).unwrap();
+        assert!(target.join("file").exists());
+        assert!(!target.join("pipe").exists());
+        assert_eq!(skips.0, vec![archive.join("pipe")]);
+    }
+
+    #[test]
+    fn tar_bodies_must_match_their_header() {
+        let mut exact = ExactReader {
+            inner: &b"hello world"[..],
+            remaining: 5,
+        };
+        let mut body = Vec::new();
+        exact.read_to_end(&mut body).unwrap();
+        assert_eq!(body, b"hello");
+
+        let mut short = ExactReader {
+            inner: &b"hi"[..],
+            remaining: 5,
+        };
+        let e = short.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use anyhow::{bail, Result};
+
+use super::file_operations::{self, FileOperationError, NoProgress};
+use super::journal::Operation;
+use super::vfs;
+
+// Renames inside one directory, as edited in $EDITOR
+#[derive(Clone)]
+pub struct BulkRename {
+    pub directory: PathBuf,
+    pub renames: Vec<(String, String)>,
+}
+
+impl BulkRename {
+    // Pairs each original name with the line at the same position in the edited text
+    pub fn new(directory: &Path, names: &[String], edited: &str) -> Result<BulkRename> {
+        let new_names: Vec<&str> = edited
+            .lines()
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect();
+        if new_names.len() != names.len() {
+            bail!(
+                "Expected {} names but the file has {} lines",
+                names.len(),
+                new_names.len()
+            );
+        }
+
+        let mut seen = HashSet::new();
+        for new_name in &new_names {
+            file_operations::validate_name(new_name)?;
+            if !seen.insert(*new_name) {
+                bail!("'{}' appears more than once", new_name);
+            }
+        }
+
+        let renames: Vec<(String, String)> = names
+            .iter()
+            .zip(new_names)
+            .filter(|(name, new_name)| name.as_str() != *new_name)
+            .map(|(name, new_name)| (name.clone(), new_name.to_string()))
+            .collect();
+
+        // Targets may only be taken by items that are renamed away themselves
+        let sources: HashSet<&str> = renames.iter().map(|(name, _)| name.as_str()).collect();
+        for (_, new_name) in &renames {
+            if !sources.contains(new_name.as_str()) && vfs::exists(&directory.join(new_name)) {
+                return Err(FileOperationError::AlreadyExists(directory.join(new_name)).into());
+            }
+        }
+
+        Ok(BulkRename {
+            directory: directory.to_path_buf(),
+            renames,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.renames.len()
+    }
+
+    // Swaps and cycles park their sources under a temporary name first, so every target
+    // is free by the time it is renamed into. Returns the steps that ran, for the journal
+    pub fn apply(&self) -> (Vec<Operation>, Option<FileOperationError>) {
+        let sources: HashSet<&str> = self.renames.iter().map(|(name, _)| name.as_str()).collect();
+        let mut operations = Vec::new();
+
+        let mut direct = Vec::new();
+        let mut parked = Vec::new();
+        for (index, (name, new_name)) in self.renames.iter().enumerate() {
+            let source = self.directory.join(name);
+            let target = self.directory.join(new_name);
+            if sources.contains(new_name.as_str()) {
+                let temporary = self.temporary_path(index);
+                if let Err(e) = self.step(&source, &temporary, &mut operations) {
+                    return (operations, Some(e));
+                }
+                parked.push((temporary, target));
+            } else {
+                direct.push((source, target));
+            }
+        }
+
+        for (source, target) in direct.into_iter().chain(parked) {
+            if let Err(e) = self.step(&source, &target, &mut operations) {
+                return (operations, Some(e));
+            }
+        }
+        (operations, None)
+    }
+
+    fn step(
+        &self,
+        source: &Path,
+        target: &Path,
+        operations: &mut Vec<Operation>,
+    ) -> Result<(), FileOperationError> {
+        file_operations::move_to(source, target, &mut NoProgress)?;
+        operations.push(Operation::rename(
+            source.to_path_buf(),
+            target.to_path_buf(),
+        ));
+        Ok(())
+    }
+
+    fn temporary_path(&self, index: usize) -> PathBuf {
+        (0..)
+            .map(|attempt| {
+                self.directory.join(format!(
+                    ".rstuifm-bulk-rename-{}-{}-{}",
+                    process::id(),
+                    index,
+                    attempt
+                ))
+            })
+            .find(|path| !vfs::exists(path))
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // A directory holding one file per name, each with its name as contents
+    fn directory_with(names: &[&str]) -> tempfile::TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        for name in names {
+            fs::write(directory.path().join(name), name).unwrap();
+        }
+        directory
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn contents(directory: &Path, name: &str) -> String {
+        fs::read_to_string(directory.join(name)).unwrap()
+    }
+
+    fn entry_count(directory: &Path) -> usize {
+        fs::read_dir(directory).unwrap().count()
+    }
+
+    #[test]
+    fn swaps_two_names() {
+        let directory = directory_with(&["a", "b"]);
+        let bulk_rename = BulkRename::new(directory.path(), &names(&["a", "b"]), "b\na\n").unwrap();
+        let (operations, error) = bulk_rename.apply();
+
+        assert!(error.is_none());
+        // Both are parked first, then moved into place
+        assert_eq!(operations.len(), 4);
+        assert_eq!(contents(directory.path(), "a"), "b");
+        assert_eq!(contents(directory.path(), "b"), "a");
+        assert_eq!(entry_count(directory.path()), 2);
+    }
+
+    #[test]
+    fn rotates_three_names() {
+        let directory = directory_with(&["a", "b", "c"]);
+        let bulk_rename =
+            BulkRename::new(directory.path(), &names(&["a", "b", "c"]), "b\nc\na\n").unwrap();
+        let (_, error) = bulk_rename.apply();
+
+        assert!(error.is_none());
+        assert_eq!(contents(directory.path(), "b"), "a");
+        assert_eq!(contents(directory.path(), "c"), "b");
+        assert_eq!(contents(directory.path(), "a"), "c");
+        assert_eq!(entry_count(directory.path()), 3);
+    }
+
+    #[test]
+    fn unchanged_lines_are_left_out() {
+        let directory = directory_with(&["a", "b"]);
+        let bulk_rename = BulkRename::new(directory.path(), &names(&["a", "b"]), "a\nc\n").unwrap();
+
+        assert_eq!(
+            bulk_rename.renames,
+            vec![(String::from("b"), String::from("c"))]
+        );
+    }
+
+    #[test]
+    fn rejects_a_duplicate_target() {
+        let directory = directory_with(&["a", "b"]);
+        let result = BulkRename::new(directory.path(), &names(&["a", "b"]), "c\nc\n");
+
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("appears more than once"));
+    }
+
+    #[test]
+    fn rejects_a_target_that_exists() {
+        let directory = directory_with(&["a", "b"]);
+        let result = BulkRename::new(directory.path(), &names(&["a"]), "b\n");
+
+        let error = result.err().unwrap();
+        assert!(matches!(
+            error.downcast_ref::<FileOperationError>(),
+            Some(FileOperationError::AlreadyExists(path)) if path == &directory.path().join("b")
+        ));
+        assert_eq!(contents(directory.path(), "a"), "a");
+    }
+
+    #[test]
+    fn rejects_a_changed_line_count() {
+        let directory = directory_with(&["a", "b"]);
+
+        let fewer = BulkRename::new(directory.path(), &names(&["a", "b"]), "a\n");
+        assert!(fewer
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("Expected 2 names but the file has 1 lines"));
+        let more = BulkRename::new(directory.path(), &names(&["a", "b"]), "a\nb\nc\n");
+        assert!(more.is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use super::vfs;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClipboardIntent {
+    Copy,
+    Cut,
+}
+
+// Lives on AppBackend rather than on a Tab so it can be pasted into any tab
+#[derive(Clone)]
+pub struct Clipboard {
+    pub paths: Vec<PathBuf>,
+    pub intent: ClipboardIntent,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    Overwrite,
+    Skip,
+    RenameWithSuffix,
+    Ask,
+}
+
+#[derive(Clone)]
+pub struct PasteEntry {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    // Whether an existing item at `target` has to be removed first
+    pub overwrite: bool,
+}
+
+// A paste that is resolved one conflict at a time when the policy is to ask
+#[derive(Clone)]
+pub struct PastePlan {
+    pub intent: ClipboardIntent,
+    pub destination: PathBuf,
+    pub entries: Vec<PasteEntry>,
+    conflicts: VecDeque<PasteEntry>,
+}
+
+impl PastePlan {
+    pub fn new(clipboard: &Clipboard, destination: &Path, policy: ConflictPolicy) -> PastePlan {
+        let mut plan = PastePlan {
+            intent: clipboard.intent,
+            destination: destination.to_path_buf(),
+            entries: Vec::new(),
+            conflicts: VecDeque::new(),
+        };
+
+        for source in &clipboard.paths {
+            let name = match source.file_name() {
+                Some(name) => name,
+                None => continue,
+            };
+            let target = destination.join(name);
+
+            if &target == source {
+                // Pasting a copy next to its source always gets a new name, a cut is a no-op
+                if plan.intent == ClipboardIntent::Copy {
+                    plan.entries.push(PasteEntry {
+                        source: source.clone(),
+                        target: unique_target(&target),
+                        overwrite: false,
+                    });
+                }
+            } else if vfs::exists(&target) {
+                let entry = PasteEntry {
+                    source: source.clone(),
+                    target,
+                    overwrite: false,
+                };
+                match policy {
+                    ConflictPolicy::Ask => plan.conflicts.push_back(entry),
+                    policy => plan.resolve(entry, policy),
+                }
+            } else {
+                plan.entries.push(PasteEntry {
+                    source: source.clone(),
+                    target,
+                    overwrite: false,
+                });
+            }
+        }
+
+        plan
+    }
+
+    fn resolve(&mut self, mut entry: PasteEntry, policy: ConflictPolicy) {
+        match policy {
+            ConflictPolicy::Overwrite => {
+                entry.overwrite = true;
+                self.entries.push(entry);
+            }
+            ConflictPolicy::RenameWithSuffix => {
+                entry.target = unique_target(&entry.target);
+                self.entries.push(entry);
+            }
+            ConflictPolicy::Skip => (),
+            ConflictPolicy::Ask => self.conflicts.push_front(entry),
+        }
+    }
+
+    pub fn next_conflict(&self) -> Option<&PasteEntry> {
+        self.conflicts.front()
+    }
+
+    pub fn resolve_next(&mut self, policy: ConflictPolicy) {
+        if let Some(entry) = self.conflicts.pop_front() {
+            self.resolve(entry, policy);
+        }
+    }
+
+    pub fn resolve_all(&mut self, policy: ConflictPolicy) {
+        if policy == ConflictPolicy::Ask {
+            return;
+        }
+        while let Some(entry) = self.conflicts.pop_front() {
+            self.resolve(entry, policy);
+        }
+    }
+}
+
+// Appends `_1`, `_2`, ... before the extension until the name is free
+pub fn unique_target(target: &Path) -> PathBuf {
+    let parent = target.parent().unwrap_or_else(|| Path::new(""));
+    let stem = target
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = target
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|suffix| parent.join(format!("{}_{}{}", stem, suffix, extension)))
+        .find(|candidate| !vfs::exists(candidate))
+        .unwrap()
+}
@@ -0,0 +1,142 @@
+// Details shown next to the names in the middle pane, picked in
+// `$XDG_CONFIG_HOME/rstuifm/columns.toml`:
+//
+// # size, modified, permissions, owner or links. On narrow terminals columns are dropped from the
+// # end of the list until the names have room
+// columns = ["size", "modified"]
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use uzers::{Groups, Users, UsersCache};
+
+use super::vfs::Metadata;
+use crate::helper_functions;
+
+// Like `ls -l`, older modification times show the year instead of the time of day
+const RECENT: Duration = Duration::from_secs(180 * 24 * 60 * 60);
+
+thread_local! {
+    static USERS: UsersCache = UsersCache::new();
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Size,
+    Modified,
+    Permissions,
+    Owner,
+    Links,
+}
+
+impl Column {
+    // Numbers line up on the right
+    pub fn is_right_aligned(&self) -> bool {
+        matches!(self, Column::Size | Column::Links)
+    }
+
+    // What the column shows for an item, empty when its provider does not know
+    pub fn cell(&self, metadata: &Metadata) -> String {
+        match self {
+            Column::Size if metadata.is_dir() => String::new(),
+            Column::Size => format_size(metadata.len),
+            Column::Modified => metadata.modified.map(format_time).unwrap_or_default(),
+            Column::Permissions => metadata
+                .mode
+                .map(helper_functions::format_mode)
+                .unwrap_or_default(),
+            Column::Owner => metadata
+                .owner
+                .map(|(uid, gid)| format!("{}:{}", user_name(uid), group_name(gid)))
+                .unwrap_or_default(),
+            Column::Links => metadata
+                .links
+                .map(|links| links.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct ColumnConfig {
+    columns: Vec<Column>,
+}
+
+impl Default for ColumnConfig {
+    fn default() -> ColumnConfig {
+        ColumnConfig {
+            columns: vec![Column::Size, Column::Modified],
+        }
+    }
+}
+
+// A missing config means the default columns, a broken one is reported
+pub fn load() -> Result<Vec<Column>> {
+    let path = config_path();
+    let config: ColumnConfig = match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents)
+            .with_context(|| format!("Invalid column config '{}'", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => ColumnConfig::default(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read '{}'", path.display())),
+    };
+    Ok(config.columns)
+}
+
+// Sizes in at most 5 chars like `ls -h`, for example `812`, `4.0K` or `13M`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [char; 6] = ['K', 'M', 'G', 'T', 'P', 'E'];
+
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 10.0 {
+        format!("{:.1}{}", size, UNITS[unit])
+    } else {
+        format!("{:.0}{}", size, UNITS[unit])
+    }
+}
+
+fn format_time(time: SystemTime) -> String {
+    let recent = SystemTime::now()
+        .duration_since(time)
+        .is_ok_and(|age| age < RECENT);
+    let format = if recent { "%b %e %H:%M" } else { "%b %e  %Y" };
+    chrono::DateTime::<chrono::Local>::from(time)
+        .format(format)
+        .to_string()
+}
+
+// Ids without a name are shown as numbers
+pub fn user_name(uid: u32) -> String {
+    USERS.with(|users| {
+        users.get_user_by_uid(uid).map_or(uid.to_string(), |user| {
+            user.name().to_string_lossy().to_string()
+        })
+    })
+}
+
+pub fn group_name(gid: u32) -> String {
+    USERS.with(|users| {
+        users
+            .get_group_by_gid(gid)
+            .map_or(gid.to_string(), |group| {
+                group.name().to_string_lossy().to_string()
+            })
+    })
+}
+
+fn config_path() -> PathBuf {
+    helper_functions::xdg_directory("XDG_CONFIG_HOME", ".config")
+        .join("rstuifm")
+        .join("columns.toml")
+}
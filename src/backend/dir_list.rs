@@ -1,138 +1,325 @@
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::mem;
+use std::path::{Path, PathBuf};
 
+use super::sort::{SortItem, SortOptions};
+use super::symlink::LinkTarget;
+use super::vfs::{self, FileKind, Metadata, Provider};
 use super::{File, Folder, Symlink};
 
-use crate::ui::widgets::DirListUI;
 use anyhow::{Context, Result};
-use ratatui::widgets::{Block, Borders, List, Widget};
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Widget},
+};
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct DirList {
-    folders: Vec<Folder>,
-    files: Vec<File>,
-    symlinks: Vec<Symlink>,
-    dotfile_folders: Vec<Folder>,
-    dotfile_files: Vec<File>,
-    dotfile_symlinks: Vec<Symlink>,
+    // Shown items in display order
+    entries: Vec<Entry>,
+    // Hidden items, empty while they are shown among the entries
+    dotfile_entries: Vec<Entry>,
+    sort: SortOptions,
+    show_hidden: bool,
+    // Set while a DirLoader is still reading the directory
+    loading: bool,
+}
+
+#[derive(Clone)]
+struct Entry {
+    item: FileSystemItem,
+    // None when the provider could not tell
+    metadata: Option<Metadata>,
+    // Set for links, whatever they point to
+    link: Option<LinkTarget>,
+}
+
+impl Entry {
+    fn is_dotfile(&self) -> bool {
+        self.item.name().starts_with('.')
+    }
+
+    fn sort_item(&self) -> SortItem<'_> {
+        SortItem {
+            name: self.item.name(),
+            is_dir: matches!(self.item, FileSystemItem::Folder(_)),
+            metadata: self.metadata.as_ref(),
+        }
+    }
 }
 
+#[derive(Clone)]
 pub enum FileSystemItem {
     Folder(Folder),
     File(File),
     Symlink(Symlink),
 }
 
+impl FileSystemItem {
+    pub fn path(&self) -> &Path {
+        match self {
+            FileSystemItem::Folder(folder) => &folder.path,
+            FileSystemItem::File(file) => &file.path,
+            FileSystemItem::Symlink(symlink) => &symlink.path,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            FileSystemItem::Folder(folder) => folder.name(),
+            FileSystemItem::File(file) => file.name(),
+            FileSystemItem::Symlink(symlink) => symlink.name(),
+        }
+    }
+}
+
 impl DirList {
-    pub fn new(path: &PathBuf) -> Result<DirList> {
-        let mut folders = Vec::new();
-        let mut files = Vec::new();
-        let mut symlinks = Vec::new();
-        let mut dotfile_folders = Vec::new();
-        let mut dotfile_files = Vec::new();
-        let mut dotfile_symlinks = Vec::new();
-
-        for entry in path
-            .read_dir()
+    pub fn new(path: &Path) -> Result<DirList> {
+        let mut dir_list = DirList::default();
+
+        let provider = vfs::resolve_directory(path)
+            .context("[app_backend.DirList.new()] Failed to read directory path")?;
+        for path in provider
+            .read_dir(path)
             .context("[app_backend.DirList.new()] Failed to read directory path")?
         {
-            let entry = entry.context("[app_backend.DirList.new()] Failed to read DirEntry")?;
-            let path = entry.path();
-            let is_dotfile = path
-                .file_name()
-                .and_then(|name| name.to_str())
-                .map_or(false, |name| name.starts_with('.'));
-
-            if path.is_dir() {
-                if is_dotfile {
-                    dotfile_folders.push(Folder::new(path).unwrap());
-                } else {
-                    folders.push(Folder::new(path).context(
-                        "[app_backend.DirList.new()] Failed to create new Folder struct",
-                    )?);
-                }
-            } else if path.is_file() {
-                if is_dotfile {
-                    dotfile_files.push(File::new(path).unwrap());
-                } else {
-                    files.push(
-                        File::new(path).context(
-                            "[app_backend.DirList.new()] Failed to create new File struct",
-                        )?,
-                    );
-                }
-            } else if path.is_symlink() {
-                if is_dotfile {
-                    dotfile_symlinks.push(Symlink::new(path).unwrap());
-                } else {
-                    symlinks.push(Symlink::new(path).context(
-                        "[app_backend.DirList.new()] Failed to create new Symlink struct",
-                    )?);
-                }
-            }
+            dir_list.push(provider.as_ref(), path)?;
+        }
+        dir_list.sort_entries();
+
+        Ok(dir_list)
+    }
+
+    // An empty list that keeps its items sorted by `sort`
+    pub fn sorted_by(sort: SortOptions) -> DirList {
+        DirList {
+            sort,
+            ..DirList::default()
+        }
+    }
+
+    // An empty list whose items are still being read
+    pub fn loading(sort: SortOptions) -> DirList {
+        DirList {
+            loading: true,
+            ..DirList::sorted_by(sort)
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading
+    }
+
+    pub fn finish_loading(&mut self) {
+        self.loading = false;
+    }
+
+    // Adds the item at `path` unsorted, `sort_entries` orders the list once everything is pushed.
+    // Items that are neither folders, files nor links are skipped
+    pub fn push(&mut self, provider: &dyn Provider, path: PathBuf) -> Result<()> {
+        // Links are listed as what they point to, dangling ones as symlinks. Either way their target
+        // is kept
+        let link_metadata = provider.symlink_metadata(&path).ok();
+        let is_link = link_metadata
+            .as_ref()
+            .is_some_and(|link_metadata| link_metadata.is_symlink());
+        let metadata = if is_link {
+            provider.metadata(&path).ok()
+        } else {
+            link_metadata.clone()
+        };
+        let link = is_link.then(|| LinkTarget {
+            path: provider.read_link(&path).unwrap_or_default(),
+            broken: metadata.is_none(),
+        });
+        let kind = metadata.as_ref().map(|metadata| metadata.kind);
+        let is_dotfile = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.'));
+
+        let (item, metadata) = if kind == Some(FileKind::Directory) {
+            let folder = if is_dotfile {
+                Folder::new(path).unwrap()
+            } else {
+                Folder::new(path)
+                    .context("[app_backend.DirList.push()] Failed to create new Folder struct")?
+            };
+            (FileSystemItem::Folder(folder), metadata)
+        } else if kind == Some(FileKind::File) {
+            let file = if is_dotfile {
+                File::new(path).unwrap()
+            } else {
+                File::new(path)
+                    .context("[app_backend.DirList.push()] Failed to create new File struct")?
+            };
+            (FileSystemItem::File(file), metadata)
+        } else {
+            let link_metadata = match link_metadata {
+                Some(link_metadata) if is_link => link_metadata,
+                _ => return Ok(()),
+            };
+            let symlink = if is_dotfile {
+                Symlink::new(path).unwrap()
+            } else {
+                Symlink::new(path)
+                    .context("[app_backend.DirList.push()] Failed to create new Symlink struct")?
+            };
+            (FileSystemItem::Symlink(symlink), Some(link_metadata))
+        };
+
+        let entry = Entry {
+            item,
+            metadata,
+            link,
+        };
+        if is_dotfile {
+            self.dotfile_entries.push(entry);
+        } else {
+            self.entries.push(entry);
         }
+        Ok(())
+    }
+
+    // Merges the items of a sorted batch into the list, which keeps this linear in the size of the
+    // list. Huge directories arrive in many batches, the loader sorts them off the main thread
+    pub fn append(&mut self, mut batch: DirList) {
+        batch.sort(self.sort);
+        batch.set_show_hidden(false);
+        let options = self.sort;
+        self.entries = merge(options, mem::take(&mut self.entries), batch.entries);
+        let dotfile_entries = if self.show_hidden {
+            &mut self.entries
+        } else {
+            &mut self.dotfile_entries
+        };
+        *dotfile_entries = merge(options, mem::take(dotfile_entries), batch.dotfile_entries);
+    }
 
-        Ok(DirList {
-            folders,
-            files,
-            symlinks,
-            dotfile_folders,
-            dotfile_files,
-            dotfile_symlinks,
-        })
+    pub fn sort_options(&self) -> SortOptions {
+        self.sort
     }
 
-    pub fn folders(&self) -> &Vec<Folder> {
-        &self.folders
+    pub fn sort(&mut self, options: SortOptions) {
+        if self.sort != options {
+            self.sort = options;
+            self.sort_entries();
+        }
     }
 
-    pub fn files(&self) -> &Vec<File> {
-        &self.files
+    pub fn sort_entries(&mut self) {
+        let options = self.sort;
+        for entries in [&mut self.entries, &mut self.dotfile_entries] {
+            entries.sort_by(|a, b| options.compare(&a.sort_item(), &b.sort_item()));
+        }
     }
 
-    pub fn symlinks(&self) -> &Vec<Symlink> {
-        &self.symlinks
+    // Folds hidden items into the entries or takes them out again, the order stays sorted
+    pub fn set_show_hidden(&mut self, show_hidden: bool) {
+        if self.show_hidden == show_hidden {
+            return;
+        }
+        self.show_hidden = show_hidden;
+        let entries = mem::take(&mut self.entries);
+        if show_hidden {
+            let dotfile_entries = mem::take(&mut self.dotfile_entries);
+            self.entries = merge(self.sort, entries, dotfile_entries);
+        } else {
+            (self.dotfile_entries, self.entries) =
+                entries.into_iter().partition(|entry| entry.is_dotfile());
+        }
     }
 
     pub fn len(&self) -> usize {
-        self.folders.len() + self.files.len() + self.symlinks.len()
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Names in display order, without cloning the items
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.item.name())
+    }
+
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|entry| entry.item.name())
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names().position(|item_name| item_name == name)
     }
 
     pub fn get(&self, index: usize) -> Option<FileSystemItem> {
-        if index < self.folders.len() {
-            let folder = self.folders.get(index).unwrap().clone();
-            let folder = FileSystemItem::Folder(folder);
-            Some(folder)
-        } else if index >= self.folders.len() && index < self.folders.len() + self.files.len() {
-            let file = self.files.get(index - self.folders.len()).unwrap().clone();
-            let file = FileSystemItem::File(file);
-            Some(file)
-        } else if index >= (self.files.len() + self.folders.len()) && index < self.len() {
-            let symlink = self
-                .symlinks
-                .get(index - self.files.len() - self.folders.len())
-                .unwrap()
-                .clone();
-            let symlink = FileSystemItem::Symlink(symlink);
-            Some(symlink)
+        self.entries.get(index).map(|entry| entry.item.clone())
+    }
+
+    pub fn metadata(&self, index: usize) -> Option<&Metadata> {
+        self.entries
+            .get(index)
+            .and_then(|entry| entry.metadata.as_ref())
+    }
+
+    pub fn link(&self, index: usize) -> Option<&LinkTarget> {
+        self.entries
+            .get(index)
+            .and_then(|entry| entry.link.as_ref())
+    }
+}
+
+fn merge(options: SortOptions, entries: Vec<Entry>, batch: Vec<Entry>) -> Vec<Entry> {
+    let mut merged = Vec::with_capacity(entries.len() + batch.len());
+    let mut entries = entries.into_iter().peekable();
+    let mut batch = batch.into_iter().peekable();
+
+    loop {
+        let from_batch = match (entries.peek(), batch.peek()) {
+            (Some(entry), Some(batch_entry)) => {
+                options.compare(&batch_entry.sort_item(), &entry.sort_item()) == Ordering::Less
+            }
+            (Some(_), None) => false,
+            (None, Some(_)) => true,
+            (None, None) => return merged,
+        };
+        merged.extend(if from_batch {
+            batch.next()
         } else {
-            None
-        }
+            entries.next()
+        });
     }
 }
 
 impl Widget for DirList {
     fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
-        let folders: Vec<String> = self.folders().iter().map(|x| x.to_string()).collect();
-        let files: Vec<String> = self.files().iter().map(|x| x.to_string()).collect();
-        let symlinks: Vec<String> = self.symlinks().iter().map(|x| x.to_string()).collect();
-
-        let items = folders
+        // Side panes show the top of the list, rows that don't fit aren't built
+        let broken_style = Style::default().fg(Color::Red);
+        let items: Vec<ListItem> = self
+            .entries
             .iter()
-            .chain(files.iter())
-            .chain(symlinks.iter())
-            .map(|x| x.as_str().to_string());
+            .take(area.height as usize)
+            .map(|entry| {
+                let name = entry.item.name().to_string();
+                match &entry.link {
+                    Some(link) if link.broken => ListItem::new(Line::from(vec![
+                        Span::styled(name, broken_style),
+                        Span::styled(link.suffix(), broken_style),
+                    ])),
+                    Some(link) => ListItem::new(Line::from(vec![
+                        Span::raw(name),
+                        Span::styled(link.suffix(), Style::default().fg(Color::DarkGray)),
+                    ])),
+                    None => ListItem::new(name),
+                }
+            })
+            .collect();
 
-        let list = List::new(items).block(Block::default().borders(Borders::ALL));
+        let mut block = Block::default().borders(Borders::ALL);
+        if self.loading {
+            block = block.title(" loading… ");
+        }
+        let list = List::new(items).block(block);
         list.render(area, buf);
     }
 }
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{self, Receiver, Sender};
+
+use super::events::RstuifmEvent;
+use super::sort::SortOptions;
+use super::vfs;
+use super::DirList;
+
+// A hung mount holds on to one worker, the others keep reading
+const WORKER_COUNT: usize = 4;
+// Time until the first batch is sent. Every batch makes the panes copy the listing so far, so the
+// interval doubles after each one
+const FIRST_BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct DirBatch {
+    pub path: PathBuf,
+    load_id: usize,
+    // Items read since the last batch, an error ends the read
+    pub items: io::Result<DirList>,
+    pub done: bool,
+}
+
+struct LoadRequest {
+    path: PathBuf,
+    id: usize,
+    // Batches come sorted like this, leaving the main thread only to merge them
+    sort: SortOptions,
+    cancelled: Arc<AtomicBool>,
+}
+
+struct PendingLoad {
+    id: usize,
+    cancelled: Arc<AtomicBool>,
+}
+
+// Reads directories on worker threads, their items come back in batches as app events
+pub struct DirLoader {
+    pending: HashMap<PathBuf, PendingLoad>,
+    next_id: usize,
+    request_tx: Sender<LoadRequest>,
+}
+
+impl DirLoader {
+    pub fn new(event_tx: Sender<RstuifmEvent>) -> DirLoader {
+        let (request_tx, request_rx) = channel::unbounded();
+
+        for _ in 0..WORKER_COUNT {
+            let request_rx: Receiver<LoadRequest> = request_rx.clone();
+            let event_tx = event_tx.clone();
+            thread::spawn(move || {
+                for request in request_rx.iter() {
+                    read_directory(request, &event_tx);
+                }
+            });
+        }
+
+        DirLoader {
+            pending: HashMap::new(),
+            next_id: 0,
+            request_tx,
+        }
+    }
+
+    // Starts reading `path`, a read of it that is still running is superseded
+    pub fn load(&mut self, path: &Path, sort: SortOptions) {
+        self.cancel(path);
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.pending.insert(
+            path.to_path_buf(),
+            PendingLoad {
+                id,
+                cancelled: cancelled.clone(),
+            },
+        );
+        let _ = self.request_tx.send(LoadRequest {
+            path: path.to_path_buf(),
+            id,
+            sort,
+            cancelled,
+        });
+    }
+
+    pub fn is_loading(&self, path: &Path) -> bool {
+        self.pending.contains_key(path)
+    }
+
+    pub fn cancel(&mut self, path: &Path) {
+        if let Some(load) = self.pending.remove(path) {
+            load.cancelled.store(true, Ordering::SeqCst);
+        }
+    }
+
+    // Cancels the reads of directories not in `paths`, returns the cancelled ones
+    pub fn retain(&mut self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let cancelled: Vec<PathBuf> = self
+            .pending
+            .keys()
+            .filter(|path| !paths.contains(path))
+            .cloned()
+            .collect();
+        for path in &cancelled {
+            self.cancel(path);
+        }
+        cancelled
+    }
+
+    // Whether `batch` comes from the current read of its directory, batches of superseded reads
+    // may still be queued. The read is forgotten with its last batch
+    pub fn accept(&mut self, batch: &DirBatch) -> bool {
+        let is_current = self
+            .pending
+            .get(&batch.path)
+            .is_some_and(|load| load.id == batch.load_id);
+        if is_current && batch.done {
+            self.pending.remove(&batch.path);
+        }
+        is_current
+    }
+}
+
+fn read_directory(request: LoadRequest, event_tx: &Sender<RstuifmEvent>) {
+    let send = |items: io::Result<DirList>, done: bool| {
+        let items = items.map(|mut items| {
+            items.sort_entries();
+            items
+        });
+        let batch = DirBatch {
+            path: request.path.clone(),
+            load_id: request.id,
+            items,
+            done,
+        };
+        event_tx.send(RstuifmEvent::DirBatch(batch)).is_ok()
+    };
+    let is_cancelled = || request.cancelled.load(Ordering::SeqCst);
+
+    // Requests pile up while the user scrolls past folders, most are cancelled before they start
+    if is_cancelled() {
+        return;
+    }
+    let listing = vfs::resolve_directory(&request.path)
+        .and_then(|provider| Ok((provider.read_dir(&request.path)?, provider)));
+    let (paths, provider) = match listing {
+        Ok(listing) => listing,
+        Err(e) => {
+            send(Err(e), true);
+            return;
+        }
+    };
+
+    let mut batch = DirList::sorted_by(request.sort);
+    let mut last_sent = Instant::now();
+    let mut interval = FIRST_BATCH_INTERVAL;
+    for path in paths {
+        if is_cancelled() {
+            return;
+        }
+        if let Err(e) = batch.push(provider.as_ref(), path) {
+            send(Err(io::Error::other(e)), true);
+            return;
+        }
+        if last_sent.elapsed() >= interval {
+            let items = mem::replace(&mut batch, DirList::sorted_by(request.sort));
+            if !send(Ok(items), false) {
+                return;
+            }
+            last_sent = Instant::now();
+            interval *= 2;
+        }
+    }
+    send(Ok(batch), true);
+}
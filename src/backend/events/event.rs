@@ -1,4 +1,27 @@
+use std::path::PathBuf;
+
+use crate::backend::dir_loader::DirBatch;
+use crate::backend::finder::FinderResults;
+use crate::backend::grep::GrepBatch;
+use crate::backend::highlighter::HighlightResult;
+use crate::backend::jobs::JobEvent;
+use crate::backend::preview::PreviewResult;
+use crate::backend::sftp::Connected;
+
 pub enum RstuifmEvent {
-    CrosstermEvent(crossterm::event::Event)
+    CrosstermEvent(crossterm::event::Event),
+    Job(JobEvent),
+    Highlight(HighlightResult),
+    // A preview loaded in the background
+    Preview(PreviewResult),
+    // A watched directory changed on disk
+    DirectoryChanged(PathBuf),
+    // Items of a directory being read in the background
+    DirBatch(DirBatch),
+    // Best matches of the fuzzy finder so far
+    Finder(FinderResults),
+    // Hits of a content search found since the last batch
+    Grep(GrepBatch),
+    // An sftp:// url a prompt named, connected or failed to
+    Connected(Connected),
 }
-
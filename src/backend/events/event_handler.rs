@@ -1,11 +1,24 @@
 use anyhow::{Context, Result};
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
 use std::{
+    sync::Arc,
     thread,
     time::Duration,
 };
 
-use crate::{backend::AppBackend, ui};
+use crate::{
+    backend::{
+        clipboard::{ClipboardIntent, ConflictPolicy},
+        prompt::PromptKind,
+        sort::SortMode,
+        AppBackend, Mode,
+    },
+    ui,
+};
+
+use super::{InputControl, RstuifmEvent};
+
+const INPUT_POLL_TIMEOUT: Duration = Duration::from_millis(30);
 
 // TODO create a seperate channel for input events, as these may be blocking with other terminal events and need to be cleared if the channel is stacked
 // TODO use bounded channels with crossbeam prolly
@@ -13,18 +26,31 @@ use crate::{backend::AppBackend, ui};
 pub struct EventHandler {
     crossterm_event_rx: crossbeam::channel::Receiver<crossterm::event::Event>,
     input_event_rx: crossbeam::channel::Receiver<crossterm::event::KeyEvent>,
+    // Events produced inside the app, such as job progress from worker threads
+    app_event_tx: crossbeam::channel::Sender<RstuifmEvent>,
+    app_event_rx: crossbeam::channel::Receiver<RstuifmEvent>,
+    input_control: Arc<InputControl>,
 }
 
 impl EventHandler {
     pub fn new() -> Result<EventHandler> {
         let (crossterm_event_tx, crossterm_event_rx) = crossbeam::channel::unbounded();
         let (input_event_tx, input_event_rx) = crossbeam::channel::bounded(3);
+        let (app_event_tx, app_event_rx) = crossbeam::channel::unbounded();
 
         let crossterm_event_tx_clone = crossterm_event_tx.clone();
         let input_event_tx_clone = input_event_tx.clone();
+        let input_control = Arc::new(InputControl::default());
+        let input_control_clone = input_control.clone();
 
         thread::spawn(move || loop {
-            if let Ok(event) = event::read() {
+            let read_event = || {
+                event::poll(INPUT_POLL_TIMEOUT)
+                    .unwrap_or(false)
+                    .then(event::read)
+                    .and_then(|event| event.ok())
+            };
+            if let Some(event) = input_control_clone.read_with(read_event) {
                 match event {
                     Event::Key(key_event) => {
                         input_event_tx_clone.try_send(key_event).ok();
@@ -41,9 +67,20 @@ impl EventHandler {
         Ok(EventHandler {
             crossterm_event_rx,
             input_event_rx,
+            app_event_tx,
+            app_event_rx,
+            input_control,
         })
     }
 
+    pub fn input_control(&self) -> Arc<InputControl> {
+        self.input_control.clone()
+    }
+
+    pub fn app_event_sender(&self) -> crossbeam::channel::Sender<RstuifmEvent> {
+        self.app_event_tx.clone()
+    }
+
     pub fn process_events(&self, app_backend: &mut AppBackend) -> Result<()> {
         let crossterm_event = self.crossterm_event_rx.try_recv();
 
@@ -57,6 +94,17 @@ impl EventHandler {
         if let Ok(key) = key_event {
             process_key_event(key, app_backend);
         }
+
+        // Workers can report faster than the loop runs, so drain everything and redraw once
+        let mut needs_redraw = false;
+        for event in self.app_event_rx.try_iter() {
+            process_app_event(event, app_backend)
+                .context("Failed to process app event")?;
+            needs_redraw = true;
+        }
+        if needs_redraw {
+            app_backend.redraw();
+        }
         Ok(())
     }
 }
@@ -79,13 +127,319 @@ fn process_crossterm_event(
     }
 }
 
+fn process_app_event(event: RstuifmEvent, app_backend: &mut AppBackend) -> Result<()> {
+    match event {
+        RstuifmEvent::CrosstermEvent(event) => process_crossterm_event(event, app_backend),
+        RstuifmEvent::Job(job_event) => {
+            app_backend.handle_job_event(job_event);
+            Ok(())
+        }
+        RstuifmEvent::Highlight(result) => {
+            app_backend.handle_highlight(result);
+            Ok(())
+        }
+        RstuifmEvent::Preview(result) => {
+            app_backend.handle_preview(result);
+            Ok(())
+        }
+        RstuifmEvent::DirectoryChanged(path) => {
+            app_backend.handle_directory_change(&path);
+            Ok(())
+        }
+        RstuifmEvent::DirBatch(batch) => {
+            app_backend.handle_dir_batch(batch);
+            Ok(())
+        }
+        RstuifmEvent::Finder(results) => {
+            app_backend.handle_finder_results(results);
+            Ok(())
+        }
+        RstuifmEvent::Grep(batch) => {
+            app_backend.handle_grep_batch(batch);
+            Ok(())
+        }
+        RstuifmEvent::Connected(connected) => {
+            app_backend.handle_connected(connected);
+            Ok(())
+        }
+    }
+}
+
 fn process_key_event(key: crossterm::event::KeyEvent, app_backend: &mut AppBackend) {
+    match app_backend.mode {
+        Mode::Normal => process_normal_key_event(key, app_backend),
+        Mode::Prompt(_) => process_prompt_key_event(key, app_backend),
+        Mode::JobPanel(_) => process_job_panel_key_event(key, app_backend),
+        Mode::Trash => process_trash_key_event(key, app_backend),
+        Mode::Grep => process_grep_key_event(key, app_backend),
+        Mode::OpenWith(_) => process_open_with_key_event(key, app_backend),
+    }
+}
+
+fn process_open_with_key_event(key: crossterm::event::KeyEvent, app_backend: &mut AppBackend) {
+    match key.code {
+        KeyCode::Enter | KeyCode::Char('l') => app_backend.open_with_selected(),
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('h') => app_backend.close_open_with(),
+        code => {
+            if let Some(menu) = app_backend.open_with_mut() {
+                match code {
+                    KeyCode::Char('j') | KeyCode::Down => menu.select_next(),
+                    KeyCode::Char('k') | KeyCode::Up => menu.select_previous(),
+                    _ => (),
+                }
+            }
+            app_backend.redraw();
+        }
+    }
+}
+
+fn process_trash_key_event(key: crossterm::event::KeyEvent, app_backend: &mut AppBackend) {
+    app_backend.clear_status();
+
+    match key.code {
+        KeyCode::Char('r') => app_backend.restore_from_trash(),
+        KeyCode::Char('D') | KeyCode::Delete => app_backend.begin_purge(),
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('T') => app_backend.close_trash(),
+        code => {
+            if let Some(list) = app_backend.trash_list_mut() {
+                match code {
+                    KeyCode::Char('j') | KeyCode::Down => list.select_next(),
+                    KeyCode::Char('k') | KeyCode::Up => list.select_previous(),
+                    KeyCode::Char(' ') => {
+                        list.toggle_mark();
+                        list.select_next();
+                    }
+                    KeyCode::Char('v') => list.invert_marks(),
+                    KeyCode::Char('V') if list.is_visual() => list.end_visual(true),
+                    KeyCode::Char('V') => list.start_visual(),
+                    _ => (),
+                }
+            }
+            app_backend.redraw();
+        }
+    }
+}
+
+fn process_grep_key_event(key: crossterm::event::KeyEvent, app_backend: &mut AppBackend) {
+    app_backend.clear_status();
+
+    match key.code {
+        KeyCode::Enter | KeyCode::Char('l') => app_backend.edit_grep_hit(),
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app_backend.stop_grep()
+        }
+        KeyCode::Esc | KeyCode::Char('q') => app_backend.close_grep(),
+        code => {
+            if let Some(search) = app_backend.search_mut() {
+                match code {
+                    KeyCode::Char('j') | KeyCode::Down => search.select_next(),
+                    KeyCode::Char('k') | KeyCode::Up => search.select_previous(),
+                    KeyCode::Char('g') | KeyCode::Home => search.selected = 0,
+                    KeyCode::Char('G') | KeyCode::End => {
+                        search.selected = search.hits.len().saturating_sub(1)
+                    }
+                    _ => (),
+                }
+            }
+            app_backend.redraw();
+        }
+    }
+}
+
+fn process_job_panel_key_event(key: crossterm::event::KeyEvent, app_backend: &mut AppBackend) {
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => app_backend.select_job(1),
+        KeyCode::Char('k') | KeyCode::Up => app_backend.select_job(-1),
+        KeyCode::Char('p') | KeyCode::Char(' ') => app_backend.toggle_pause_selected_job(),
+        KeyCode::Char('c') | KeyCode::Char('x') => app_backend.cancel_selected_job(),
+        KeyCode::Esc | KeyCode::Char('J') | KeyCode::Char('q') => app_backend.close_job_panel(),
+        _ => (),
+    }
+}
+
+fn process_normal_key_event(key: crossterm::event::KeyEvent, app_backend: &mut AppBackend) {
+    app_backend.clear_status();
+
+    if let Some(first) = app_backend.pending_key.take() {
+        process_key_sequence(first, key, app_backend);
+        return;
+    }
+
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        match key.code {
+            KeyCode::Char('a') => app_backend.mark_all(),
+            KeyCode::Char('r') => app_backend.redo(),
+            KeyCode::Char('f') => app_backend.scroll_preview(1),
+            KeyCode::Char('b') => app_backend.scroll_preview(-1),
+            KeyCode::Char('p') => app_backend.begin_find(),
+            KeyCode::Char('g') => app_backend.begin_grep(),
+            _ => app_backend.redraw(),
+        }
+        return;
+    }
+
     match key.code {
         KeyCode::Char('q') => app_backend.exit_app().unwrap(),
         KeyCode::Char('j') => app_backend.select_next(),
         KeyCode::Char('k') => app_backend.select_previous(),
         KeyCode::Char('l') => app_backend.select_right(),
         KeyCode::Char('h') => app_backend.select_left(),
-        _ => (),
+        KeyCode::Char('r') => app_backend.begin_rename(),
+        KeyCode::Char('R') => app_backend.begin_bulk_rename(),
+        KeyCode::Char('M') => app_backend.begin_make_directory(),
+        KeyCode::Char('A') => app_backend.begin_make_file(),
+        KeyCode::Char('C') => app_backend.begin_copy(),
+        KeyCode::Char('X') => app_backend.begin_move(),
+        KeyCode::Char('Z') => app_backend.begin_compress(),
+        KeyCode::Char('E') => app_backend.begin_extract(),
+        KeyCode::Char('D') => app_backend.trash_selected(),
+        KeyCode::Delete => app_backend.begin_delete(),
+        KeyCode::Char('T') => app_backend.open_trash(),
+        KeyCode::Char(' ') => app_backend.toggle_mark(),
+        KeyCode::Char('v') => app_backend.invert_marks(),
+        KeyCode::Char('V') => app_backend.toggle_visual(),
+        KeyCode::Char('*') => app_backend.begin_mark_matching(),
+        KeyCode::Char('/') => app_backend.begin_filter(),
+        KeyCode::Esc => app_backend.cancel_selection(),
+        KeyCode::Char('J') => app_backend.open_job_panel(),
+        KeyCode::Char('u') => app_backend.undo(),
+        KeyCode::Char('P') => app_backend.cycle_preview_mode(),
+        KeyCode::Char('.') => app_backend.toggle_hidden(),
+        KeyCode::Char(c @ ('y' | 'd' | 'p' | 'g' | 'o')) => {
+            app_backend.pending_key = Some(c);
+            app_backend.redraw();
+        }
+        _ => app_backend.redraw(),
+    }
+}
+
+fn process_key_sequence(
+    first: char,
+    key: crossterm::event::KeyEvent,
+    app_backend: &mut AppBackend,
+) {
+    match (first, key.code) {
+        ('y', KeyCode::Char('y')) => app_backend.yank(ClipboardIntent::Copy),
+        ('d', KeyCode::Char('d')) => app_backend.yank(ClipboardIntent::Cut),
+        ('p', KeyCode::Char('p')) => app_backend.paste(ConflictPolicy::Ask),
+        ('p', KeyCode::Char('o')) => app_backend.paste(ConflictPolicy::Overwrite),
+        ('p', KeyCode::Char('r')) => app_backend.paste(ConflictPolicy::RenameWithSuffix),
+        ('p', KeyCode::Char('s')) => app_backend.paste(ConflictPolicy::Skip),
+        ('g', KeyCode::Char('n')) => app_backend.new_tab(),
+        ('g', KeyCode::Char('o')) => app_backend.begin_open_tab(),
+        ('g', KeyCode::Char('t')) => app_backend.next_tab(),
+        ('g', KeyCode::Char('T')) => app_backend.previous_tab(),
+        ('g', KeyCode::Char('c')) => app_backend.close_tab(),
+        ('g', KeyCode::Char('l')) => app_backend.follow_link(),
+        ('o', KeyCode::Char('n')) => app_backend.change_sort(|sort| sort.mode = SortMode::Name),
+        ('o', KeyCode::Char('s')) => app_backend.change_sort(|sort| sort.mode = SortMode::Size),
+        ('o', KeyCode::Char('m')) => app_backend.change_sort(|sort| sort.mode = SortMode::Modified),
+        ('o', KeyCode::Char('c')) => app_backend.change_sort(|sort| sort.mode = SortMode::Changed),
+        ('o', KeyCode::Char('e')) => {
+            app_backend.change_sort(|sort| sort.mode = SortMode::Extension)
+        }
+        ('o', KeyCode::Char('t')) => app_backend.change_sort(|sort| sort.mode = SortMode::Type),
+        ('o', KeyCode::Char('r')) => app_backend.change_sort(|sort| sort.reverse = !sort.reverse),
+        ('o', KeyCode::Char('d')) => {
+            app_backend.change_sort(|sort| sort.directories_first = !sort.directories_first)
+        }
+        ('o', KeyCode::Char('i')) => {
+            app_backend.change_sort(|sort| sort.case_sensitive = !sort.case_sensitive)
+        }
+        _ => app_backend.redraw(),
+    }
+}
+
+fn process_prompt_key_event(key: crossterm::event::KeyEvent, app_backend: &mut AppBackend) {
+    let (is_confirmation, is_paste_conflict, is_filter, is_find) = match app_backend.prompt_mut() {
+        Some(prompt) => (
+            prompt.kind.is_confirmation(),
+            matches!(prompt.kind, PromptKind::PasteConflict(_)),
+            matches!(prompt.kind, PromptKind::Filter(_)),
+            matches!(prompt.kind, PromptKind::Find),
+        ),
+        None => return,
+    };
+
+    if is_paste_conflict {
+        match key.code {
+            KeyCode::Char('o') => app_backend.resolve_paste_conflict(ConflictPolicy::Overwrite, false),
+            KeyCode::Char('s') => app_backend.resolve_paste_conflict(ConflictPolicy::Skip, false),
+            KeyCode::Char('r') => {
+                app_backend.resolve_paste_conflict(ConflictPolicy::RenameWithSuffix, false)
+            }
+            KeyCode::Char('O') => app_backend.resolve_paste_conflict(ConflictPolicy::Overwrite, true),
+            KeyCode::Char('S') => app_backend.resolve_paste_conflict(ConflictPolicy::Skip, true),
+            KeyCode::Char('R') => {
+                app_backend.resolve_paste_conflict(ConflictPolicy::RenameWithSuffix, true)
+            }
+            KeyCode::Esc => app_backend.cancel_prompt(),
+            _ => (),
+        }
+        return;
+    }
+
+    if is_confirmation {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => app_backend.submit_prompt(),
+            _ => app_backend.cancel_prompt(),
+        }
+        return;
+    }
+
+    if is_filter {
+        match key.code {
+            KeyCode::Esc => return app_backend.cancel_filter(),
+            KeyCode::Tab => return app_backend.cycle_filter_kind(),
+            KeyCode::Down => return app_backend.select_next(),
+            KeyCode::Up => return app_backend.select_previous(),
+            _ => (),
+        }
+    }
+
+    if is_find {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match (key.code, ctrl) {
+            (KeyCode::Esc, _) => return app_backend.cancel_find(),
+            (KeyCode::Down, _) | (KeyCode::Char('n'), true) => {
+                if let Some(finder) = app_backend.finder_mut() {
+                    finder.select_next();
+                }
+                return app_backend.redraw();
+            }
+            (KeyCode::Up, _) | (KeyCode::Char('p'), true) => {
+                if let Some(finder) = app_backend.finder_mut() {
+                    finder.select_previous();
+                }
+                return app_backend.redraw();
+            }
+            _ => (),
+        }
+    }
+
+    match key.code {
+        KeyCode::Esc => app_backend.cancel_prompt(),
+        KeyCode::Enter => app_backend.submit_prompt(),
+        code => {
+            if let Some(prompt) = app_backend.prompt_mut() {
+                match code {
+                    KeyCode::Char(c) => prompt.insert_char(c),
+                    KeyCode::Backspace => prompt.delete_previous_char(),
+                    KeyCode::Delete => prompt.delete_next_char(),
+                    KeyCode::Left => prompt.move_left(),
+                    KeyCode::Right => prompt.move_right(),
+                    KeyCode::Home => prompt.move_to_start(),
+                    KeyCode::End => prompt.move_to_end(),
+                    _ => (),
+                }
+            }
+            if is_filter {
+                app_backend.update_filter();
+            } else if is_find {
+                app_backend.update_find();
+            } else {
+                app_backend.redraw();
+            }
+        }
     }
 }
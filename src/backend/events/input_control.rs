@@ -0,0 +1,28 @@
+use std::sync::Mutex;
+
+// Lets the input thread step aside while another program, such as $EDITOR, owns the terminal.
+// The input thread holds the lock while polling, so suspending waits for the current poll to end
+#[derive(Default)]
+pub struct InputControl {
+    suspended: Mutex<bool>,
+}
+
+impl InputControl {
+    pub fn suspend(&self) {
+        *self.suspended.lock().unwrap() = true;
+    }
+
+    pub fn resume(&self) {
+        *self.suspended.lock().unwrap() = false;
+    }
+
+    // Runs `read` unless input is suspended
+    pub fn read_with<T>(&self, read: impl FnOnce() -> Option<T>) -> Option<T> {
+        let suspended = self.suspended.lock().unwrap();
+        if *suspended {
+            None
+        } else {
+            read()
+        }
+    }
+}
@@ -1,4 +1,6 @@
 pub mod event;
 pub mod event_handler;
+pub mod input_control;
 
 pub use self::event::RstuifmEvent;
+pub use self::input_control::InputControl;
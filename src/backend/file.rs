@@ -1,15 +1,16 @@
+use std::fmt;
 use std::path::PathBuf;
 use anyhow::{anyhow, Result};
 
 #[derive(Clone)]
 pub struct File {
-    _path: PathBuf,
+    pub path: PathBuf,
     name: String,
 }
 
-impl ToString for File {
-    fn to_string(&self) -> String {
-        self.name.clone()
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
     }
 }
 
@@ -21,6 +22,10 @@ impl File {
             .to_str()
             .ok_or_else(|| anyhow!("[app_backend.File.new()] failed to unwrap Option<&str>"))?
             .to_string();
-        Ok(File { _path: path, name })
+        Ok(File { path, name })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 }
@@ -0,0 +1,155 @@
+// Details of one item for the info view in the right pane, like `stat` shows them. Links are
+// described themselves, with what they point to
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget, Wrap},
+};
+
+use super::columns;
+use super::opener;
+use super::vfs::{self, FileKind};
+use crate::helper_functions::{self, format_bytes};
+
+#[derive(Clone)]
+pub struct FileInfo {
+    pub path: PathBuf,
+    pub fields: Vec<(&'static str, String)>,
+    // Set when the item could not be read
+    pub error: Option<String>,
+}
+
+impl FileInfo {
+    pub fn load(path: &Path) -> FileInfo {
+        let mut info = FileInfo {
+            path: path.to_path_buf(),
+            fields: vec![("Path", path.display().to_string())],
+            error: None,
+        };
+        let metadata = match vfs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                info.error = Some(e.to_string());
+                return info;
+            }
+        };
+
+        let (kind, mime_type) = match metadata.kind {
+            FileKind::Directory => ("Directory", String::from("inode/directory")),
+            FileKind::File => ("File", opener::mime_type(path)),
+            FileKind::Symlink => ("Symbolic link", String::from("inode/symlink")),
+            FileKind::Other => ("Special file", String::from("application/octet-stream")),
+        };
+        info.push("Type", kind.to_string());
+        info.push("MIME type", mime_type);
+        if metadata.is_symlink() {
+            let target = vfs::resolve(path).and_then(|provider| provider.read_link(path));
+            let target = match target {
+                Ok(target) if vfs::metadata(path).is_err() => {
+                    format!("{} (broken)", target.display())
+                }
+                Ok(target) => target.display().to_string(),
+                Err(e) => format!("? ({})", e),
+            };
+            info.push("Target", target);
+        }
+
+        info.push(
+            "Size",
+            format!("{} ({} bytes)", format_bytes(metadata.len), metadata.len),
+        );
+        // Providers other than the local one may not know the rest
+        if let Some(blocks) = metadata.blocks {
+            info.push(
+                "Blocks",
+                format!("{} ({} allocated)", blocks, format_bytes(blocks * 512)),
+            );
+        }
+        if let Some((device, inode)) = metadata.identity {
+            info.push("Inode", inode.to_string());
+            info.push("Device", format!("{}:{}", major(device), minor(device)));
+        }
+        if let Some(links) = metadata.links {
+            info.push("Links", links.to_string());
+        }
+        info.push_time("Modified", metadata.modified);
+        info.push_time("Accessed", metadata.accessed);
+        info.push_time("Changed", metadata.changed);
+        if let Some(mode) = metadata.mode {
+            info.push(
+                "Mode",
+                format!(
+                    "{} ({:04o})",
+                    helper_functions::format_mode(mode),
+                    mode & 0o7777
+                ),
+            );
+        }
+        if let Some((uid, gid)) = metadata.owner {
+            info.push("Owner", format!("{} ({})", columns::user_name(uid), uid));
+            info.push("Group", format!("{} ({})", columns::group_name(gid), gid));
+        }
+        info
+    }
+
+    fn push(&mut self, label: &'static str, value: String) {
+        self.fields.push((label, value));
+    }
+
+    fn push_time(&mut self, label: &'static str, time: Option<SystemTime>) {
+        if let Some(time) = time {
+            let formatted = chrono::DateTime::<chrono::Local>::from(time)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string();
+            self.push(label, formatted);
+        }
+    }
+}
+
+// Device numbers are split like glibc's gnu_dev_major and gnu_dev_minor do
+fn major(device: u64) -> u64 {
+    ((device >> 8) & 0xfff) | ((device >> 32) & !0xfff)
+}
+
+fn minor(device: u64) -> u64 {
+    (device & 0xff) | ((device >> 12) & !0xff)
+}
+
+impl Widget for FileInfo {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let dim = Style::default().fg(Color::DarkGray);
+        let label_width = self
+            .fields
+            .iter()
+            .map(|(label, _)| label.len())
+            .max()
+            .unwrap_or(0);
+
+        let mut lines: Vec<Line> = self
+            .fields
+            .into_iter()
+            .map(|(label, value)| {
+                Line::from(vec![
+                    Span::styled(format!("{:<label_width$}  ", label), dim),
+                    Span::raw(value),
+                ])
+            })
+            .collect();
+        if let Some(error) = self.error {
+            lines.push(Line::from(Span::styled(
+                error,
+                Style::default().fg(Color::Red),
+            )));
+        }
+
+        Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(" Info "))
+            .wrap(Wrap { trim: false })
+            .render(area, buf);
+    }
+}
@@ -0,0 +1,397 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::vfs::{self, FileKind, Provider};
+
+pub type OperationResult<T> = std::result::Result<T, FileOperationError>;
+
+// Errors are kept structured so the UI can show a short message for them
+#[derive(Debug)]
+pub enum FileOperationError {
+    AlreadyExists(PathBuf),
+    NotFound(PathBuf),
+    PermissionDenied(PathBuf),
+    ReadOnly(PathBuf),
+    InvalidName(String),
+    IntoItself(PathBuf),
+    // The item is not what a recorded operation left behind anymore
+    Changed(PathBuf),
+    Cancelled,
+    Io { path: PathBuf, source: io::Error },
+}
+
+impl FileOperationError {
+    pub fn from_io(path: &Path, source: io::Error) -> FileOperationError {
+        let path = path.to_path_buf();
+        match source.kind() {
+            io::ErrorKind::AlreadyExists => FileOperationError::AlreadyExists(path),
+            io::ErrorKind::NotFound => FileOperationError::NotFound(path),
+            io::ErrorKind::PermissionDenied => FileOperationError::PermissionDenied(path),
+            io::ErrorKind::ReadOnlyFilesystem => FileOperationError::ReadOnly(path),
+            _ => FileOperationError::Io { path, source },
+        }
+    }
+}
+
+impl fmt::Display for FileOperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileOperationError::AlreadyExists(path) => {
+                write!(f, "'{}' already exists", path.display())
+            }
+            FileOperationError::NotFound(path) => write!(f, "'{}' does not exist", path.display()),
+            FileOperationError::PermissionDenied(path) => {
+                write!(f, "Permission denied: '{}'", path.display())
+            }
+            FileOperationError::ReadOnly(path) => write!(f, "'{}' is read-only", path.display()),
+            FileOperationError::InvalidName(name) => write!(f, "Invalid name: '{}'", name),
+            FileOperationError::IntoItself(path) => {
+                write!(f, "Cannot copy or move '{}' into itself", path.display())
+            }
+            FileOperationError::Changed(path) => {
+                write!(f, "'{}' changed since the operation", path.display())
+            }
+            FileOperationError::Cancelled => write!(f, "Cancelled"),
+            FileOperationError::Io { path, source } => {
+                write!(f, "'{}': {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl Error for FileOperationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FileOperationError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// The provider holding `path`, see vfs
+pub fn provider(path: &Path) -> OperationResult<Arc<dyn Provider>> {
+    vfs::resolve(path).map_err(|e| FileOperationError::from_io(path, e))
+}
+
+pub fn validate_name(name: &str) -> OperationResult<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.contains('\0') {
+        Err(FileOperationError::InvalidName(name.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+// Fails instead of silently replacing an existing item
+fn ensure_free(path: &Path) -> OperationResult<()> {
+    if vfs::exists(path) {
+        Err(FileOperationError::AlreadyExists(path.to_path_buf()))
+    } else {
+        Ok(())
+    }
+}
+
+fn ensure_not_into_itself(source: &Path, target: &Path) -> OperationResult<()> {
+    if target.starts_with(source) {
+        Err(FileOperationError::IntoItself(source.to_path_buf()))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn make_directory(parent: &Path, name: &str) -> OperationResult<PathBuf> {
+    validate_name(name)?;
+    let path = parent.join(name);
+    provider(&path)?
+        .create_dir(&path)
+        .map_err(|e| FileOperationError::from_io(&path, e))?;
+    Ok(path)
+}
+
+pub fn make_file(parent: &Path, name: &str) -> OperationResult<PathBuf> {
+    validate_name(name)?;
+    let path = parent.join(name);
+    provider(&path)?
+        .create(&path)
+        .map_err(|e| FileOperationError::from_io(&path, e))?;
+    Ok(path)
+}
+
+pub fn rename(path: &Path, new_name: &str) -> OperationResult<PathBuf> {
+    validate_name(new_name)?;
+    let parent = path
+        .parent()
+        .ok_or_else(|| FileOperationError::InvalidName(path.display().to_string()))?;
+    let new_path = parent.join(new_name);
+    if new_path == path {
+        return Ok(new_path);
+    }
+    ensure_free(&new_path)?;
+    provider(path)?
+        .rename(path, &new_path)
+        .map_err(|e| FileOperationError::from_io(path, e))?;
+    Ok(new_path)
+}
+
+// Receives progress from long running operations, returning an error aborts them
+pub trait Progress {
+    fn add_bytes(&mut self, bytes: u64) -> OperationResult<()>;
+    fn add_item(&mut self) -> OperationResult<()>;
+    // An item left out because it cannot be written there, like a device in an archive
+    fn skip(&mut self, _path: &Path) {}
+}
+
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn add_bytes(&mut self, _bytes: u64) -> OperationResult<()> {
+        Ok(())
+    }
+
+    fn add_item(&mut self) -> OperationResult<()> {
+        Ok(())
+    }
+}
+
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+
+// Works across providers, which is how items are copied out of archives
+pub fn copy_to(source: &Path, target: &Path, progress: &mut dyn Progress) -> OperationResult<()> {
+    ensure_free(target)?;
+    ensure_not_into_itself(source, target)?;
+    copy_recursive(source, target, progress)
+}
+
+// Falls back to copy and delete when `target` is on another filesystem or provider
+pub fn move_to(source: &Path, target: &Path, progress: &mut dyn Progress) -> OperationResult<()> {
+    ensure_free(target)?;
+    ensure_not_into_itself(source, target)?;
+
+    let source_fs = provider(source)?;
+    if !Arc::ptr_eq(&source_fs, &provider(target)?) {
+        copy_recursive(source, target, progress)?;
+        return delete(source, &mut NoProgress);
+    }
+    match source_fs.rename(source, target) {
+        Ok(()) => progress.add_item(),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_recursive(source, target, progress)?;
+            delete(source, &mut NoProgress)
+        }
+        Err(e) => Err(FileOperationError::from_io(source, e)),
+    }
+}
+
+pub fn delete(path: &Path, progress: &mut dyn Progress) -> OperationResult<()> {
+    delete_recursive(provider(path)?.as_ref(), path, progress)
+}
+
+fn delete_recursive(
+    path_fs: &dyn Provider,
+    path: &Path,
+    progress: &mut dyn Progress,
+) -> OperationResult<()> {
+    let metadata = path_fs
+        .symlink_metadata(path)
+        .map_err(|e| FileOperationError::from_io(path, e))?;
+
+    if metadata.is_dir() {
+        for entry in path_fs
+            .read_dir(path)
+            .map_err(|e| FileOperationError::from_io(path, e))?
+        {
+            delete_recursive(path_fs, &entry, progress)?;
+        }
+        path_fs
+            .remove_dir(path)
+            .map_err(|e| FileOperationError::from_io(path, e))?;
+    } else {
+        path_fs
+            .remove_file(path)
+            .map_err(|e| FileOperationError::from_io(path, e))?;
+    }
+    progress.add_item()
+}
+
+// Total size in bytes and number of items below `path`, used to size progress bars
+pub fn measure(path: &Path) -> (u64, u64) {
+    match vfs::resolve(path) {
+        Ok(path_fs) => measure_recursive(path_fs.as_ref(), path),
+        Err(_) => (0, 0),
+    }
+}
+
+fn measure_recursive(path_fs: &dyn Provider, path: &Path) -> (u64, u64) {
+    let metadata = match path_fs.symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return (0, 0),
+    };
+
+    match metadata.kind {
+        FileKind::Directory => {
+            let (mut bytes, mut items) = (0, 1);
+            for entry in path_fs.read_dir(path).unwrap_or_default() {
+                let (entry_bytes, entry_items) = measure_recursive(path_fs, &entry);
+                bytes += entry_bytes;
+                items += entry_items;
+            }
+            (bytes, items)
+        }
+        FileKind::File => (metadata.len, 1),
+        _ => (0, 1),
+    }
+}
+
+pub fn copy_recursive(
+    source: &Path,
+    target: &Path,
+    progress: &mut dyn Progress,
+) -> OperationResult<()> {
+    let source_fs = provider(source)?;
+    let target_fs = provider(target)?;
+    let mut created = false;
+    copy_between(
+        source_fs.as_ref(),
+        source,
+        target_fs.as_ref(),
+        target,
+        progress,
+        &mut created,
+    )
+    .inspect_err(|_| {
+        // A failed or cancelled copy leaves nothing behind, but only what it made is removed
+        if created {
+            let _ = delete_recursive(target_fs.as_ref(), target, &mut NoProgress);
+        }
+    })
+}
+
+// Sets `created` once `target` exists, from then on a failure leaves a partial copy there
+fn copy_between(
+    source_fs: &dyn Provider,
+    source: &Path,
+    target_fs: &dyn Provider,
+    target: &Path,
+    progress: &mut dyn Progress,
+    created: &mut bool,
+) -> OperationResult<()> {
+    let source_error = |e: io::Error| FileOperationError::from_io(source, e);
+    let target_error = |e: io::Error| FileOperationError::from_io(target, e);
+    let metadata = source_fs.symlink_metadata(source).map_err(source_error)?;
+
+    if metadata.is_symlink() {
+        let link_target = source_fs.read_link(source).map_err(source_error)?;
+        target_fs
+            .symlink(&link_target, target)
+            .map_err(target_error)?;
+        *created = true;
+    } else if metadata.is_dir() {
+        target_fs.create_dir(target).map_err(target_error)?;
+        *created = true;
+        for entry in source_fs.read_dir(source).map_err(source_error)? {
+            if let Some(name) = entry.file_name() {
+                let entry_target = target.join(name);
+                copy_between(
+                    source_fs,
+                    &entry,
+                    target_fs,
+                    &entry_target,
+                    progress,
+                    &mut false,
+                )?;
+            }
+        }
+        if let Some(mode) = metadata.mode {
+            target_fs
+                .set_permissions(target, mode)
+                .map_err(target_error)?;
+        }
+    } else {
+        // Copies in chunks so progress can be reported and the copy can be cancelled
+        let mut reader = source_fs.open(source).map_err(source_error)?;
+        let mut writer = target_fs.create(target).map_err(target_error)?;
+        *created = true;
+        write_stream(&mut reader, source, &mut writer, target, progress)?;
+        drop(writer);
+        if let Some(mode) = metadata.mode {
+            target_fs
+                .set_permissions(target, mode)
+                .map_err(target_error)?;
+        }
+    }
+    progress.add_item()
+}
+
+// Copies everything `reader` yields to `writer`, the paths name both ends in errors
+pub fn write_stream(
+    reader: &mut dyn Read,
+    source: &Path,
+    writer: &mut dyn Write,
+    target: &Path,
+    progress: &mut dyn Progress,
+) -> OperationResult<()> {
+    let mut buffer = vec![0; COPY_BUFFER_SIZE];
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(FileOperationError::from_io(source, e)),
+        };
+        writer
+            .write_all(&buffer[..read])
+            .map_err(|e| FileOperationError::from_io(target, e))?;
+        progress.add_bytes(read as u64)?;
+    }
+    writer
+        .flush()
+        .map_err(|e| FileOperationError::from_io(target, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Cancels the copy at the first byte
+    struct Cancel;
+
+    impl Progress for Cancel {
+        fn add_bytes(&mut self, _bytes: u64) -> OperationResult<()> {
+            Err(FileOperationError::Cancelled)
+        }
+
+        fn add_item(&mut self) -> OperationResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_copy_removes_what_it_created() {
+        let directory = tempfile::tempdir().unwrap();
+        let source = directory.path().join("source");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("nested/file"), "data").unwrap();
+
+        let target = directory.path().join("target");
+        let result = copy_recursive(&source, &target, &mut Cancel);
+        assert!(matches!(result, Err(FileOperationError::Cancelled)));
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn failed_copy_keeps_an_existing_target() {
+        let directory = tempfile::tempdir().unwrap();
+        let source = directory.path().join("source");
+        fs::write(&source, "new").unwrap();
+        let target = directory.path().join("target");
+        fs::write(&target, "old").unwrap();
+
+        let result = copy_recursive(&source, &target, &mut NoProgress);
+        assert!(matches!(result, Err(FileOperationError::AlreadyExists(_))));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+    }
+}
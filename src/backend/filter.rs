@@ -0,0 +1,252 @@
+// Narrows a listing to the names matching a pattern. Every kind of pattern becomes a regex, so
+// matching and highlighting work the same for all of them. Like in vim, a pattern without
+// uppercase letters ignores case
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FilterKind {
+    #[default]
+    Substring,
+    Glob,
+    Regex,
+}
+
+impl FilterKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FilterKind::Substring => "substring",
+            FilterKind::Glob => "glob",
+            FilterKind::Regex => "regex",
+        }
+    }
+
+    pub fn next(&self) -> FilterKind {
+        match self {
+            FilterKind::Substring => FilterKind::Glob,
+            FilterKind::Glob => FilterKind::Regex,
+            FilterKind::Regex => FilterKind::Substring,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Filter {
+    kind: FilterKind,
+    pattern: String,
+    regex: Regex,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, pattern: &str) -> Result<Filter, regex::Error> {
+        let source = match kind {
+            FilterKind::Substring => regex::escape(pattern),
+            FilterKind::Glob => glob_to_regex(pattern),
+            FilterKind::Regex => pattern.to_string(),
+        };
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(!pattern.chars().any(char::is_uppercase))
+            .build()?;
+
+        Ok(Filter {
+            kind,
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    // Byte ranges of `name` to highlight, None when it does not match. A glob matches the whole
+    // name, only its literal parts are highlighted
+    pub fn matches(&self, name: &str) -> Option<Vec<Range<usize>>> {
+        match self.kind {
+            FilterKind::Glob => {
+                let captures = self.regex.captures(name)?;
+                Some(
+                    captures
+                        .iter()
+                        .skip(1)
+                        .flatten()
+                        .map(|literal| literal.range())
+                        .collect(),
+                )
+            }
+            FilterKind::Substring | FilterKind::Regex => {
+                let mut ranges = self.regex.find_iter(name).map(|found| found.range());
+                let first = ranges.next()?;
+                Some(
+                    std::iter::once(first)
+                        .chain(ranges)
+                        .filter(|range| !range.is_empty())
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+// `*`, `?` and `[...]` like in the shell, runs of other characters become groups so they can be
+// highlighted. A `[` without a closing `]` is taken literally
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::from("^");
+    let mut literal = String::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let wildcard = match chars[index] {
+            '*' => Some((String::from(".*"), 1)),
+            '?' => Some((String::from("."), 1)),
+            '[' => glob_class(&chars[index..]),
+            _ => None,
+        };
+        match wildcard {
+            Some((wildcard, length)) => {
+                push_literal(&mut regex, &mut literal);
+                regex.push_str(&wildcard);
+                index += length;
+            }
+            None => {
+                literal.push(chars[index]);
+                index += 1;
+            }
+        }
+    }
+    push_literal(&mut regex, &mut literal);
+    regex.push('$');
+    regex
+}
+
+fn push_literal(regex: &mut String, literal: &mut String) {
+    if !literal.is_empty() {
+        regex.push('(');
+        regex.push_str(&regex::escape(literal));
+        regex.push(')');
+        literal.clear();
+    }
+}
+
+// The bracket expression `chars` starts with as a regex class, and how many chars it spans
+fn glob_class(chars: &[char]) -> Option<(String, usize)> {
+    let mut class = String::from("[");
+    let mut index = 1;
+    if matches!(chars.get(index), Some('!' | '^')) {
+        class.push('^');
+        index += 1;
+    }
+
+    // A `]` right after the opening bracket is part of the class
+    let start = index;
+    loop {
+        match *chars.get(index)? {
+            ']' if index > start => {
+                class.push(']');
+                return Some((class, index + 1));
+            }
+            c @ ('\\' | '[' | ']' | '&' | '~') => {
+                class.push('\\');
+                class.push(c);
+            }
+            c => class.push(c),
+        }
+        index += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str) -> Filter {
+        Filter::new(FilterKind::Glob, pattern).unwrap()
+    }
+
+    // Highlighted ranges as (start, end) pairs
+    fn highlights(filter: &Filter, name: &str) -> Option<Vec<(usize, usize)>> {
+        filter.matches(name).map(|ranges| {
+            ranges
+                .into_iter()
+                .map(|range| (range.start, range.end))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn glob_escapes_regex_metacharacters() {
+        assert_eq!(glob_to_regex("a.b+(c)"), r"^(a\.b\+\(c\))$");
+
+        let filter = glob("v1.0+build");
+        assert!(filter.matches("v1.0+build").is_some());
+        assert!(filter.matches("v1x0+build").is_none());
+        assert!(filter.matches("v1.00build").is_none());
+    }
+
+    #[test]
+    fn glob_translates_wildcards() {
+        assert_eq!(glob_to_regex("*.rs"), r"^.*(\.rs)$");
+        assert_eq!(glob_to_regex("a?c"), "^(a).(c)$");
+
+        let filter = glob("*.rs");
+        assert!(filter.matches("main.rs").is_some());
+        assert!(filter.matches("main.rs.bak").is_none());
+        let filter = glob("a?c");
+        assert!(filter.matches("abc").is_some());
+        assert!(filter.matches("ac").is_none());
+        assert!(filter.matches("abbc").is_none());
+    }
+
+    #[test]
+    fn glob_classes_and_unclosed_brackets() {
+        assert_eq!(glob_to_regex("[!a]"), "^[^a]$");
+        assert!(glob("[]x]").matches("]").is_some());
+        // Without a closing `]` the bracket is a literal
+        assert_eq!(glob_to_regex("a["), r"^(a\[)$");
+    }
+
+    #[test]
+    fn glob_highlights_only_literal_parts() {
+        assert_eq!(highlights(&glob("*.rs"), "main.rs"), Some(vec![(4, 7)]));
+    }
+
+    #[test]
+    fn lowercase_patterns_ignore_case() {
+        let patterns = [
+            (FilterKind::Substring, "read"),
+            (FilterKind::Glob, "read*"),
+            (FilterKind::Regex, "^read"),
+        ];
+        for (kind, pattern) in patterns {
+            let filter = Filter::new(kind, pattern).unwrap();
+            assert!(filter.matches("README").is_some(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn uppercase_makes_patterns_case_sensitive() {
+        let patterns = [
+            (FilterKind::Substring, "Read"),
+            (FilterKind::Glob, "Read*"),
+            (FilterKind::Regex, "^Read"),
+        ];
+        for (kind, pattern) in patterns {
+            let filter = Filter::new(kind, pattern).unwrap();
+            assert!(filter.matches("Readme").is_some(), "{}", pattern);
+            assert!(filter.matches("readme").is_none(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn substring_is_taken_literally() {
+        let filter = Filter::new(FilterKind::Substring, "a.c").unwrap();
+        assert_eq!(highlights(&filter, "xa.cy"), Some(vec![(1, 4)]));
+        assert!(filter.matches("abc").is_none());
+    }
+}
@@ -0,0 +1,303 @@
+// Fuzzy finds files below a directory. Walker threads stream the paths they find to a ranker
+// thread, which scores them against the query and reports the best matches as app events. Both
+// stop once the Finder is dropped
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{self, Receiver, Sender};
+use ignore::{WalkBuilder, WalkState};
+use nucleo_matcher::pattern::{AtomKind, CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher, Utf32Str};
+
+use super::events::RstuifmEvent;
+
+// More than fit on any screen
+const MAX_RESULTS: usize = 200;
+// While paths keep coming the results are reported at most this often
+const REPORT_INTERVAL: Duration = Duration::from_millis(50);
+
+static NEXT_FINDER_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone)]
+pub struct FinderMatch {
+    // Relative to the directory searched
+    pub path: String,
+    // Matched chars, for highlighting
+    pub indices: Vec<u32>,
+}
+
+pub struct FinderResults {
+    finder_id: usize,
+    pub matches: Vec<FinderMatch>,
+    // Paths matching the query, of `total` found so far
+    pub matched: usize,
+    pub total: usize,
+    pub walking: bool,
+}
+
+pub struct Finder {
+    id: usize,
+    pub root: PathBuf,
+    query_tx: Sender<String>,
+    cancelled: Arc<AtomicBool>,
+    pub results: FinderResults,
+    pub selected: usize,
+}
+
+impl Finder {
+    // Starts walking `root`, paths ignored by git and hidden ones unless they are shown are left out
+    pub fn start(root: PathBuf, show_hidden: bool, event_tx: Sender<RstuifmEvent>) -> Finder {
+        let id = NEXT_FINDER_ID.fetch_add(1, Ordering::SeqCst);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (query_tx, query_rx) = channel::unbounded();
+        let (path_tx, path_rx) = channel::unbounded();
+
+        let walk_root = root.clone();
+        let walk_cancelled = cancelled.clone();
+        thread::spawn(move || walk(&walk_root, show_hidden, &walk_cancelled, path_tx));
+        thread::spawn(move || rank(id, query_rx, path_rx, event_tx));
+
+        Finder {
+            id,
+            root,
+            query_tx,
+            cancelled,
+            results: FinderResults {
+                finder_id: id,
+                matches: Vec::new(),
+                matched: 0,
+                total: 0,
+                walking: true,
+            },
+            selected: 0,
+        }
+    }
+
+    pub fn set_query(&self, query: &str) {
+        let _ = self.query_tx.send(query.to_string());
+    }
+
+    // Takes results meant for this finder, the selection moves back to the best match
+    pub fn accept(&mut self, results: FinderResults) -> bool {
+        if results.finder_id != self.id {
+            return false;
+        }
+        self.results = results;
+        self.selected = self
+            .selected
+            .min(self.results.matches.len().saturating_sub(1));
+        true
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.results.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        self.results
+            .matches
+            .get(self.selected)
+            .map(|found| self.root.join(&found.path))
+    }
+}
+
+impl Drop for Finder {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+fn walk(root: &Path, show_hidden: bool, cancelled: &AtomicBool, path_tx: Sender<String>) {
+    WalkBuilder::new(root)
+        .hidden(!show_hidden)
+        // .gitignore files count outside of repositories too
+        .require_git(false)
+        .build_parallel()
+        .run(|| {
+            let path_tx = path_tx.clone();
+            Box::new(move |entry| {
+                if cancelled.load(Ordering::SeqCst) {
+                    return WalkState::Quit;
+                }
+                if let Ok(entry) = entry {
+                    let path = entry.path().strip_prefix(root).unwrap_or(entry.path());
+                    if entry.depth() > 0 && path_tx.send(path.to_string_lossy().into()).is_err() {
+                        return WalkState::Quit;
+                    }
+                }
+                WalkState::Continue
+            })
+        });
+}
+
+fn rank(
+    finder_id: usize,
+    query_rx: Receiver<String>,
+    path_rx: Receiver<String>,
+    event_tx: Sender<RstuifmEvent>,
+) {
+    let mut ranker = Ranker::new();
+    let mut walking = true;
+    // Paths came in since the last report
+    let mut changed = true;
+    let mut last_report = Instant::now();
+
+    loop {
+        // A new query is answered right away, new paths wait for the next report
+        let mut urgent = false;
+        if walking {
+            let report_due = if changed {
+                channel::after(REPORT_INTERVAL.saturating_sub(last_report.elapsed()))
+            } else {
+                channel::never()
+            };
+            channel::select! {
+                recv(query_rx) -> query => match query {
+                    Ok(query) => {
+                        let query = query_rx.try_iter().last().unwrap_or(query);
+                        ranker.set_query(&query);
+                        urgent = true;
+                    }
+                    Err(_) => return,
+                },
+                recv(path_rx) -> path => match path {
+                    Ok(path) => {
+                        ranker.add(path);
+                        // Taken in bounded chunks so queries are not kept waiting
+                        for path in path_rx.try_iter().take(10_000) {
+                            ranker.add(path);
+                        }
+                        changed = true;
+                    }
+                    Err(_) => {
+                        walking = false;
+                        urgent = true;
+                    }
+                },
+                recv(report_due) -> _ => (),
+            }
+        } else {
+            match query_rx.recv() {
+                Ok(query) => {
+                    let query = query_rx.try_iter().last().unwrap_or(query);
+                    ranker.set_query(&query);
+                    urgent = true;
+                }
+                Err(_) => return,
+            }
+        }
+
+        if urgent || (changed && last_report.elapsed() >= REPORT_INTERVAL) {
+            let results = ranker.results(finder_id, walking);
+            if event_tx.send(RstuifmEvent::Finder(results)).is_err() {
+                return;
+            }
+            changed = false;
+            last_report = Instant::now();
+        }
+    }
+}
+
+struct Ranker {
+    matcher: Matcher,
+    paths: Vec<String>,
+    query: String,
+    pattern: Pattern,
+    // Scores and indices of the paths matching the query
+    matching: Vec<(u32, usize)>,
+    buffer: Vec<char>,
+}
+
+impl Ranker {
+    fn new() -> Ranker {
+        Ranker {
+            matcher: Matcher::new(Config::DEFAULT.match_paths()),
+            paths: Vec::new(),
+            query: String::new(),
+            pattern: Pattern::default(),
+            matching: Vec::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    fn score(&mut self, index: usize) -> Option<u32> {
+        let haystack = Utf32Str::new(&self.paths[index], &mut self.buffer);
+        self.pattern.score(haystack, &mut self.matcher)
+    }
+
+    fn add(&mut self, path: String) {
+        self.paths.push(path);
+        let index = self.paths.len() - 1;
+        if let Some(score) = self.score(index) {
+            self.matching.push((score, index));
+        }
+    }
+
+    // Words are matched separately, so typing more only narrows the matches and just those are
+    // scored again
+    fn set_query(&mut self, query: &str) {
+        let narrows = query.starts_with(&self.query) && !self.query.ends_with('\\');
+        self.query = query.to_string();
+        self.pattern = Pattern::new(
+            query,
+            CaseMatching::Smart,
+            Normalization::Smart,
+            AtomKind::Fuzzy,
+        );
+
+        let candidates: Vec<usize> = if narrows {
+            self.matching.iter().map(|(_, index)| *index).collect()
+        } else {
+            (0..self.paths.len()).collect()
+        };
+        self.matching = candidates
+            .into_iter()
+            .filter_map(|index| self.score(index).map(|score| (score, index)))
+            .collect();
+    }
+
+    // Best scores first, then shorter paths, then the order they were found in
+    fn results(&mut self, finder_id: usize, walking: bool) -> FinderResults {
+        // The heap's top is the worst of the best so far
+        let mut best = BinaryHeap::with_capacity(MAX_RESULTS + 1);
+        for &(score, index) in &self.matching {
+            best.push((Reverse(score), self.paths[index].len(), index));
+            if best.len() > MAX_RESULTS {
+                best.pop();
+            }
+        }
+
+        let mut matches = Vec::with_capacity(best.len());
+        for (_, _, index) in best.into_sorted_vec() {
+            let mut indices = Vec::new();
+            let haystack = Utf32Str::new(&self.paths[index], &mut self.buffer);
+            self.pattern
+                .indices(haystack, &mut self.matcher, &mut indices);
+            indices.sort_unstable();
+            indices.dedup();
+            matches.push(FinderMatch {
+                path: self.paths[index].clone(),
+                indices,
+            });
+        }
+
+        FinderResults {
+            finder_id,
+            matches,
+            matched: self.matching.len(),
+            total: self.paths.len(),
+            walking,
+        }
+    }
+}
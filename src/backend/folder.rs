@@ -1,3 +1,4 @@
+use std::fmt;
 use std::path::PathBuf;
 use anyhow::{anyhow, Result};
 
@@ -7,9 +8,9 @@ pub struct Folder {
     name: String,
 }
 
-impl ToString for Folder {
-    fn to_string(&self) -> String {
-        self.name.clone()
+impl fmt::Display for Folder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
     }
 }
 
@@ -23,4 +24,8 @@ impl Folder {
             .to_string();
         Ok(Folder { path, name })
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 }
@@ -0,0 +1,321 @@
+// Searches file contents below a directory. Walker threads read the files, skipping binary ones and
+// those ignored by git, and their hits come back in batches as app events. Dropping the Search
+// stops the walk
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{self, Sender};
+use ignore::{WalkBuilder, WalkState};
+use regex::{Regex, RegexBuilder};
+
+use super::events::RstuifmEvent;
+use crate::helper_functions;
+
+// Searching stops after this many hits, a pattern matching everywhere would fill the memory
+const MAX_HITS: usize = 10_000;
+// Longer lines are cut, minified files would make single hits huge
+const MAX_LINE_LENGTH: usize = 500;
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+// Like grep, files with a NUL byte in their first block count as binary
+const BINARY_CHECK_LENGTH: u64 = 8192;
+
+static NEXT_SEARCH_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone)]
+pub struct GrepHit {
+    pub path: PathBuf,
+    // Counted from 1 like editors do
+    pub line: usize,
+    pub text: String,
+    // Byte ranges of the matches in `text`
+    pub matches: Vec<Range<usize>>,
+}
+
+pub struct GrepBatch {
+    search_id: usize,
+    pub hits: Vec<GrepHit>,
+    pub done: bool,
+}
+
+// Lines around a hit, for the preview
+pub struct GrepContext {
+    pub first_line: usize,
+    pub lines: Vec<String>,
+}
+
+pub struct Search {
+    id: usize,
+    pub root: PathBuf,
+    pub pattern: String,
+    cancelled: Arc<AtomicBool>,
+    pub hits: Vec<GrepHit>,
+    pub selected: usize,
+    // First hit on screen
+    offset: usize,
+    pub running: bool,
+    // Context of the selected hit, as (hit, height) it was read for
+    context: Option<((PathBuf, usize, usize), Arc<GrepContext>)>,
+}
+
+impl Search {
+    // Patterns are regexes, ones without uppercase letters ignore case
+    pub fn start(
+        root: PathBuf,
+        pattern: &str,
+        show_hidden: bool,
+        event_tx: Sender<RstuifmEvent>,
+    ) -> Result<Search, regex::Error> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(!pattern.chars().any(char::is_uppercase))
+            .build()?;
+        let id = NEXT_SEARCH_ID.fetch_add(1, Ordering::SeqCst);
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let walk_root = root.clone();
+        let walk_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            search(
+                id,
+                &walk_root,
+                &regex,
+                show_hidden,
+                &walk_cancelled,
+                &event_tx,
+            )
+        });
+
+        Ok(Search {
+            id,
+            root,
+            pattern: pattern.to_string(),
+            cancelled,
+            hits: Vec::new(),
+            selected: 0,
+            offset: 0,
+            running: true,
+            context: None,
+        })
+    }
+
+    // Takes a batch meant for this search, unless it was stopped already
+    pub fn accept(&mut self, batch: GrepBatch) -> bool {
+        if batch.search_id != self.id || !self.running {
+            return false;
+        }
+        self.hits.extend(batch.hits);
+        if batch.done {
+            self.running = false;
+        }
+        true
+    }
+
+    // Stops searching, the hits found so far stay
+    pub fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.running = false;
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.hits.len() >= MAX_HITS
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.hits.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected_hit(&self) -> Option<&GrepHit> {
+        self.hits.get(self.selected)
+    }
+
+    // The hits that fit in `height` rows, scrolled just enough to show the selected one, and the
+    // index of that among them
+    pub fn visible_hits(&mut self, height: usize) -> (&[GrepHit], usize) {
+        let height = height.max(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+        let end = (self.offset + height).min(self.hits.len());
+        (
+            &self.hits[self.offset.min(end)..end],
+            self.selected - self.offset,
+        )
+    }
+
+    // The file was edited, its lines are read again
+    pub fn forget_context(&mut self) {
+        self.context = None;
+    }
+
+    // Lines around the selected hit, about `height` of them with the hit in the middle. They are
+    // read again only when the selection changes
+    pub fn context(&mut self, height: usize) -> Option<Arc<GrepContext>> {
+        let hit = self.hits.get(self.selected)?;
+        let key = (hit.path.clone(), hit.line, height);
+        if let Some((cached_key, context)) = &self.context {
+            if *cached_key == key {
+                return Some(context.clone());
+            }
+        }
+
+        let first_line = hit.line.saturating_sub(height / 2).max(1);
+        let lines = BufReader::new(File::open(&hit.path).ok()?)
+            .split(b'\n')
+            .skip(first_line - 1)
+            .take(height)
+            .map_while(Result::ok)
+            .map(|line| {
+                String::from_utf8_lossy(&line)
+                    .trim_end()
+                    .replace('\t', "    ")
+            })
+            .collect();
+        let context = Arc::new(GrepContext { first_line, lines });
+        self.context = Some((key, context.clone()));
+        Some(context)
+    }
+}
+
+impl Drop for Search {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+fn search(
+    search_id: usize,
+    root: &Path,
+    regex: &Regex,
+    show_hidden: bool,
+    cancelled: &AtomicBool,
+    event_tx: &Sender<RstuifmEvent>,
+) {
+    let (hit_tx, hit_rx) = channel::unbounded();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            WalkBuilder::new(root)
+                .hidden(!show_hidden)
+                // .gitignore files count outside of repositories too
+                .require_git(false)
+                .build_parallel()
+                .run(|| {
+                    let hit_tx = hit_tx.clone();
+                    Box::new(move |entry| {
+                        if cancelled.load(Ordering::SeqCst) {
+                            return WalkState::Quit;
+                        }
+                        let entry = match entry {
+                            Ok(entry) if entry.file_type().is_some_and(|kind| kind.is_file()) => {
+                                entry
+                            }
+                            _ => return WalkState::Continue,
+                        };
+                        let hits = search_file(entry.path(), regex);
+                        if !hits.is_empty() && hit_tx.send(hits).is_err() {
+                            return WalkState::Quit;
+                        }
+                        WalkState::Continue
+                    })
+                });
+        });
+
+        let mut batch = Vec::new();
+        let mut found = 0;
+        let mut last_sent = Instant::now();
+        loop {
+            let done = match hit_rx.recv_timeout(BATCH_INTERVAL) {
+                Ok(mut hits) => {
+                    hits.truncate(MAX_HITS - found);
+                    found += hits.len();
+                    batch.append(&mut hits);
+                    found >= MAX_HITS
+                }
+                Err(channel::RecvTimeoutError::Timeout) => false,
+                Err(channel::RecvTimeoutError::Disconnected) => true,
+            };
+            if done {
+                cancelled.store(true, Ordering::SeqCst);
+            }
+            if done || last_sent.elapsed() >= BATCH_INTERVAL {
+                let batch = GrepBatch {
+                    search_id,
+                    hits: std::mem::take(&mut batch),
+                    done,
+                };
+                if event_tx.send(RstuifmEvent::Grep(batch)).is_err() || done {
+                    cancelled.store(true, Ordering::SeqCst);
+                    return;
+                }
+                last_sent = Instant::now();
+            }
+        }
+    });
+}
+
+// Hits in the file at `path`, none for binary files and ones that cannot be read
+fn search_file(path: &Path, regex: &Regex) -> Vec<GrepHit> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+    let mut start = Vec::new();
+    if (&mut file)
+        .take(BINARY_CHECK_LENGTH)
+        .read_to_end(&mut start)
+        .is_err()
+        || !helper_functions::looks_like_text(&start)
+    {
+        return Vec::new();
+    }
+
+    let reader = BufReader::new(start.as_slice().chain(file));
+    let mut hits = Vec::new();
+    for (index, line) in reader.split(b'\n').enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let line = String::from_utf8_lossy(&line);
+        if !regex.is_match(&line) {
+            continue;
+        }
+
+        // Indentation is left out, the match ranges move along
+        let indent = line.len() - line.trim_start().len();
+        let text = truncate(line.trim(), MAX_LINE_LENGTH);
+        let matches = regex
+            .find_iter(&line)
+            .map(|found| found.start().saturating_sub(indent)..found.end() - indent)
+            .map(|range| range.start.min(text.len())..range.end.min(text.len()))
+            .filter(|range| !range.is_empty())
+            .collect();
+        hits.push(GrepHit {
+            path: path.to_path_buf(),
+            line: index + 1,
+            text: text.to_string(),
+            matches,
+        });
+    }
+    hits
+}
+
+// At most `length` chars of `text`
+fn truncate(text: &str, length: usize) -> &str {
+    match text.char_indices().nth(length) {
+        Some((index, _)) => &text[..index],
+        None => text,
+    }
+}
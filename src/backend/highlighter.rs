@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::SystemTime;
+
+use crossbeam::channel::{self, Sender};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{self, Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+use super::events::RstuifmEvent;
+
+const THEME: &str = "base16-ocean.dark";
+
+pub type HighlightedLines = Arc<Vec<Line<'static>>>;
+
+// Identifies one version of a file, highlights are reused until it is modified
+pub type HighlightKey = (PathBuf, Option<SystemTime>);
+
+pub struct HighlightRequest {
+    pub key: HighlightKey,
+    pub lines: Vec<String>,
+}
+
+pub struct HighlightResult {
+    pub key: HighlightKey,
+    // None when no syntax matches the file
+    pub lines: Option<HighlightedLines>,
+}
+
+// Highlights previews on its own thread, results come back as app events
+pub struct Highlighter {
+    request_tx: Sender<HighlightRequest>,
+}
+
+impl Highlighter {
+    pub fn new(event_tx: Sender<RstuifmEvent>) -> Highlighter {
+        let (request_tx, request_rx) = channel::unbounded::<HighlightRequest>();
+
+        thread::spawn(move || {
+            // Loading the syntax definitions takes a moment, so it is done here and not at startup
+            let syntax_set = SyntaxSet::load_defaults_nonewlines();
+            let theme = ThemeSet::load_defaults()
+                .themes
+                .remove(THEME)
+                .unwrap_or_default();
+
+            while let Ok(mut request) = request_rx.recv() {
+                // Only the newest request matters when the user scrolls past files quickly
+                while let Ok(newer) = request_rx.try_recv() {
+                    request = newer;
+                }
+
+                let lines = find_syntax(&syntax_set, &request.key.0, &request.lines)
+                    .map(|syntax| Arc::new(highlight(&syntax_set, syntax, &theme, &request.lines)));
+                let result = HighlightResult {
+                    key: request.key,
+                    lines,
+                };
+                if event_tx.send(RstuifmEvent::Highlight(result)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Highlighter { request_tx }
+    }
+
+    pub fn request(&self, request: HighlightRequest) {
+        let _ = self.request_tx.send(request);
+    }
+}
+
+// By extension first, then by the first line, which covers shebangs
+fn find_syntax<'a>(
+    syntax_set: &'a SyntaxSet,
+    path: &Path,
+    lines: &[String],
+) -> Option<&'a SyntaxReference> {
+    path.extension()
+        .and_then(|extension| syntax_set.find_syntax_by_extension(&extension.to_string_lossy()))
+        .or_else(|| {
+            path.file_name()
+                .and_then(|name| syntax_set.find_syntax_by_extension(&name.to_string_lossy()))
+        })
+        .or_else(|| {
+            lines
+                .first()
+                .and_then(|line| syntax_set.find_syntax_by_first_line(line))
+        })
+        .filter(|syntax| syntax.name != "Plain Text")
+}
+
+fn highlight(
+    syntax_set: &SyntaxSet,
+    syntax: &SyntaxReference,
+    theme: &Theme,
+    lines: &[String],
+) -> Vec<Line<'static>> {
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    lines
+        .iter()
+        .map(|line| match highlighter.highlight_line(line, syntax_set) {
+            Ok(regions) => Line::from(
+                regions
+                    .into_iter()
+                    .map(|(style, text)| Span::styled(text.to_string(), convert_style(style)))
+                    .collect::<Vec<Span>>(),
+            ),
+            Err(_) => Line::from(line.clone()),
+        })
+        .collect()
+}
+
+fn convert_style(style: highlighting::Style) -> Style {
+    let color = style.foreground;
+    let mut converted = Style::default().fg(Color::Rgb(color.r, color.g, color.b));
+    if style.font_style.contains(highlighting::FontStyle::BOLD) {
+        converted = converted.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(highlighting::FontStyle::ITALIC) {
+        converted = converted.add_modifier(Modifier::ITALIC);
+    }
+    converted
+}
@@ -0,0 +1,540 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{self, Receiver, Sender};
+
+use super::archive;
+use super::events::RstuifmEvent;
+use super::file_operations::{self, FileOperationError, OperationResult, Progress};
+use super::journal::Operation;
+use super::trash;
+use super::vfs;
+
+const WORKER_COUNT: usize = 2;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone)]
+pub enum JobTask {
+    Copy {
+        source: PathBuf,
+        target: PathBuf,
+        overwrite: bool,
+    },
+    Move {
+        source: PathBuf,
+        target: PathBuf,
+        overwrite: bool,
+    },
+    Delete(PathBuf),
+    Trash(PathBuf),
+    Restore {
+        name: String,
+        original_path: PathBuf,
+    },
+    Purge(String),
+    Compress {
+        sources: Vec<PathBuf>,
+        target: PathBuf,
+    },
+    Extract {
+        archive: PathBuf,
+        target: PathBuf,
+    },
+    Undo(Operation),
+    Redo(Operation),
+}
+
+impl JobTask {
+    // Directories whose listing changes once the task ran
+    pub fn affected_directories(&self) -> Vec<PathBuf> {
+        let paths: Vec<&Path> = match self {
+            JobTask::Copy { target, .. }
+            | JobTask::Compress { target, .. }
+            | JobTask::Extract { target, .. } => vec![target],
+            JobTask::Move { source, target, .. } => vec![source, target],
+            JobTask::Delete(path) | JobTask::Trash(path) => vec![path],
+            JobTask::Restore { original_path, .. } => vec![original_path],
+            JobTask::Purge(_) => vec![],
+            JobTask::Undo(operation) | JobTask::Redo(operation) => {
+                return operation.affected_directories()
+            }
+        };
+
+        paths
+            .into_iter()
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect()
+    }
+
+    // Paths that no longer hold what was cached for them
+    pub fn invalidated_paths(&self) -> Vec<PathBuf> {
+        match self {
+            JobTask::Copy { target, .. }
+            | JobTask::Compress { target, .. }
+            | JobTask::Extract { target, .. } => vec![target.clone()],
+            JobTask::Move { source, target, .. } => vec![source.clone(), target.clone()],
+            JobTask::Delete(path) | JobTask::Trash(path) => vec![path.clone()],
+            JobTask::Restore { original_path, .. } => vec![original_path.clone()],
+            JobTask::Purge(_) => vec![],
+            JobTask::Undo(operation) | JobTask::Redo(operation) => operation.invalidated_paths(),
+        }
+    }
+
+    // Whether the contents of the trash change, overwritten items go there too
+    pub fn touches_trash(&self) -> bool {
+        matches!(
+            self,
+            JobTask::Copy {
+                overwrite: true,
+                ..
+            } | JobTask::Move {
+                overwrite: true,
+                ..
+            } | JobTask::Trash(_)
+                | JobTask::Restore { .. }
+                | JobTask::Purge(_)
+                | JobTask::Undo(Operation::Copy { .. } | Operation::Trash { .. })
+                | JobTask::Redo(Operation::Trash { .. })
+        )
+    }
+
+    // Bytes and items the task is expected to process
+    fn measure(&self) -> (u64, u64) {
+        match self {
+            JobTask::Copy { source, .. } => file_operations::measure(source),
+            JobTask::Move { source, target, .. } => {
+                if crosses_devices(source, target) {
+                    file_operations::measure(source)
+                } else {
+                    (0, 1)
+                }
+            }
+            JobTask::Delete(path) => (0, file_operations::measure(path).1),
+            JobTask::Trash(path) => {
+                if crosses_devices(path, &trash::files_directory().join("_")) {
+                    file_operations::measure(path)
+                } else {
+                    (0, 1)
+                }
+            }
+            JobTask::Restore {
+                name,
+                original_path,
+            } => {
+                let trashed_path = trash::files_directory().join(name);
+                if crosses_devices(&trashed_path, original_path) {
+                    file_operations::measure(&trashed_path)
+                } else {
+                    (0, 1)
+                }
+            }
+            JobTask::Purge(name) => (
+                0,
+                file_operations::measure(&trash::files_directory().join(name)).1,
+            ),
+            JobTask::Compress { sources, .. } => sources
+                .iter()
+                .map(|source| file_operations::measure(source))
+                .fold((0, 0), |(bytes, items), (source_bytes, source_items)| {
+                    (bytes + source_bytes, items + source_items)
+                }),
+            JobTask::Extract { archive, .. } => archive::measure_contents(archive),
+            JobTask::Redo(Operation::Copy { source, .. }) => file_operations::measure(source),
+            JobTask::Undo(_) | JobTask::Redo(_) => (0, 1),
+        }
+    }
+
+    // Returns the operations for the undo journal, deleted items cannot come back
+    fn run(&self, progress: &mut dyn Progress) -> OperationResult<Vec<Operation>> {
+        match self {
+            JobTask::Copy {
+                source,
+                target,
+                overwrite,
+            } => replacing(source, target, *overwrite, || {
+                file_operations::copy_to(source, target, progress)?;
+                Ok(Operation::copy(source.clone(), target.clone()))
+            }),
+            JobTask::Move {
+                source,
+                target,
+                overwrite,
+            } => replacing(source, target, *overwrite, || {
+                file_operations::move_to(source, target, progress)?;
+                Ok(Operation::move_item(source.clone(), target.clone()))
+            }),
+            JobTask::Delete(path) => file_operations::delete(path, progress).map(|_| Vec::new()),
+            JobTask::Trash(path) => {
+                trash::trash(path, progress).map(|name| vec![Operation::trash(path.clone(), name)])
+            }
+            JobTask::Restore { name, .. } => trash::restore(name, progress).map(|_| Vec::new()),
+            JobTask::Purge(name) => trash::purge(name, progress).map(|_| Vec::new()),
+            JobTask::Compress { sources, target } => {
+                archive::compress(sources, target, progress).map(|_| Vec::new())
+            }
+            JobTask::Extract { archive, target } => {
+                archive::extract(archive, target, progress).map(|_| Vec::new())
+            }
+            JobTask::Undo(operation) => operation.undo(progress).map(|undone| vec![undone]),
+            JobTask::Redo(operation) => operation.redo(progress).map(|redone| vec![redone]),
+        }
+    }
+}
+
+// Runs `operation` after moving an item in its way to the trash, where undo finds it again. The
+// item is put back when the operation fails. The trash is local, so remote items are not replaced
+fn replacing(
+    source: &Path,
+    target: &Path,
+    overwrite: bool,
+    operation: impl FnOnce() -> OperationResult<Operation>,
+) -> OperationResult<Vec<Operation>> {
+    if !overwrite || !vfs::exists(target) {
+        return operation().map(|operation| vec![operation]);
+    }
+    if source.starts_with(target) {
+        return Err(FileOperationError::IntoItself(source.to_path_buf()));
+    }
+    if !file_operations::provider(target)?.is_local() {
+        return Err(FileOperationError::Io {
+            path: target.to_path_buf(),
+            source: io::Error::new(
+                io::ErrorKind::Unsupported,
+                "remote items cannot be overwritten, delete it first",
+            ),
+        });
+    }
+
+    let name = trash::trash(target, &mut file_operations::NoProgress)?;
+    match operation() {
+        Ok(operation) => Ok(vec![
+            Operation::trash(target.to_path_buf(), name),
+            operation,
+        ]),
+        Err(e) => {
+            let _ = trash::restore(&name, &mut file_operations::NoProgress);
+            Err(e)
+        }
+    }
+}
+
+fn crosses_devices(source: &Path, target: &Path) -> bool {
+    let device = |metadata: std::io::Result<vfs::Metadata>| {
+        metadata
+            .ok()
+            .and_then(|metadata| metadata.identity)
+            .map(|(device, _)| device)
+    };
+    let source_device = device(vfs::symlink_metadata(source));
+    let target_device = target
+        .parent()
+        .and_then(|parent| device(vfs::metadata(parent)));
+
+    match (source_device, target_device) {
+        (Some(source_device), Some(target_device)) => source_device != target_device,
+        _ => false,
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct JobProgress {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub items_done: u64,
+    pub items_total: u64,
+}
+
+impl JobProgress {
+    pub fn ratio(&self) -> f64 {
+        let (done, total) = if self.bytes_total > 0 {
+            (self.bytes_done, self.bytes_total)
+        } else {
+            (self.items_done, self.items_total)
+        };
+
+        if total == 0 {
+            0.0
+        } else {
+            (done as f64 / total as f64).min(1.0)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Paused,
+    Cancelling,
+}
+
+#[derive(Clone)]
+pub struct JobInfo {
+    pub id: usize,
+    pub description: String,
+    pub state: JobState,
+    pub progress: JobProgress,
+}
+
+pub enum JobEvent {
+    Started(usize),
+    Progress(usize, JobProgress),
+    Finished {
+        id: usize,
+        // Results of the tasks that succeeded, in task order
+        operations: Vec<Operation>,
+        // Items the tasks could not handle and left out
+        skipped: Vec<PathBuf>,
+        error: Option<FileOperationError>,
+    },
+}
+
+pub struct FinishedJob {
+    pub id: usize,
+    pub description: String,
+    pub tasks: Vec<JobTask>,
+    pub operations: Vec<Operation>,
+    pub skipped: Vec<PathBuf>,
+    pub error: Option<FileOperationError>,
+}
+
+#[derive(Default)]
+struct JobControl {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+}
+
+struct QueuedJob {
+    id: usize,
+    tasks: Vec<JobTask>,
+    control: Arc<JobControl>,
+}
+
+struct Job {
+    info: JobInfo,
+    tasks: Vec<JobTask>,
+    control: Arc<JobControl>,
+}
+
+// Runs file operations on worker threads and reports back through the app event channel
+pub struct JobManager {
+    jobs: Vec<Job>,
+    next_id: usize,
+    queue_tx: Sender<QueuedJob>,
+}
+
+impl JobManager {
+    pub fn new(event_tx: Sender<RstuifmEvent>) -> JobManager {
+        let (queue_tx, queue_rx) = channel::unbounded();
+
+        for _ in 0..WORKER_COUNT {
+            let queue_rx: Receiver<QueuedJob> = queue_rx.clone();
+            let event_tx = event_tx.clone();
+            thread::spawn(move || {
+                for job in queue_rx.iter() {
+                    run_job(job, &event_tx);
+                }
+            });
+        }
+
+        JobManager {
+            jobs: Vec::new(),
+            next_id: 0,
+            queue_tx,
+        }
+    }
+
+    pub fn submit(&mut self, description: String, tasks: Vec<JobTask>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let control = Arc::new(JobControl::default());
+        self.jobs.push(Job {
+            info: JobInfo {
+                id,
+                description,
+                state: JobState::Queued,
+                progress: JobProgress::default(),
+            },
+            tasks: tasks.clone(),
+            control: control.clone(),
+        });
+
+        let _ = self.queue_tx.send(QueuedJob { id, tasks, control });
+        id
+    }
+
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        self.jobs.iter().map(|job| job.info.clone()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    fn job_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.info.id == id)
+    }
+
+    pub fn toggle_pause(&mut self, id: usize) {
+        if let Some(job) = self.job_mut(id) {
+            let paused = !job.control.paused.load(Ordering::SeqCst);
+            job.control.paused.store(paused, Ordering::SeqCst);
+            job.info.state = match (paused, job.info.state) {
+                (_, JobState::Cancelling) => JobState::Cancelling,
+                (true, _) => JobState::Paused,
+                (false, JobState::Queued) => JobState::Queued,
+                (false, _) => JobState::Running,
+            };
+        }
+    }
+
+    pub fn cancel(&mut self, id: usize) {
+        if let Some(job) = self.job_mut(id) {
+            job.control.cancelled.store(true, Ordering::SeqCst);
+            job.info.state = JobState::Cancelling;
+        }
+    }
+
+    // Updates the job list, returning the job once it is done
+    pub fn handle_event(&mut self, event: JobEvent) -> Option<FinishedJob> {
+        match event {
+            JobEvent::Started(id) => {
+                if let Some(job) = self.job_mut(id) {
+                    if job.info.state == JobState::Queued {
+                        job.info.state = JobState::Running;
+                    }
+                }
+                None
+            }
+            JobEvent::Progress(id, progress) => {
+                if let Some(job) = self.job_mut(id) {
+                    job.info.progress = progress;
+                }
+                None
+            }
+            JobEvent::Finished {
+                id,
+                operations,
+                skipped,
+                error,
+            } => {
+                let index = self.jobs.iter().position(|job| job.info.id == id)?;
+                let job = self.jobs.remove(index);
+                Some(FinishedJob {
+                    id,
+                    description: job.info.description,
+                    tasks: job.tasks,
+                    operations,
+                    skipped,
+                    error,
+                })
+            }
+        }
+    }
+}
+
+struct JobReporter<'a> {
+    id: usize,
+    control: &'a JobControl,
+    event_tx: &'a Sender<RstuifmEvent>,
+    progress: JobProgress,
+    last_sent: Instant,
+    skipped: Vec<PathBuf>,
+}
+
+impl JobReporter<'_> {
+    // Blocks while paused and turns a cancel request into an error that unwinds the operation
+    fn checkpoint(&mut self) -> OperationResult<()> {
+        loop {
+            if self.control.cancelled.load(Ordering::SeqCst) {
+                return Err(FileOperationError::Cancelled);
+            }
+            if !self.control.paused.load(Ordering::SeqCst) {
+                break;
+            }
+            thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+
+        if self.last_sent.elapsed() >= PROGRESS_INTERVAL {
+            self.send();
+        }
+        Ok(())
+    }
+
+    fn send(&mut self) {
+        self.last_sent = Instant::now();
+        let _ = self.event_tx.send(RstuifmEvent::Job(JobEvent::Progress(
+            self.id,
+            self.progress,
+        )));
+    }
+}
+
+impl Progress for JobReporter<'_> {
+    fn add_bytes(&mut self, bytes: u64) -> OperationResult<()> {
+        self.progress.bytes_done += bytes;
+        self.checkpoint()
+    }
+
+    fn add_item(&mut self) -> OperationResult<()> {
+        self.progress.items_done += 1;
+        self.checkpoint()
+    }
+
+    fn skip(&mut self, path: &Path) {
+        self.skipped.push(path.to_path_buf());
+    }
+}
+
+fn run_job(job: QueuedJob, event_tx: &Sender<RstuifmEvent>) {
+    let _ = event_tx.send(RstuifmEvent::Job(JobEvent::Started(job.id)));
+
+    let mut reporter = JobReporter {
+        id: job.id,
+        control: &job.control,
+        event_tx,
+        progress: JobProgress::default(),
+        last_sent: Instant::now(),
+        skipped: Vec::new(),
+    };
+
+    for task in &job.tasks {
+        let (bytes, items) = task.measure();
+        reporter.progress.bytes_total += bytes;
+        reporter.progress.items_total += items;
+    }
+    reporter.send();
+
+    let mut operations = Vec::new();
+    let mut first_error = None;
+    for task in &job.tasks {
+        match reporter.checkpoint().and_then(|_| task.run(&mut reporter)) {
+            Ok(operation) => operations.extend(operation),
+            Err(e) => {
+                let cancelled = matches!(e, FileOperationError::Cancelled);
+                first_error.get_or_insert(e);
+                if cancelled {
+                    break;
+                }
+            }
+        }
+    }
+
+    reporter.send();
+    let _ = event_tx.send(RstuifmEvent::Job(JobEvent::Finished {
+        id: job.id,
+        operations,
+        skipped: reporter.skipped,
+        error: first_error,
+    }));
+}
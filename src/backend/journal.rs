@@ -0,0 +1,485 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::file_operations::{self, FileOperationError, OperationResult, Progress};
+use super::trash;
+use super::vfs;
+use crate::helper_functions;
+
+const MAX_ENTRIES: usize = 100;
+
+// Identifies an item so we notice when it was replaced or modified behind our back
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Fingerprint {
+    device: u64,
+    inode: u64,
+    is_dir: bool,
+    size: u64,
+    modified: i64,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> Option<Fingerprint> {
+        let metadata = vfs::symlink_metadata(path).ok()?;
+        let (device, inode) = metadata.identity.unwrap_or_default();
+        let modified = metadata
+            .modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_nanos() as i64);
+        Some(Fingerprint {
+            device,
+            inode,
+            is_dir: metadata.is_dir(),
+            size: metadata.len,
+            modified,
+        })
+    }
+
+    // Directory sizes and times change with their contents, which undoing does not care about
+    fn matches(&self, other: &Fingerprint) -> bool {
+        let same_item =
+            self.device == other.device && self.inode == other.inode && self.is_dir == other.is_dir;
+        same_item && (self.is_dir || (self.size == other.size && self.modified == other.modified))
+    }
+}
+
+// A reversible operation. `fingerprint` describes the item where the operation
+// currently leaves it: the new location while done, the old one once undone
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Operation {
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        fingerprint: Option<Fingerprint>,
+    },
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+        fingerprint: Option<Fingerprint>,
+    },
+    Copy {
+        source: PathBuf,
+        target: PathBuf,
+        fingerprint: Option<Fingerprint>,
+    },
+    MakeDirectory {
+        path: PathBuf,
+        fingerprint: Option<Fingerprint>,
+    },
+    MakeFile {
+        path: PathBuf,
+        fingerprint: Option<Fingerprint>,
+    },
+    Trash {
+        original_path: PathBuf,
+        trash_name: String,
+        fingerprint: Option<Fingerprint>,
+    },
+}
+
+impl Operation {
+    pub fn rename(from: PathBuf, to: PathBuf) -> Operation {
+        let fingerprint = Fingerprint::of(&to);
+        Operation::Rename {
+            from,
+            to,
+            fingerprint,
+        }
+    }
+
+    pub fn move_item(from: PathBuf, to: PathBuf) -> Operation {
+        let fingerprint = Fingerprint::of(&to);
+        Operation::Move {
+            from,
+            to,
+            fingerprint,
+        }
+    }
+
+    pub fn copy(source: PathBuf, target: PathBuf) -> Operation {
+        let fingerprint = Fingerprint::of(&target);
+        Operation::Copy {
+            source,
+            target,
+            fingerprint,
+        }
+    }
+
+    pub fn make_directory(path: PathBuf) -> Operation {
+        let fingerprint = Fingerprint::of(&path);
+        Operation::MakeDirectory { path, fingerprint }
+    }
+
+    pub fn make_file(path: PathBuf) -> Operation {
+        let fingerprint = Fingerprint::of(&path);
+        Operation::MakeFile { path, fingerprint }
+    }
+
+    pub fn trash(original_path: PathBuf, trash_name: String) -> Operation {
+        let fingerprint = Fingerprint::of(&trash::files_directory().join(&trash_name));
+        Operation::Trash {
+            original_path,
+            trash_name,
+            fingerprint,
+        }
+    }
+
+    fn fingerprint(&self) -> Option<&Fingerprint> {
+        match self {
+            Operation::Rename { fingerprint, .. }
+            | Operation::Move { fingerprint, .. }
+            | Operation::Copy { fingerprint, .. }
+            | Operation::MakeDirectory { fingerprint, .. }
+            | Operation::MakeFile { fingerprint, .. }
+            | Operation::Trash { fingerprint, .. } => fingerprint.as_ref(),
+        }
+    }
+
+    // Where the item is while the operation is done, if it still has to be there
+    fn done_location(&self) -> PathBuf {
+        match self {
+            Operation::Rename { to, .. } | Operation::Move { to, .. } => to.clone(),
+            Operation::Copy { target, .. } => target.clone(),
+            Operation::MakeDirectory { path, .. } | Operation::MakeFile { path, .. } => {
+                path.clone()
+            }
+            Operation::Trash { trash_name, .. } => trash::files_directory().join(trash_name),
+        }
+    }
+
+    // Where the item is once undone, created and copied items are gone by then
+    fn undone_location(&self) -> Option<PathBuf> {
+        match self {
+            Operation::Rename { from, .. } | Operation::Move { from, .. } => Some(from.clone()),
+            Operation::Trash { original_path, .. } => Some(original_path.clone()),
+            _ => None,
+        }
+    }
+
+    fn ensure_unchanged(&self, path: &Path) -> OperationResult<()> {
+        let current = Fingerprint::of(path)
+            .ok_or_else(|| FileOperationError::NotFound(path.to_path_buf()))?;
+        match self.fingerprint() {
+            Some(recorded) if recorded.matches(&current) => Ok(()),
+            _ => Err(FileOperationError::Changed(path.to_path_buf())),
+        }
+    }
+
+    // Reverses the operation, returning it with a fingerprint for redoing it
+    pub fn undo(&self, progress: &mut dyn Progress) -> OperationResult<Operation> {
+        let location = self.done_location();
+        self.ensure_unchanged(&location)?;
+
+        let mut undone = self.clone();
+        match &mut undone {
+            Operation::Rename { from, to, .. } | Operation::Move { from, to, .. } => {
+                file_operations::move_to(to, from, progress)?;
+            }
+            Operation::Copy { target, .. } => {
+                // The copy may hold the only version of later edits, so keep it in the trash
+                trash::trash(target, progress)?;
+            }
+            Operation::MakeDirectory { path, .. } => {
+                file_operations::provider(path)?
+                    .remove_dir(path)
+                    .map_err(|e| FileOperationError::from_io(path, e))?;
+            }
+            Operation::MakeFile { path, .. } => {
+                file_operations::provider(path)?
+                    .remove_file(path)
+                    .map_err(|e| FileOperationError::from_io(path, e))?;
+            }
+            Operation::Trash { trash_name, .. } => {
+                trash::restore(trash_name, progress)?;
+            }
+        }
+
+        undone.set_fingerprint(
+            self.undone_location()
+                .and_then(|path| Fingerprint::of(&path)),
+        );
+        Ok(undone)
+    }
+
+    // Applies the operation again, returning it with a fingerprint for undoing it
+    pub fn redo(&self, progress: &mut dyn Progress) -> OperationResult<Operation> {
+        if let Some(location) = self.undone_location() {
+            self.ensure_unchanged(&location)?;
+        }
+
+        let mut redone = self.clone();
+        match &mut redone {
+            Operation::Rename { from, to, .. } | Operation::Move { from, to, .. } => {
+                file_operations::move_to(from, to, progress)?;
+            }
+            Operation::Copy { source, target, .. } => {
+                file_operations::copy_to(source, target, progress)?;
+            }
+            Operation::MakeDirectory { path, .. } => {
+                file_operations::provider(path)?
+                    .create_dir(path)
+                    .map_err(|e| FileOperationError::from_io(path, e))?;
+            }
+            Operation::MakeFile { path, .. } => {
+                file_operations::provider(path)?
+                    .create(path)
+                    .map_err(|e| FileOperationError::from_io(path, e))?;
+            }
+            Operation::Trash {
+                original_path,
+                trash_name,
+                ..
+            } => {
+                *trash_name = trash::trash(original_path, progress)?;
+            }
+        }
+
+        redone.set_fingerprint(Fingerprint::of(&redone.done_location()));
+        Ok(redone)
+    }
+
+    fn set_fingerprint(&mut self, new_fingerprint: Option<Fingerprint>) {
+        match self {
+            Operation::Rename { fingerprint, .. }
+            | Operation::Move { fingerprint, .. }
+            | Operation::Copy { fingerprint, .. }
+            | Operation::MakeDirectory { fingerprint, .. }
+            | Operation::MakeFile { fingerprint, .. }
+            | Operation::Trash { fingerprint, .. } => *fingerprint = new_fingerprint,
+        }
+    }
+
+    // Directories whose listing changes when the operation is undone or redone
+    pub fn affected_directories(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.done_location()];
+        paths.extend(self.undone_location());
+        paths
+            .iter()
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect()
+    }
+
+    // Paths whose cached listing is stale after undoing or redoing
+    pub fn invalidated_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.done_location()];
+        paths.extend(self.undone_location());
+        paths
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub description: String,
+    pub operations: Vec<Operation>,
+}
+
+// Undo and redo run as jobs, this remembers which entry a job belongs to
+pub enum JournalJob {
+    Undo(String),
+    Redo(String),
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Journal {
+    undo_stack: Vec<JournalEntry>,
+    redo_stack: Vec<JournalEntry>,
+}
+
+impl Journal {
+    // Starts empty when there is no journal yet or it cannot be read
+    pub fn load() -> Journal {
+        Journal::load_from(&journal_path())
+    }
+
+    fn load_from(path: &Path) -> Journal {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&journal_path())
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("[Journal.save()] Failed to create state directory")?;
+        }
+        let contents =
+            serde_json::to_string(self).context("[Journal.save()] Failed to serialize")?;
+        fs::write(path, contents).context("[Journal.save()] Failed to write journal")
+    }
+
+    // A new operation makes the redo history meaningless
+    pub fn record(&mut self, entry: JournalEntry) {
+        self.redo_stack.clear();
+        self.push_undo(entry);
+    }
+
+    pub fn push_undo(&mut self, entry: JournalEntry) {
+        self.undo_stack.push(entry);
+        if self.undo_stack.len() > MAX_ENTRIES {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    pub fn push_redo(&mut self, entry: JournalEntry) {
+        self.redo_stack.push(entry);
+    }
+
+    pub fn pop_undo(&mut self) -> Option<JournalEntry> {
+        self.undo_stack.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<JournalEntry> {
+        self.redo_stack.pop()
+    }
+}
+
+fn journal_path() -> PathBuf {
+    helper_functions::xdg_directory("XDG_STATE_HOME", ".local/state")
+        .join("rstuifm")
+        .join("journal.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_operations::NoProgress;
+
+    fn renamed(directory: &Path, contents: &str) -> (PathBuf, PathBuf, Operation) {
+        let from = directory.join("from");
+        let to = directory.join("to");
+        fs::write(&from, contents).unwrap();
+        fs::rename(&from, &to).unwrap();
+        let operation = Operation::rename(from.clone(), to.clone());
+        (from, to, operation)
+    }
+
+    fn entry(description: &str, operation: Operation) -> JournalEntry {
+        JournalEntry {
+            description: description.to_string(),
+            operations: vec![operation],
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_follow_the_item() {
+        let directory = tempfile::tempdir().unwrap();
+        let (from, to, operation) = renamed(directory.path(), "data");
+
+        let undone = operation.undo(&mut NoProgress).unwrap();
+        assert!(from.exists() && !to.exists());
+        let redone = undone.redo(&mut NoProgress).unwrap();
+        assert!(!from.exists() && to.exists());
+        redone.undo(&mut NoProgress).unwrap();
+        assert_eq!(fs::read_to_string(&from).unwrap(), "data");
+    }
+
+    #[test]
+    fn modified_items_are_not_undone() {
+        let directory = tempfile::tempdir().unwrap();
+        let (from, to, operation) = renamed(directory.path(), "data");
+        fs::write(&to, "edited since").unwrap();
+
+        let result = operation.undo(&mut NoProgress);
+        assert!(matches!(result, Err(FileOperationError::Changed(path)) if path == to));
+        assert!(!from.exists());
+    }
+
+    #[test]
+    fn replaced_items_are_not_undone() {
+        let directory = tempfile::tempdir().unwrap();
+        let (_, to, operation) = renamed(directory.path(), "data");
+        // Same contents and size, but another file
+        let other = directory.path().join("other");
+        fs::write(&other, "data").unwrap();
+        fs::rename(&other, &to).unwrap();
+
+        let result = operation.undo(&mut NoProgress);
+        assert!(matches!(result, Err(FileOperationError::Changed(_))));
+    }
+
+    #[test]
+    fn missing_items_are_not_undone() {
+        let directory = tempfile::tempdir().unwrap();
+        let (_, to, operation) = renamed(directory.path(), "data");
+        fs::remove_file(&to).unwrap();
+
+        let result = operation.undo(&mut NoProgress);
+        assert!(matches!(result, Err(FileOperationError::NotFound(path)) if path == to));
+    }
+
+    #[test]
+    fn directories_may_change_inside() {
+        let directory = tempfile::tempdir().unwrap();
+        let from = directory.path().join("from");
+        let to = directory.path().join("to");
+        fs::create_dir(&from).unwrap();
+        fs::rename(&from, &to).unwrap();
+        let operation = Operation::rename(from.clone(), to.clone());
+        fs::write(to.join("new"), "").unwrap();
+
+        operation.undo(&mut NoProgress).unwrap();
+        assert!(from.join("new").exists());
+    }
+
+    #[test]
+    fn journal_round_trips_through_its_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let (_, to, operation) = renamed(directory.path(), "data");
+        let path = directory.path().join("state").join("journal.json");
+
+        let mut journal = Journal::default();
+        journal.record(entry("Rename 'from'", operation));
+        journal.save_to(&path).unwrap();
+
+        let mut loaded = Journal::load_from(&path);
+        let entry = loaded.pop_undo().unwrap();
+        assert_eq!(entry.description, "Rename 'from'");
+        assert!(loaded.pop_undo().is_none());
+        // The fingerprint came along, so a change made meanwhile is still noticed
+        fs::write(&to, "edited since").unwrap();
+        let result = entry.operations[0].undo(&mut NoProgress);
+        assert!(matches!(result, Err(FileOperationError::Changed(_))));
+    }
+
+    #[test]
+    fn unreadable_journals_start_empty() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("journal.json");
+        assert!(Journal::load_from(&path).pop_undo().is_none());
+
+        fs::write(&path, "not json").unwrap();
+        assert!(Journal::load_from(&path).pop_undo().is_none());
+    }
+
+    #[test]
+    fn recording_clears_redo_and_keeps_the_newest_entries() {
+        let directory = tempfile::tempdir().unwrap();
+        let made = |name: &str| {
+            let path = directory.path().join(name);
+            fs::write(&path, "").unwrap();
+            Operation::make_file(path)
+        };
+
+        let mut journal = Journal::default();
+        journal.push_redo(entry("redo", made("redo")));
+        for index in 0..=MAX_ENTRIES {
+            journal.record(entry(&index.to_string(), made("made")));
+        }
+        assert!(journal.pop_redo().is_none());
+        assert_eq!(journal.undo_stack.len(), MAX_ENTRIES);
+        assert_eq!(journal.undo_stack[0].description, "1");
+        assert_eq!(
+            journal.pop_undo().unwrap().description,
+            MAX_ENTRIES.to_string()
+        );
+    }
+}
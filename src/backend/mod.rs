@@ -1,15 +1,39 @@
 pub mod app_backend;
+pub mod archive;
+pub mod bulk_rename;
+pub mod clipboard;
+pub mod columns;
 pub mod dir_list;
+pub mod dir_loader;
 pub mod file;
+pub mod file_info;
+pub mod file_operations;
+pub mod filter;
+pub mod finder;
 pub mod folder;
+pub mod grep;
+pub mod highlighter;
+pub mod jobs;
+pub mod journal;
+pub mod mode;
+pub mod opener;
+pub mod preferences;
+pub mod preview;
+pub mod prompt;
+pub mod sftp;
+pub mod sort;
 pub mod symlink;
 pub mod tab;
+pub mod trash;
+pub mod vfs;
+pub mod watcher;
 pub mod events;
 
 pub use self::app_backend::AppBackend;
 pub use self::dir_list::DirList;
 pub use self::file::File;
 pub use self::folder::Folder;
+pub use self::mode::Mode;
 pub use self::symlink::Symlink;
 pub use self::tab::Tab;
 pub use self::tab::Tabs;
@@ -0,0 +1,16 @@
+use super::opener::OpenWithMenu;
+use super::prompt::Prompt;
+
+// Decides how key events are interpreted
+pub enum Mode {
+    Normal,
+    Prompt(Prompt),
+    // Index of the selected job in the progress panel
+    JobPanel(usize),
+    // Browsing the trash, the view itself lives on AppBackend so prompts can return to it
+    Trash,
+    // Going through the hits of a content search, kept on AppBackend like the trash view
+    Grep,
+    // Choosing between the programs that can open a file
+    OpenWith(OpenWithMenu),
+}
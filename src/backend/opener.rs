@@ -0,0 +1,178 @@
+// Picks programs for files from the rules in `$XDG_CONFIG_HOME/rstuifm/openers.toml`:
+//
+// [[rule]]
+// name = "Neovim"
+// command = "nvim"
+// extensions = ["rs", "toml"]
+// mime = ["text/*"]
+// globs = ["Makefile"]
+// terminal = true
+use std::fs;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::vfs;
+use crate::helper_functions;
+
+const SNIFF_LENGTH: u64 = 8192;
+
+#[derive(Clone, Deserialize)]
+pub struct OpenerRule {
+    pub name: Option<String>,
+    // Run through `sh -c`, the file is passed as "$@"
+    pub command: String,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub mime: Vec<String>,
+    #[serde(default)]
+    pub globs: Vec<String>,
+    // Terminal programs take over the screen, others are detached
+    #[serde(default)]
+    pub terminal: bool,
+}
+
+impl OpenerRule {
+    // Used when no rule matches
+    pub fn fallback() -> OpenerRule {
+        OpenerRule {
+            name: None,
+            command: String::from("xdg-open"),
+            extensions: Vec::new(),
+            mime: Vec::new(),
+            globs: Vec::new(),
+            terminal: false,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.command)
+    }
+
+    fn matches(&self, name: &str, extension: Option<&str>, mime_type: &str) -> bool {
+        let extension_matches = extension.is_some_and(|extension| {
+            self.extensions
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(extension))
+        });
+        let pattern_matches = |patterns: &[String], value: &str| {
+            patterns.iter().any(|pattern| {
+                glob::Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(value))
+            })
+        };
+
+        extension_matches
+            || pattern_matches(&self.mime, mime_type)
+            || pattern_matches(&self.globs, name)
+    }
+
+    pub fn command(&self, path: &Path) -> Command {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("{} \"$@\"", self.command))
+            .arg("sh")
+            .arg(path);
+        command
+    }
+
+    // Starts a GUI program in its own process group so it outlives us and ignores our terminal
+    pub fn spawn_detached(&self, path: &Path) -> Result<()> {
+        let mut child = self
+            .command(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()
+            .with_context(|| format!("Failed to run '{}'", self.command))?;
+
+        // Reap the child once it exits instead of leaving a zombie around
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+#[derive(Default, Deserialize)]
+pub struct Openers {
+    #[serde(default, rename = "rule")]
+    rules: Vec<OpenerRule>,
+}
+
+impl Openers {
+    // A missing config means no rules, a broken one is reported
+    pub fn load() -> Result<Openers> {
+        let path = config_path();
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .with_context(|| format!("Invalid opener config '{}'", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Openers::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read '{}'", path.display())),
+        }
+    }
+
+    // Matching rules in config order
+    pub fn matching(&self, path: &Path) -> Vec<OpenerRule> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy());
+        let mime_type = mime_type(path);
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(&name, extension.as_deref(), &mime_type))
+            .cloned()
+            .collect()
+    }
+}
+
+fn config_path() -> PathBuf {
+    helper_functions::xdg_directory("XDG_CONFIG_HOME", ".config")
+        .join("rstuifm")
+        .join("openers.toml")
+}
+
+// Guesses from the extension first, then falls back to sniffing the first bytes
+pub fn mime_type(path: &Path) -> String {
+    if let Some(mime) = mime_guess::from_path(path).first() {
+        return mime.essence_str().to_string();
+    }
+
+    let mut buffer = Vec::new();
+    let read = vfs::resolve(path)
+        .and_then(|provider| provider.open(path))
+        .and_then(|file| file.take(SNIFF_LENGTH).read_to_end(&mut buffer));
+    match read {
+        Ok(_) if buffer.is_empty() => String::from("application/x-empty"),
+        Ok(_) if helper_functions::looks_like_text(&buffer) => String::from("text/plain"),
+        _ => String::from("application/octet-stream"),
+    }
+}
+
+// Shown when more than one program could open the file
+#[derive(Clone)]
+pub struct OpenWithMenu {
+    pub path: PathBuf,
+    pub rules: Vec<OpenerRule>,
+    pub selected: usize,
+}
+
+impl OpenWithMenu {
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.rules.len().saturating_sub(1));
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
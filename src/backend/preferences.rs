@@ -0,0 +1,43 @@
+// Settings changed from within the app, kept across sessions in
+// `$XDG_STATE_HOME/rstuifm/preferences.json`
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::helper_functions;
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    // Dotfiles are listed like other items
+    pub show_hidden: bool,
+}
+
+impl Preferences {
+    // Missing or unreadable preferences mean the defaults
+    pub fn load() -> Preferences {
+        fs::read_to_string(preferences_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = preferences_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("[Preferences.save()] Failed to create state directory")?;
+        }
+        let contents =
+            serde_json::to_string(self).context("[Preferences.save()] Failed to serialize")?;
+        fs::write(&path, contents).context("[Preferences.save()] Failed to write preferences")
+    }
+}
+
+fn preferences_path() -> PathBuf {
+    helper_functions::xdg_directory("XDG_STATE_HOME", ".local/state")
+        .join("rstuifm")
+        .join("preferences.json")
+}
@@ -0,0 +1,439 @@
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::SystemTime;
+
+use crossbeam::channel::{self, Sender};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget},
+};
+use unicode_width::UnicodeWidthChar;
+
+use super::events::RstuifmEvent;
+use super::file_info::FileInfo;
+use super::highlighter::{HighlightKey, HighlightedLines};
+use super::opener;
+use super::vfs::{self, Metadata};
+use crate::helper_functions::{self, format_bytes};
+
+// Previews read at most this much, so huge files cost the same as small ones
+const PREVIEW_BYTES: u64 = 64 * 1024;
+const PREVIEW_LINES: usize = 256;
+// Lines wider than this many columns are cut, the pane could never show the rest anyway
+const MAX_LINE_WIDTH: usize = 1024;
+const TAB_WIDTH: usize = 4;
+// Widest first, the hex dump uses the widest row that fits the pane
+const HEX_ROW_SIZES: [usize; 3] = [16, 8, 4];
+const HEX_GROUP_SIZE: usize = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PreviewMode {
+    Text,
+    Hex,
+    // Details of any item instead of its contents, folders included
+    Info,
+}
+
+impl PreviewMode {
+    pub fn next(self) -> PreviewMode {
+        match self {
+            PreviewMode::Text => PreviewMode::Hex,
+            PreviewMode::Hex => PreviewMode::Info,
+            PreviewMode::Info => PreviewMode::Text,
+        }
+    }
+}
+
+// One way of showing one item, a loaded preview is reused until its key changes or the item's
+// directory reports a change
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum PreviewKey {
+    Text(PathBuf),
+    Hex {
+        path: PathBuf,
+        offset: u64,
+        area: Rect,
+    },
+    Info(PathBuf),
+}
+
+impl PreviewKey {
+    pub fn path(&self) -> &Path {
+        match self {
+            PreviewKey::Text(path) | PreviewKey::Info(path) => path,
+            PreviewKey::Hex { path, .. } => path,
+        }
+    }
+
+    pub fn load(&self) -> LoadedPreview {
+        match self {
+            PreviewKey::Text(path) => LoadedPreview::File(FilePreview::load(path)),
+            PreviewKey::Hex { path, offset, area } => {
+                LoadedPreview::File(FilePreview::hex_dump(path, *offset, *area))
+            }
+            PreviewKey::Info(path) => LoadedPreview::Info(FileInfo::load(path)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum LoadedPreview {
+    File(FilePreview),
+    Info(FileInfo),
+}
+
+pub struct PreviewResult {
+    pub key: PreviewKey,
+    pub preview: LoadedPreview,
+}
+
+// Loads previews of remote and archived items on its own thread, so a slow connection or a long
+// decompression does not hold up drawing. Results come back as app events
+pub struct Previewer {
+    request_tx: Sender<PreviewKey>,
+}
+
+impl Previewer {
+    pub fn new(event_tx: Sender<RstuifmEvent>) -> Previewer {
+        let (request_tx, request_rx) = channel::unbounded::<PreviewKey>();
+
+        thread::spawn(move || {
+            while let Ok(mut key) = request_rx.recv() {
+                // Items scrolled past in the meantime are not loaded anymore
+                while let Ok(newer) = request_rx.try_recv() {
+                    key = newer;
+                }
+                let preview = key.load();
+                if event_tx
+                    .send(RstuifmEvent::Preview(PreviewResult { key, preview }))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Previewer { request_tx }
+    }
+
+    pub fn request(&self, key: PreviewKey) {
+        let _ = self.request_tx.send(key);
+    }
+}
+
+#[derive(Clone)]
+pub enum PreviewContent {
+    Text(Vec<String>),
+    Binary { mime_type: String },
+    Hex(HexDump),
+    Error(String),
+}
+
+// One screenful of bytes starting at `offset`
+#[derive(Clone)]
+pub struct HexDump {
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+    pub bytes_per_row: usize,
+}
+
+impl HexDump {
+    // Offset column, hex columns with a gap between groups, then the ASCII column between bars
+    fn row_width(bytes_per_row: usize, offset_width: usize) -> usize {
+        let groups = bytes_per_row.div_ceil(HEX_GROUP_SIZE);
+        offset_width + 2 + bytes_per_row * 3 + groups + bytes_per_row + 2
+    }
+
+    pub fn bytes_per_row(width: u16, size: u64) -> usize {
+        let offset_width = offset_width(size);
+        HEX_ROW_SIZES
+            .into_iter()
+            .find(|&bytes_per_row| {
+                HexDump::row_width(bytes_per_row, offset_width) <= width as usize
+            })
+            .unwrap_or(HEX_ROW_SIZES[HEX_ROW_SIZES.len() - 1])
+    }
+
+    // Bytes shown at once in `area`
+    pub fn page_size(area: Rect, size: u64) -> u64 {
+        (HexDump::bytes_per_row(area.width, size) * area.height.max(1) as usize) as u64
+    }
+
+    // Rounds down to a row start and keeps at least the last row on screen
+    pub fn clamp_offset(offset: u64, size: u64, bytes_per_row: usize) -> u64 {
+        let bytes_per_row = bytes_per_row as u64;
+        let last_row = size.saturating_sub(1) / bytes_per_row * bytes_per_row;
+        (offset / bytes_per_row * bytes_per_row).min(last_row)
+    }
+}
+
+fn offset_width(size: u64) -> usize {
+    format!("{:x}", size).len().max(8)
+}
+
+#[derive(Clone)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub content: PreviewContent,
+    // Filled in once the highlighter thread is done with the text
+    pub highlighted: Option<HighlightedLines>,
+}
+
+impl FilePreview {
+    fn new(path: &Path, metadata: Option<&Metadata>) -> FilePreview {
+        FilePreview {
+            path: path.to_path_buf(),
+            size: metadata.map_or(0, |metadata| metadata.len),
+            modified: metadata.and_then(|metadata| metadata.modified),
+            content: PreviewContent::Text(Vec::new()),
+            highlighted: None,
+        }
+    }
+
+    pub fn load(path: &Path) -> FilePreview {
+        let mut preview = FilePreview::new(path, vfs::metadata(path).ok().as_ref());
+
+        let read =
+            vfs::resolve(path).and_then(|provider| provider.read_range(path, 0, PREVIEW_BYTES));
+        preview.content = match read {
+            Err(e) => PreviewContent::Error(e.to_string()),
+            Ok(buffer) if helper_functions::looks_like_text(&buffer) => {
+                PreviewContent::Text(text_lines(&buffer))
+            }
+            Ok(_) => PreviewContent::Binary {
+                mime_type: opener::mime_type(path),
+            },
+        };
+        preview
+    }
+
+    // Reads only the bytes that fit in `area`, starting at `offset`
+    pub fn hex_dump(path: &Path, offset: u64, area: Rect) -> FilePreview {
+        let mut preview = FilePreview::new(path, vfs::metadata(path).ok().as_ref());
+        let bytes_per_row = HexDump::bytes_per_row(area.width, preview.size);
+        let offset = HexDump::clamp_offset(offset, preview.size, bytes_per_row);
+
+        let read = vfs::resolve(path).and_then(|provider| {
+            provider.read_range(path, offset, HexDump::page_size(area, preview.size))
+        });
+        preview.content = match read {
+            Ok(bytes) => PreviewContent::Hex(HexDump {
+                offset,
+                bytes,
+                bytes_per_row,
+            }),
+            Err(e) => PreviewContent::Error(e.to_string()),
+        };
+        preview
+    }
+
+    pub fn highlight_key(&self) -> HighlightKey {
+        (self.path.clone(), self.modified)
+    }
+}
+
+fn text_lines(buffer: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(buffer)
+        .lines()
+        .take(PREVIEW_LINES)
+        .map(expand_line)
+        .collect()
+}
+
+// Expands tabs and replaces control characters, which would otherwise move the terminal cursor.
+// Columns are counted in display width, so tab stops line up after wide characters
+fn expand_line(line: &str) -> String {
+    let mut expanded = String::new();
+    let mut column = 0;
+    for c in line.chars() {
+        let width = match c {
+            '\t' => TAB_WIDTH - column % TAB_WIDTH,
+            c if c.is_control() => 1,
+            c => c.width().unwrap_or(0),
+        };
+        if column + width > MAX_LINE_WIDTH {
+            break;
+        }
+        match c {
+            '\t' => expanded.extend(std::iter::repeat_n(' ', width)),
+            c if c.is_control() => expanded.push(char::REPLACEMENT_CHARACTER),
+            c => expanded.push(c),
+        }
+        column += width;
+    }
+    expanded
+}
+
+fn byte_style(byte: u8) -> Style {
+    match byte {
+        0 => Style::default().fg(Color::DarkGray),
+        b' '..=b'~' => Style::default(),
+        b'\t' | b'\n' | b'\r' => Style::default().fg(Color::Green),
+        _ => Style::default().fg(Color::Yellow),
+    }
+}
+
+fn hex_lines(hex_dump: &HexDump, size: u64) -> Vec<Line<'static>> {
+    let dim = Style::default().fg(Color::DarkGray);
+    let offset_width = offset_width(size);
+
+    hex_dump
+        .bytes
+        .chunks(hex_dump.bytes_per_row)
+        .enumerate()
+        .map(|(row, bytes)| {
+            let offset = hex_dump.offset + (row * hex_dump.bytes_per_row) as u64;
+            let mut spans = vec![Span::styled(format!("{:0offset_width$x}  ", offset), dim)];
+
+            for index in 0..hex_dump.bytes_per_row {
+                if index > 0 && index % HEX_GROUP_SIZE == 0 {
+                    spans.push(Span::raw(" "));
+                }
+                match bytes.get(index) {
+                    Some(&byte) => {
+                        spans.push(Span::styled(format!("{:02x} ", byte), byte_style(byte)))
+                    }
+                    None => spans.push(Span::raw("   ")),
+                }
+            }
+
+            spans.push(Span::styled(" |", dim));
+            for &byte in bytes {
+                let c = if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                };
+                spans.push(Span::styled(c.to_string(), byte_style(byte)));
+            }
+            spans.push(Span::styled("|", dim));
+            Line::from(spans)
+        })
+        .collect()
+}
+
+impl Widget for FilePreview {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let dim = Style::default().fg(Color::DarkGray);
+        let block = match &self.content {
+            PreviewContent::Hex(hex_dump) => Block::default().borders(Borders::ALL).title(
+                Span::styled(format!(" {:x} / {:x} ", hex_dump.offset, self.size), dim),
+            ),
+            _ => Block::default().borders(Borders::ALL),
+        };
+        let visible_lines = block.inner(area).height as usize;
+
+        let lines: Vec<Line> = match self.content {
+            PreviewContent::Text(lines) => {
+                let gutter_width = lines.len().min(visible_lines).max(1).to_string().len();
+                let highlighted = self.highlighted.as_deref();
+                lines
+                    .into_iter()
+                    .take(visible_lines)
+                    .enumerate()
+                    .map(|(index, line)| {
+                        let gutter = Span::styled(format!("{:>gutter_width$} ", index + 1), dim);
+                        let mut spans = vec![gutter];
+                        match highlighted.and_then(|highlighted| highlighted.get(index)) {
+                            Some(highlighted_line) => {
+                                spans.extend(highlighted_line.spans.iter().cloned())
+                            }
+                            None => spans.push(Span::raw(line)),
+                        }
+                        Line::from(spans)
+                    })
+                    .collect()
+            }
+            PreviewContent::Binary { mime_type } => vec![
+                Line::from("Binary file"),
+                Line::from(Span::styled(mime_type, dim)),
+                Line::from(Span::styled(format_bytes(self.size), dim)),
+                Line::from(""),
+                Line::from(Span::styled("P: hex dump", dim)),
+            ],
+            PreviewContent::Hex(hex_dump) => hex_lines(&hex_dump, self.size),
+            PreviewContent::Error(error) => {
+                vec![Line::from(Span::styled(
+                    error,
+                    Style::default().fg(Color::Red),
+                ))]
+            }
+        };
+
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unicode_width::UnicodeWidthStr;
+
+    #[test]
+    fn tabs_stop_at_display_columns() {
+        assert_eq!(expand_line("a\tb"), "a   b");
+        assert_eq!(expand_line("abcd\tb"), "abcd    b");
+        // Wide characters take two columns before the tab stop
+        assert_eq!(expand_line("漢\tb"), "漢  b");
+        // Combining marks take none
+        assert_eq!(expand_line("e\u{301}\tb"), "e\u{301}   b");
+    }
+
+    #[test]
+    fn control_characters_are_replaced() {
+        assert_eq!(expand_line("a\u{1b}[2Jb"), "a\u{fffd}[2Jb");
+    }
+
+    #[test]
+    fn long_lines_are_cut_at_the_display_width() {
+        let ascii = expand_line(&"a".repeat(MAX_LINE_WIDTH + 10));
+        assert_eq!(ascii.width(), MAX_LINE_WIDTH);
+
+        let wide = expand_line(&"漢".repeat(MAX_LINE_WIDTH));
+        assert_eq!(wide.width(), MAX_LINE_WIDTH);
+        assert_eq!(wide.chars().count(), MAX_LINE_WIDTH / 2);
+
+        // A wide character that would stick out is left off
+        let odd = expand_line(&format!("{}漢", "a".repeat(MAX_LINE_WIDTH - 1)));
+        assert_eq!(odd.width(), MAX_LINE_WIDTH - 1);
+    }
+
+    #[test]
+    fn hex_offsets_round_down_to_a_row() {
+        assert_eq!(HexDump::clamp_offset(0, 1000, 16), 0);
+        assert_eq!(HexDump::clamp_offset(37, 1000, 16), 32);
+        assert_eq!(HexDump::clamp_offset(37, 1000, 8), 32);
+        assert_eq!(HexDump::clamp_offset(48, 1000, 16), 48);
+    }
+
+    #[test]
+    fn hex_offsets_keep_the_last_row_on_screen() {
+        // 1000 bytes end in the row starting at 992
+        assert_eq!(HexDump::clamp_offset(5000, 1000, 16), 992);
+        assert_eq!(HexDump::clamp_offset(u64::MAX, 1000, 16), 992);
+        // A size that fills its last row exactly
+        assert_eq!(HexDump::clamp_offset(5000, 1024, 16), 1008);
+        assert_eq!(HexDump::clamp_offset(5000, 1, 16), 0);
+        assert_eq!(HexDump::clamp_offset(5000, 0, 16), 0);
+    }
+
+    #[test]
+    fn hex_rows_get_as_wide_as_the_pane_allows() {
+        assert_eq!(HexDump::bytes_per_row(78, 1000), 16);
+        assert_eq!(HexDump::bytes_per_row(77, 1000), 8);
+        assert_eq!(HexDump::bytes_per_row(45, 1000), 8);
+        assert_eq!(HexDump::bytes_per_row(44, 1000), 4);
+        // Too narrow for any row still shows the narrowest
+        assert_eq!(HexDump::bytes_per_row(10, 1000), 4);
+        // Offsets of huge files need more than 8 digits
+        assert_eq!(HexDump::bytes_per_row(78, 1 << 40), 8);
+
+        assert_eq!(HexDump::page_size(Rect::new(0, 0, 78, 10), 1000), 160);
+        assert_eq!(HexDump::page_size(Rect::new(0, 0, 78, 0), 1000), 16);
+    }
+}
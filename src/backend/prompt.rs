@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+use super::bulk_rename::BulkRename;
+use super::clipboard::PastePlan;
+use super::filter::FilterKind;
+
+#[derive(Clone)]
+pub enum PromptKind {
+    Rename(PathBuf),
+    MakeDirectory,
+    MakeFile,
+    CopyTo(Vec<PathBuf>),
+    MoveTo(Vec<PathBuf>),
+    CompressTo(Vec<PathBuf>),
+    ExtractTo(PathBuf),
+    ConfirmDelete(Vec<PathBuf>),
+    ConfirmPurge(Vec<String>),
+    MarkMatching,
+    // Filters the listing while typing
+    Filter(FilterKind),
+    // Query of the fuzzy finder, its results are shown over the panes
+    Find,
+    // Regex to search file contents for
+    Grep,
+    OpenTab,
+    PasteConflict(PastePlan),
+    ConfirmBulkRename(BulkRename),
+}
+
+impl PromptKind {
+    pub fn label(&self) -> String {
+        match self {
+            PromptKind::Rename(_) => String::from("Rename: "),
+            PromptKind::MakeDirectory => String::from("New directory: "),
+            PromptKind::MakeFile => String::from("New file: "),
+            PromptKind::CopyTo(paths) => format!("Copy {} to: ", describe_paths(paths)),
+            PromptKind::MoveTo(paths) => format!("Move {} to: ", describe_paths(paths)),
+            PromptKind::CompressTo(paths) => format!("Compress {} to: ", describe_paths(paths)),
+            PromptKind::ExtractTo(archive) => {
+                format!(
+                    "Extract {} to: ",
+                    describe_paths(std::slice::from_ref(archive))
+                )
+            }
+            PromptKind::ConfirmDelete(paths) => {
+                format!("Permanently delete {}? (y/n)", describe_paths(paths))
+            }
+            PromptKind::ConfirmPurge(names) => {
+                format!("Permanently delete {} items from trash? (y/n)", names.len())
+            }
+            PromptKind::MarkMatching => String::from("Mark matching: "),
+            PromptKind::Filter(kind) => format!("Filter ({}, Tab to switch): ", kind.label()),
+            PromptKind::Find => String::from("Find: "),
+            PromptKind::Grep => String::from("Search contents: "),
+            PromptKind::OpenTab => String::from("Open in new tab: "),
+            PromptKind::PasteConflict(plan) => {
+                let target = plan
+                    .next_conflict()
+                    .map(|entry| vec![entry.target.clone()])
+                    .unwrap_or_default();
+                format!(
+                    "{} exists: (o)verwrite (s)kip (r)ename, O/S/R for all, Esc to abort",
+                    describe_paths(&target)
+                )
+            }
+            PromptKind::ConfirmBulkRename(bulk_rename) => {
+                format!("Rename {} items? (y/n)", bulk_rename.len())
+            }
+        }
+    }
+
+    // Confirmations are answered with a single key instead of typed input
+    pub fn is_confirmation(&self) -> bool {
+        matches!(
+            self,
+            PromptKind::ConfirmDelete(_)
+                | PromptKind::ConfirmPurge(_)
+                | PromptKind::ConfirmBulkRename(_)
+        )
+    }
+}
+
+pub fn describe_paths(paths: &[PathBuf]) -> String {
+    match paths {
+        [path] => format!(
+            "'{}'",
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string())
+        ),
+        _ => format!("{} items", paths.len()),
+    }
+}
+
+#[derive(Clone)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+    // Cursor position counted in chars, not bytes
+    pub cursor: usize,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind) -> Prompt {
+        Prompt::with_input(kind, String::new())
+    }
+
+    pub fn with_input(kind: PromptKind, input: String) -> Prompt {
+        let cursor = input.chars().count();
+        Prompt {
+            kind,
+            input,
+            cursor,
+        }
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.input
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.input.len(), |(index, _)| index)
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let index = self.byte_index(self.cursor);
+        self.input.insert(index, c);
+        self.cursor += 1;
+    }
+
+    pub fn delete_previous_char(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let index = self.byte_index(self.cursor);
+            self.input.remove(index);
+        }
+    }
+
+    pub fn delete_next_char(&mut self) {
+        if self.cursor < self.input.chars().count() {
+            let index = self.byte_index(self.cursor);
+            self.input.remove(index);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.input.chars().count());
+    }
+
+    pub fn move_to_start(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_to_end(&mut self) {
+        self.cursor = self.input.chars().count();
+    }
+}
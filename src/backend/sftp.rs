@@ -0,0 +1,1015 @@
+// Remote directories over SFTP, opened from urls like sftp://user@host:port/path. The connection is
+// an `ssh -s <host> sftp` child, so host keys, agent auth and ~/.ssh/config work like they do in ssh
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{self, BufReader, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crossbeam::channel::Sender;
+
+use super::events::event::RstuifmEvent;
+use super::vfs::{self, FileKind, Metadata, Provider};
+
+pub const SCHEME: &str = "sftp://";
+
+const PROTOCOL_VERSION: u32 = 3;
+// Every server handles reads and writes of this size
+const CHUNK_SIZE: u32 = 32 * 1024;
+const MAX_PACKET_SIZE: usize = 256 * 1024;
+
+const FXP_INIT: u8 = 1;
+const FXP_VERSION: u8 = 2;
+const FXP_OPEN: u8 = 3;
+const FXP_CLOSE: u8 = 4;
+const FXP_READ: u8 = 5;
+const FXP_WRITE: u8 = 6;
+const FXP_LSTAT: u8 = 7;
+const FXP_SETSTAT: u8 = 9;
+const FXP_OPENDIR: u8 = 11;
+const FXP_READDIR: u8 = 12;
+const FXP_REMOVE: u8 = 13;
+const FXP_MKDIR: u8 = 14;
+const FXP_RMDIR: u8 = 15;
+const FXP_REALPATH: u8 = 16;
+const FXP_STAT: u8 = 17;
+const FXP_RENAME: u8 = 18;
+const FXP_READLINK: u8 = 19;
+const FXP_SYMLINK: u8 = 20;
+const FXP_STATUS: u8 = 101;
+const FXP_HANDLE: u8 = 102;
+const FXP_DATA: u8 = 103;
+const FXP_NAME: u8 = 104;
+const FXP_ATTRS: u8 = 105;
+
+const STATUS_OK: u32 = 0;
+const STATUS_EOF: u32 = 1;
+const STATUS_NO_SUCH_FILE: u32 = 2;
+const STATUS_PERMISSION_DENIED: u32 = 3;
+const STATUS_OP_UNSUPPORTED: u32 = 8;
+
+const OPEN_READ: u32 = 0x01;
+const OPEN_WRITE: u32 = 0x02;
+const OPEN_CREATE: u32 = 0x08;
+const OPEN_EXCLUSIVE: u32 = 0x20;
+
+const ATTR_SIZE: u32 = 0x01;
+const ATTR_UIDGID: u32 = 0x02;
+const ATTR_PERMISSIONS: u32 = 0x04;
+const ATTR_ACMODTIME: u32 = 0x08;
+const ATTR_EXTENDED: u32 = 0x8000_0000;
+
+const FILE_TYPE_MASK: u32 = 0o170000;
+const FILE_TYPE_DIRECTORY: u32 = 0o040000;
+const FILE_TYPE_FILE: u32 = 0o100000;
+const FILE_TYPE_SYMLINK: u32 = 0o120000;
+
+static CONNECTIONS: LazyLock<Mutex<HashMap<PathBuf, Arc<SftpFs>>>> = LazyLock::new(Mutex::default);
+
+pub fn is_url(input: &str) -> bool {
+    input.starts_with(SCHEME)
+}
+
+pub fn is_remote(path: &Path) -> bool {
+    path.as_os_str().as_bytes().starts_with(SCHEME.as_bytes())
+}
+
+struct SftpUrl {
+    // user@host, handed to ssh as is
+    destination: String,
+    port: Option<u16>,
+    // Empty for the login directory
+    path: String,
+}
+
+impl SftpUrl {
+    fn parse(input: &str) -> io::Result<SftpUrl> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' is not an sftp://[user@]host[:port]/path url", input),
+            )
+        };
+
+        let rest = input.strip_prefix(SCHEME).ok_or_else(invalid)?;
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        // An IPv6 host is bracketed, so only a colon after the bracket starts the port
+        let (destination, port) = match authority.rsplit_once(':') {
+            Some((destination, port))
+                if !port.contains(']')
+                    && (!destination.contains(':') || destination.ends_with(']')) =>
+            {
+                (destination, Some(port.parse().map_err(|_| invalid())?))
+            }
+            _ => (authority, None),
+        };
+        let host = destination
+            .rsplit_once('@')
+            .map_or(destination, |(_, host)| host);
+        let bracketed = host.starts_with('[') || host.ends_with(']');
+        if bracketed && (host.len() < 3 || !host.starts_with('[') || !host.ends_with(']')) {
+            return Err(invalid());
+        }
+        // ssh would take a destination starting with '-' for an option
+        if host.is_empty() || (!bracketed && host.contains(':')) || destination.starts_with('-') {
+            return Err(invalid());
+        }
+
+        Ok(SftpUrl {
+            destination: destination.to_string(),
+            port,
+            path: path.trim_end_matches('/').to_string(),
+        })
+    }
+
+    // ssh takes an IPv6 host without its brackets
+    fn ssh_destination(&self) -> String {
+        let unbracket = |host: &str| {
+            host.trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string()
+        };
+        match self.destination.rsplit_once('@') {
+            Some((user, host)) => format!("{}@{}", user, unbracket(host)),
+            None => unbracket(&self.destination),
+        }
+    }
+
+    // Remote paths show up below this local path, the url without its path
+    fn prefix(&self) -> PathBuf {
+        match self.port {
+            Some(port) => PathBuf::from(format!("{}{}:{}", SCHEME, self.destination, port)),
+            None => PathBuf::from(format!("{}{}", SCHEME, self.destination)),
+        }
+    }
+}
+
+// Connects to the host of `url` unless a connection is open already, then returns the path the
+// url's directory has in the tabs
+pub fn open(url: &str) -> io::Result<PathBuf> {
+    let url = SftpUrl::parse(url)?;
+    let sftp_fs = connection(&url, true)?;
+
+    let remote_path = if url.path.is_empty() {
+        sftp_fs.session.lock().unwrap().real_path(b".")?
+    } else {
+        format!("/{}", url.path).into_bytes()
+    };
+    Ok(sftp_fs.local_path(&remote_path))
+}
+
+// The outcome of opening an sftp:// url on a worker
+pub struct Connected {
+    pub url: String,
+    pub result: io::Result<PathBuf>,
+}
+
+// Opens `url` like `open` does, without waiting for ssh to connect
+pub fn open_in_background(url: &str, event_tx: Sender<RstuifmEvent>) {
+    let url = url.to_string();
+    thread::spawn(move || {
+        let result = open(&url);
+        let _ = event_tx.send(RstuifmEvent::Connected(Connected { url, result }));
+    });
+}
+
+// The connection serving a remote path, like one recorded in the journal by an earlier session
+pub fn provider(path: &Path) -> io::Result<Arc<dyn Provider>> {
+    let authority = path
+        .components()
+        .nth(1)
+        .and_then(|authority| authority.as_os_str().to_str())
+        .unwrap_or_default();
+    let url = SftpUrl::parse(&format!("{}{}", SCHEME, authority))?;
+    Ok(connection(&url, false)?)
+}
+
+// A lost connection is only replaced when asked to, otherwise every redraw would try again. The
+// connections stay unlocked while ssh connects, so other hosts are not held up meanwhile
+fn connection(url: &SftpUrl, reconnect: bool) -> io::Result<Arc<SftpFs>> {
+    let prefix = url.prefix();
+    if let Some(sftp_fs) = live_connection(&prefix, reconnect)? {
+        return Ok(sftp_fs);
+    }
+
+    let mut session = Session::connect(url)?;
+    let mut connections = CONNECTIONS.lock().unwrap();
+    // Another worker connected to the same host first
+    if let Some(sftp_fs) = connections
+        .get(&prefix)
+        .filter(|sftp_fs| sftp_fs.is_alive())
+    {
+        session.disconnect();
+        return Ok(sftp_fs.clone());
+    }
+    let sftp_fs = Arc::new(SftpFs {
+        prefix: prefix.clone(),
+        alive: session.alive.clone(),
+        session: Arc::new(Mutex::new(session)),
+    });
+    connections.insert(prefix.clone(), sftp_fs.clone());
+    vfs::mount(prefix, sftp_fs.clone());
+    Ok(sftp_fs)
+}
+
+fn live_connection(prefix: &Path, reconnect: bool) -> io::Result<Option<Arc<SftpFs>>> {
+    let connections = CONNECTIONS.lock().unwrap();
+    match connections.get(prefix) {
+        Some(sftp_fs) if sftp_fs.is_alive() => Ok(Some(sftp_fs.clone())),
+        Some(_) if !reconnect => Err(io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            format!(
+                "Lost the connection to {}, open it again to reconnect",
+                prefix.display()
+            ),
+        )),
+        _ => Ok(None),
+    }
+}
+
+pub struct SftpFs {
+    prefix: PathBuf,
+    session: Arc<Mutex<Session>>,
+    // Shared with the session, so checking it does not wait for a request in flight
+    alive: Arc<AtomicBool>,
+}
+
+impl SftpFs {
+    fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    fn remote_path(&self, path: &Path) -> io::Result<Vec<u8>> {
+        remote_path(&self.prefix, path)
+    }
+
+    fn local_path(&self, remote_path: &[u8]) -> PathBuf {
+        local_path(&self.prefix, remote_path)
+    }
+
+    fn request_status(&self, kind: u8, payload: Payload) -> io::Result<()> {
+        let mut session = self.session.lock().unwrap();
+        let (response, mut fields) = session.request(kind, payload)?;
+        expect_status(response, &mut fields)
+    }
+
+    fn open_file(&self, path: &Path, flags: u32) -> io::Result<RemoteFile> {
+        let payload = Payload::default()
+            .string(&self.remote_path(path)?)
+            .u32(flags)
+            .u32(0);
+        let handle = self.session.lock().unwrap().handle(FXP_OPEN, payload)?;
+        Ok(RemoteFile {
+            session: self.session.clone(),
+            handle,
+            offset: 0,
+        })
+    }
+
+    fn stat(&self, kind: u8, path: &Path) -> io::Result<Metadata> {
+        let payload = Payload::default().string(&self.remote_path(path)?);
+        let mut session = self.session.lock().unwrap();
+        let (response, mut fields) = session.request(kind, payload)?;
+        match response {
+            FXP_ATTRS => fields.attributes(),
+            _ => Err(unexpected_response(response, &mut fields)),
+        }
+    }
+}
+
+impl Provider for SftpFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let payload = Payload::default().string(&self.remote_path(path)?);
+        let mut session = self.session.lock().unwrap();
+        let handle = session.handle(FXP_OPENDIR, payload)?;
+
+        let mut items = Vec::new();
+        let result = loop {
+            let payload = Payload::default().string(&handle);
+            let (response, mut fields) = match session.request(FXP_READDIR, payload) {
+                Ok(response) => response,
+                Err(e) => break Err(e),
+            };
+            match response {
+                FXP_NAME => match fields.names() {
+                    Ok(names) => items.extend(
+                        names
+                            .into_iter()
+                            .filter(|name| name != b"." && name != b"..")
+                            .map(|name| path.join(OsStr::from_bytes(&name))),
+                    ),
+                    Err(e) => break Err(e),
+                },
+                _ => match unexpected_response(response, &mut fields) {
+                    e if e.kind() == io::ErrorKind::UnexpectedEof => break Ok(()),
+                    e => break Err(e),
+                },
+            }
+        };
+        let _ = session.close(&handle);
+        result.map(|_| items)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.stat(FXP_STAT, path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.stat(FXP_LSTAT, path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let payload = Payload::default().string(&self.remote_path(path)?);
+        let mut session = self.session.lock().unwrap();
+        let target = session.name(FXP_READLINK, payload)?;
+        Ok(PathBuf::from(OsStr::from_bytes(&target)))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(self.open_file(path, OPEN_READ)?))
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+        let mut file = self.open_file(path, OPEN_READ)?;
+        file.offset = offset;
+        let mut buffer = Vec::new();
+        file.take(length).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        let file = self.open_file(path, OPEN_WRITE | OPEN_CREATE | OPEN_EXCLUSIVE)?;
+        Ok(Box::new(file))
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let payload = Payload::default().string(&self.remote_path(path)?).u32(0);
+        self.request_status(FXP_MKDIR, payload)
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        // OpenSSH takes the target first, the other way round from the draft
+        let payload = Payload::default()
+            .string(target.as_os_str().as_bytes())
+            .string(&self.remote_path(path)?);
+        self.request_status(FXP_SYMLINK, payload)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let payload = Payload::default()
+            .string(&self.remote_path(path)?)
+            .u32(ATTR_PERMISSIONS)
+            .u32(mode);
+        self.request_status(FXP_SETSTAT, payload)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let payload = Payload::default()
+            .string(&self.remote_path(from)?)
+            .string(&self.remote_path(to)?);
+        self.request_status(FXP_RENAME, payload)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let payload = Payload::default().string(&self.remote_path(path)?);
+        self.request_status(FXP_REMOVE, payload)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let payload = Payload::default().string(&self.remote_path(path)?);
+        self.request_status(FXP_RMDIR, payload)
+    }
+}
+
+// The path on the server of a local path below `prefix`
+fn remote_path(prefix: &Path, path: &Path) -> io::Result<Vec<u8>> {
+    let relative = path.strip_prefix(prefix).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' is not on {}", path.display(), prefix.display()),
+        )
+    })?;
+    let mut remote_path = b"/".to_vec();
+    remote_path.extend_from_slice(relative.as_os_str().as_bytes());
+    Ok(remote_path)
+}
+
+// The local path below `prefix` of a path on the server
+fn local_path(prefix: &Path, remote_path: &[u8]) -> PathBuf {
+    let relative = remote_path.strip_prefix(b"/").unwrap_or(remote_path);
+    let relative = relative.strip_suffix(b"/").unwrap_or(relative);
+    if relative.is_empty() {
+        prefix.to_path_buf()
+    } else {
+        prefix.join(OsStr::from_bytes(relative))
+    }
+}
+
+struct Session {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    next_id: u32,
+    alive: Arc<AtomicBool>,
+}
+
+impl Session {
+    fn connect(url: &SftpUrl) -> io::Result<Session> {
+        let mut command = Command::new("ssh");
+        // Password and host key prompts would garble the terminal, so they fail instead
+        command.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"]);
+        if let Some(port) = url.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        let mut child = command
+            .arg("-s")
+            .arg("--")
+            .arg(url.ssh_destination())
+            .arg("sftp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut stderr = child.stderr.take().unwrap();
+        let mut session = Session {
+            input: child.stdin.take().unwrap(),
+            output: BufReader::new(child.stdout.take().unwrap()),
+            child,
+            next_id: 0,
+            alive: Arc::new(AtomicBool::new(true)),
+        };
+
+        if let Err(e) = session.init() {
+            // ssh explains on stderr why it gave up, like a failed host key check
+            session.disconnect();
+            let mut message = String::new();
+            let _ = stderr.read_to_string(&mut message);
+            return match message.trim().lines().last() {
+                Some(line) => Err(io::Error::new(e.kind(), line.to_string())),
+                None => Err(e),
+            };
+        }
+        thread::spawn(move || io::copy(&mut stderr, &mut io::sink()));
+        Ok(session)
+    }
+
+    fn disconnect(&mut self) {
+        self.alive.store(false, Ordering::SeqCst);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    fn init(&mut self) -> io::Result<()> {
+        let payload = PROTOCOL_VERSION.to_be_bytes();
+        self.send(FXP_INIT, &payload)?;
+        let (response, mut fields) = self.receive()?;
+        if response != FXP_VERSION || fields.u32()? < PROTOCOL_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Server does not speak SFTP version 3",
+            ));
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, kind: u8, body: &[u8]) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(body.len() + 5);
+        packet.extend_from_slice(&(body.len() as u32 + 1).to_be_bytes());
+        packet.push(kind);
+        packet.extend_from_slice(body);
+        self.input.write_all(&packet)?;
+        self.input.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<(u8, Fields)> {
+        let mut length = [0; 4];
+        self.output.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length == 0 || length > MAX_PACKET_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Malformed SFTP packet",
+            ));
+        }
+        let mut data = vec![0; length];
+        self.output.read_exact(&mut data)?;
+        Ok((data[0], Fields { data, position: 1 }))
+    }
+
+    // Requests are answered in order since only one is in flight at a time
+    fn request(&mut self, kind: u8, payload: Payload) -> io::Result<(u8, Fields)> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let mut body = id.to_be_bytes().to_vec();
+        body.extend_from_slice(&payload.0);
+
+        let response = self.send(kind, &body).and_then(|_| self.receive());
+        let (response, mut fields) = match response {
+            Ok(response) => response,
+            Err(e) => {
+                // A half sent or half read packet leaves the stream unusable
+                self.disconnect();
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("SFTP connection lost: {}", e),
+                ));
+            }
+        };
+        if fields.u32()? != id {
+            self.disconnect();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SFTP response out of order",
+            ));
+        }
+        Ok((response, fields))
+    }
+
+    fn handle(&mut self, kind: u8, payload: Payload) -> io::Result<Vec<u8>> {
+        let (response, mut fields) = self.request(kind, payload)?;
+        match response {
+            FXP_HANDLE => fields.string(),
+            _ => Err(unexpected_response(response, &mut fields)),
+        }
+    }
+
+    // The single name REALPATH and READLINK answer with
+    fn name(&mut self, kind: u8, payload: Payload) -> io::Result<Vec<u8>> {
+        let (response, mut fields) = self.request(kind, payload)?;
+        match response {
+            FXP_NAME => fields.names()?.into_iter().next().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Empty SFTP name response")
+            }),
+            _ => Err(unexpected_response(response, &mut fields)),
+        }
+    }
+
+    fn real_path(&mut self, path: &[u8]) -> io::Result<Vec<u8>> {
+        self.name(FXP_REALPATH, Payload::default().string(path))
+    }
+
+    // An empty result means the end of the file
+    fn read(&mut self, handle: &[u8], offset: u64, length: u32) -> io::Result<Vec<u8>> {
+        let payload = Payload::default().string(handle).u64(offset).u32(length);
+        let (response, mut fields) = self.request(FXP_READ, payload)?;
+        match response {
+            FXP_DATA => fields.string(),
+            _ => match unexpected_response(response, &mut fields) {
+                e if e.kind() == io::ErrorKind::UnexpectedEof => Ok(Vec::new()),
+                e => Err(e),
+            },
+        }
+    }
+
+    fn write(&mut self, handle: &[u8], offset: u64, data: &[u8]) -> io::Result<()> {
+        let payload = Payload::default().string(handle).u64(offset).string(data);
+        let (response, mut fields) = self.request(FXP_WRITE, payload)?;
+        expect_status(response, &mut fields)
+    }
+
+    fn close(&mut self, handle: &[u8]) -> io::Result<()> {
+        let (response, mut fields) = self.request(FXP_CLOSE, Payload::default().string(handle))?;
+        expect_status(response, &mut fields)
+    }
+}
+
+// An open remote file, reads and writes go to the server one chunk at a time
+struct RemoteFile {
+    session: Arc<Mutex<Session>>,
+    handle: Vec<u8>,
+    offset: u64,
+}
+
+impl Read for RemoteFile {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        let length = buffer.len().min(CHUNK_SIZE as usize);
+        let data = self
+            .session
+            .lock()
+            .unwrap()
+            .read(&self.handle, self.offset, length as u32)?;
+        let length = data.len().min(length);
+        buffer[..length].copy_from_slice(&data[..length]);
+        self.offset += length as u64;
+        Ok(length)
+    }
+}
+
+impl Write for RemoteFile {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let length = buffer.len().min(CHUNK_SIZE as usize);
+        self.session
+            .lock()
+            .unwrap()
+            .write(&self.handle, self.offset, &buffer[..length])?;
+        self.offset += length as u64;
+        Ok(length)
+    }
+
+    // Every write waits for the server to confirm it
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RemoteFile {
+    fn drop(&mut self) {
+        // A worker that panicked holding the session must not make this panic too
+        if let Ok(mut session) = self.session.lock() {
+            let _ = session.close(&self.handle);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Payload(Vec<u8>);
+
+impl Payload {
+    fn u32(mut self, value: u32) -> Payload {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn u64(mut self, value: u64) -> Payload {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn string(self, value: &[u8]) -> Payload {
+        let mut payload = self.u32(value.len() as u32);
+        payload.0.extend_from_slice(value);
+        payload
+    }
+}
+
+struct Fields {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl Fields {
+    fn take(&mut self, length: usize) -> io::Result<&[u8]> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Truncated SFTP packet"))?;
+        let field = &self.data[self.position..end];
+        self.position = end;
+        Ok(field)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> io::Result<Vec<u8>> {
+        let length = self.u32()? as usize;
+        Ok(self.take(length)?.to_vec())
+    }
+
+    // File names of a NAME response, the long names and attributes are skipped
+    fn names(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let count = self.u32()?;
+        let mut names = Vec::new();
+        for _ in 0..count {
+            names.push(self.string()?);
+            self.string()?;
+            self.attributes()?;
+        }
+        Ok(names)
+    }
+
+    fn attributes(&mut self) -> io::Result<Metadata> {
+        let flags = self.u32()?;
+        let len = if flags & ATTR_SIZE != 0 {
+            self.u64()?
+        } else {
+            0
+        };
+        let owner = if flags & ATTR_UIDGID != 0 {
+            Some((self.u32()?, self.u32()?))
+        } else {
+            None
+        };
+        let mode = if flags & ATTR_PERMISSIONS != 0 {
+            Some(self.u32()?)
+        } else {
+            None
+        };
+        let (accessed, modified) = if flags & ATTR_ACMODTIME != 0 {
+            let accessed = UNIX_EPOCH + Duration::from_secs(self.u32()? as u64);
+            let modified = UNIX_EPOCH + Duration::from_secs(self.u32()? as u64);
+            (Some(accessed), Some(modified))
+        } else {
+            (None, None)
+        };
+        if flags & ATTR_EXTENDED != 0 {
+            for _ in 0..self.u32()? {
+                self.string()?;
+                self.string()?;
+            }
+        }
+
+        let kind = match mode.map(|mode| mode & FILE_TYPE_MASK) {
+            Some(FILE_TYPE_DIRECTORY) => FileKind::Directory,
+            Some(FILE_TYPE_FILE) => FileKind::File,
+            Some(FILE_TYPE_SYMLINK) => FileKind::Symlink,
+            _ => FileKind::Other,
+        };
+        Ok(Metadata {
+            kind,
+            len,
+            modified,
+            accessed,
+            changed: None,
+            mode,
+            owner,
+            links: None,
+            blocks: None,
+            identity: None,
+        })
+    }
+}
+
+// Ok for a STATUS of success, the end of a file or listing is an UnexpectedEof error
+fn expect_status(response: u8, fields: &mut Fields) -> io::Result<()> {
+    if response != FXP_STATUS {
+        return Err(unexpected_response(response, fields));
+    }
+    let code = fields.u32()?;
+    let message = fields
+        .string()
+        .map(|message| String::from_utf8_lossy(&message).to_string())
+        .unwrap_or_default();
+
+    let kind = match code {
+        STATUS_OK => return Ok(()),
+        STATUS_EOF => io::ErrorKind::UnexpectedEof,
+        STATUS_NO_SUCH_FILE => io::ErrorKind::NotFound,
+        STATUS_PERMISSION_DENIED => io::ErrorKind::PermissionDenied,
+        STATUS_OP_UNSUPPORTED => io::ErrorKind::Unsupported,
+        _ => io::ErrorKind::Other,
+    };
+    if message.is_empty() {
+        Err(io::Error::from(kind))
+    } else {
+        Err(io::Error::new(kind, message))
+    }
+}
+
+fn unexpected_response(response: u8, fields: &mut Fields) -> io::Error {
+    match response {
+        FXP_STATUS => match expect_status(response, fields) {
+            Err(e) => e,
+            Ok(()) => io::Error::new(io::ErrorKind::InvalidData, "Unexpected SFTP status"),
+        },
+        _ => io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unexpected SFTP response {}", response),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Option<(String, Option<u16>, String)> {
+        SftpUrl::parse(input)
+            .ok()
+            .map(|url| (url.destination, url.port, url.path))
+    }
+
+    fn fields(payload: Payload) -> Fields {
+        Fields {
+            data: payload.0,
+            position: 0,
+        }
+    }
+
+    #[test]
+    fn parses_user_port_and_path() {
+        assert_eq!(
+            parse("sftp://alice@example.org:2222/srv/data/"),
+            Some(("alice@example.org".into(), Some(2222), "srv/data".into()))
+        );
+        assert_eq!(
+            parse("sftp://example.org/srv"),
+            Some(("example.org".into(), None, "srv".into()))
+        );
+    }
+
+    #[test]
+    fn empty_path_is_the_login_directory() {
+        assert_eq!(
+            parse("sftp://example.org"),
+            Some(("example.org".into(), None, String::new()))
+        );
+        assert_eq!(
+            parse("sftp://example.org:22/"),
+            Some(("example.org".into(), Some(22), String::new()))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_urls() {
+        for input in [
+            "example.org/srv",
+            "sftp://",
+            "sftp://alice@/srv",
+            "sftp://example.org:port/srv",
+            "sftp://example.org:/srv",
+            // ssh would read these as options
+            "sftp://-oProxyCommand=x/srv",
+            "sftp://-v@example.org",
+        ] {
+            assert!(parse(input).is_none(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_bracketed_ipv6_hosts() {
+        assert_eq!(
+            parse("sftp://[::1]/x"),
+            Some(("[::1]".into(), None, "x".into()))
+        );
+        assert_eq!(
+            parse("sftp://alice@[fe80::1]:2222/x"),
+            Some(("alice@[fe80::1]".into(), Some(2222), "x".into()))
+        );
+        for input in ["sftp://::1/x", "sftp://[::1/x", "sftp://[]/x"] {
+            assert!(parse(input).is_none(), "{}", input);
+        }
+
+        let url = SftpUrl::parse("sftp://alice@[::1]:2222/x").unwrap();
+        assert_eq!(url.ssh_destination(), "alice@::1");
+        assert_eq!(url.prefix(), PathBuf::from("sftp://alice@[::1]:2222"));
+    }
+
+    #[test]
+    fn maps_remote_paths_below_the_prefix() {
+        let prefix = SftpUrl::parse("sftp://alice@example.org:2222")
+            .unwrap()
+            .prefix();
+        let local = prefix.join("srv/data");
+
+        assert_eq!(remote_path(&prefix, &local).unwrap(), b"/srv/data");
+        assert_eq!(remote_path(&prefix, &prefix).unwrap(), b"/");
+        assert!(remote_path(&prefix, Path::new("sftp://example.org/srv")).is_err());
+
+        assert_eq!(local_path(&prefix, b"/srv/data/"), local);
+        assert_eq!(local_path(&prefix, b"/"), prefix);
+        // Paths on the server are bytes, not necessarily UTF-8
+        assert_eq!(
+            local_path(&prefix, b"/caf\xe9"),
+            prefix.join(OsStr::from_bytes(b"caf\xe9"))
+        );
+    }
+
+    #[test]
+    fn payload_fields_round_trip() {
+        let payload = Payload::default()
+            .u32(7)
+            .u64(1 << 40)
+            .string(b"name")
+            .string(b"");
+        assert_eq!(
+            payload.0[..16],
+            [0, 0, 0, 7, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 4]
+        );
+
+        let mut fields = fields(payload);
+        assert_eq!(fields.u32().unwrap(), 7);
+        assert_eq!(fields.u64().unwrap(), 1 << 40);
+        assert_eq!(fields.string().unwrap(), b"name");
+        assert_eq!(fields.string().unwrap(), b"");
+        assert_eq!(fields.u32().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_strings_are_invalid() {
+        let mut fields = fields(Payload::default().u32(10).u32(0));
+        assert_eq!(
+            fields.string().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn decodes_attributes() {
+        let flags = ATTR_SIZE | ATTR_UIDGID | ATTR_PERMISSIONS | ATTR_ACMODTIME | ATTR_EXTENDED;
+        let payload = Payload::default()
+            .u32(flags)
+            .u64(1234)
+            .u32(1000)
+            .u32(100)
+            .u32(FILE_TYPE_DIRECTORY | 0o755)
+            .u32(10)
+            .u32(20)
+            .u32(1)
+            .string(b"vendor@example.org")
+            .string(b"data")
+            .u32(99);
+        let mut fields = fields(payload);
+        let metadata = fields.attributes().unwrap();
+
+        assert_eq!(metadata.kind, FileKind::Directory);
+        assert_eq!(metadata.len, 1234);
+        assert_eq!(metadata.owner, Some((1000, 100)));
+        assert_eq!(metadata.mode, Some(FILE_TYPE_DIRECTORY | 0o755));
+        assert_eq!(
+            metadata.accessed,
+            Some(UNIX_EPOCH + Duration::from_secs(10))
+        );
+        assert_eq!(
+            metadata.modified,
+            Some(UNIX_EPOCH + Duration::from_secs(20))
+        );
+        // Extended attributes are skipped, leaving what follows them
+        assert_eq!(fields.u32().unwrap(), 99);
+    }
+
+    #[test]
+    fn attributes_without_flags_are_unknown() {
+        let metadata = fields(Payload::default().u32(0)).attributes().unwrap();
+        assert_eq!(metadata.kind, FileKind::Other);
+        assert_eq!(metadata.len, 0);
+        assert_eq!(metadata.mode, None);
+        assert_eq!(metadata.modified, None);
+    }
+
+    #[test]
+    fn status_codes_map_to_error_kinds() {
+        let status = |code: u32, message: &[u8]| {
+            let mut fields = fields(Payload::default().u32(code).string(message));
+            expect_status(FXP_STATUS, &mut fields)
+        };
+        assert!(status(STATUS_OK, b"").is_ok());
+        assert_eq!(
+            status(STATUS_EOF, b"").unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        let e = status(STATUS_NO_SUCH_FILE, b"No such file").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert_eq!(e.to_string(), "No such file");
+        assert_eq!(
+            status(STATUS_PERMISSION_DENIED, b"").unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+
+        let mut fields = fields(Payload::default());
+        assert_eq!(
+            expect_status(FXP_DATA, &mut fields).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    // Needs an ssh server on localhost that takes the user's key without a prompt
+    #[test]
+    #[ignore]
+    fn browses_and_writes_over_localhost() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("hello.txt"), "hello").unwrap();
+
+        let url = format!("{}localhost{}", SCHEME, directory.path().display());
+        let path = open(&url).unwrap();
+        assert!(is_remote(&path));
+        let provider = vfs::resolve(&path).unwrap();
+
+        let names = provider.read_dir(&path).unwrap();
+        assert_eq!(names, vec![path.join("hello.txt")]);
+        let metadata = provider.metadata(&path.join("hello.txt")).unwrap();
+        assert_eq!((metadata.kind, metadata.len), (FileKind::File, 5));
+        assert_eq!(
+            provider.read_range(&path.join("hello.txt"), 1, 3).unwrap(),
+            b"ell"
+        );
+
+        let mut file = provider.create(&path.join("new.txt")).unwrap();
+        file.write_all(b"written remotely").unwrap();
+        drop(file);
+        provider
+            .rename(&path.join("new.txt"), &path.join("renamed.txt"))
+            .unwrap();
+        assert_eq!(
+            std::fs::read(directory.path().join("renamed.txt")).unwrap(),
+            b"written remotely"
+        );
+        provider.remove_file(&path.join("renamed.txt")).unwrap();
+        assert!(!directory.path().join("renamed.txt").exists());
+    }
+}
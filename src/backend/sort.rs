@@ -0,0 +1,448 @@
+// Orders directory listings. Defaults come from `$XDG_CONFIG_HOME/rstuifm/sort.toml`:
+//
+// mode = "name" # name, size, modified, changed, extension or type
+// case_sensitive = false
+// reverse = false
+// directories_first = true
+// # Sorts picked in a directory are used again when coming back, also in later sessions
+// remember_per_directory = false
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::vfs::Metadata;
+use crate::helper_functions;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Changed,
+    Extension,
+    Type,
+}
+
+impl SortMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortMode::Name => "name",
+            SortMode::Size => "size",
+            SortMode::Modified => "modification time",
+            SortMode::Changed => "change time",
+            SortMode::Extension => "extension",
+            SortMode::Type => "type",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SortOptions {
+    pub mode: SortMode,
+    pub case_sensitive: bool,
+    pub reverse: bool,
+    pub directories_first: bool,
+}
+
+impl Default for SortOptions {
+    fn default() -> SortOptions {
+        SortOptions {
+            mode: SortMode::Name,
+            case_sensitive: false,
+            reverse: false,
+            directories_first: true,
+        }
+    }
+}
+
+// What an item is sorted by
+pub struct SortItem<'a> {
+    pub name: &'a str,
+    pub is_dir: bool,
+    pub metadata: Option<&'a Metadata>,
+}
+
+impl SortOptions {
+    // Sizes and times come largest and newest first like in `ls`, ties are ordered by name
+    pub fn compare(&self, a: &SortItem, b: &SortItem) -> Ordering {
+        if self.directories_first && a.is_dir != b.is_dir {
+            return b.is_dir.cmp(&a.is_dir);
+        }
+
+        let ordering = match self.mode {
+            SortMode::Name => Ordering::Equal,
+            SortMode::Size => {
+                let len = |item: &SortItem| item.metadata.map(|metadata| metadata.len);
+                len(b).cmp(&len(a))
+            }
+            SortMode::Modified => {
+                let modified =
+                    |item: &SortItem| item.metadata.and_then(|metadata| metadata.modified);
+                modified(b).cmp(&modified(a))
+            }
+            SortMode::Changed => {
+                let changed = |item: &SortItem| item.metadata.and_then(|metadata| metadata.changed);
+                changed(b).cmp(&changed(a))
+            }
+            SortMode::Extension => {
+                let extension = |item: &SortItem| {
+                    Path::new(item.name)
+                        .extension()
+                        .map(|extension| self.fold_case(&extension.to_string_lossy()))
+                };
+                extension(a).cmp(&extension(b))
+            }
+            SortMode::Type => file_type(a).cmp(file_type(b)),
+        }
+        .then_with(|| natural_cmp(a.name, b.name, self.case_sensitive))
+        .then_with(|| a.name.cmp(b.name));
+
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    fn fold_case(&self, text: &str) -> String {
+        if self.case_sensitive {
+            text.to_string()
+        } else {
+            text.to_lowercase()
+        }
+    }
+
+    // Like "size, reversed, directories mixed in"
+    pub fn describe(&self) -> String {
+        let mut description = String::from(self.mode.label());
+        if self.reverse {
+            description.push_str(", reversed");
+        }
+        if !self.directories_first {
+            description.push_str(", directories mixed in");
+        }
+        if self.case_sensitive {
+            description.push_str(", case-sensitive");
+        }
+        description
+    }
+}
+
+// Guessed from the name, reading every file would make sorting as slow as previewing them all
+fn file_type<'a>(item: &SortItem<'a>) -> &'a str {
+    if item.is_dir {
+        "inode/directory"
+    } else {
+        mime_guess::from_path(item.name)
+            .first_raw()
+            .unwrap_or("application/octet-stream")
+    }
+}
+
+// Compares like `ls -v`, runs of digits by their value so that file2 comes before file10
+pub fn natural_cmp(mut a: &str, mut b: &str, case_sensitive: bool) -> Ordering {
+    loop {
+        let (a_char, b_char) = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char)) => (a_char, b_char),
+        };
+
+        let ordering = if a_char.is_ascii_digit() && b_char.is_ascii_digit() {
+            let (a_number, a_rest) = split_number(a);
+            let (b_number, b_rest) = split_number(b);
+            (a, b) = (a_rest, b_rest);
+            let a_digits = a_number.trim_start_matches('0');
+            let b_digits = b_number.trim_start_matches('0');
+            a_digits
+                .len()
+                .cmp(&b_digits.len())
+                .then_with(|| a_digits.cmp(b_digits))
+        } else {
+            (a, b) = (&a[a_char.len_utf8()..], &b[b_char.len_utf8()..]);
+            if case_sensitive || a_char == b_char {
+                a_char.cmp(&b_char)
+            } else {
+                a_char.to_lowercase().cmp(b_char.to_lowercase())
+            }
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+// The leading digits of `text` and the rest
+fn split_number(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct SortConfig {
+    #[serde(flatten)]
+    default: SortOptions,
+    remember_per_directory: bool,
+}
+
+#[derive(Default)]
+pub struct SortSettings {
+    // Used in directories without a remembered sort
+    pub default: SortOptions,
+    remember_per_directory: bool,
+    remembered: HashMap<PathBuf, SortOptions>,
+}
+
+impl SortSettings {
+    // A missing config means the defaults, a broken one is reported
+    pub fn load() -> Result<SortSettings> {
+        let path = config_path();
+        let config: SortConfig = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .with_context(|| format!("Invalid sort config '{}'", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => SortConfig::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read '{}'", path.display()))
+            }
+        };
+
+        let remembered = if config.remember_per_directory {
+            fs::read_to_string(remembered_path())
+                .ok()
+                .and_then(|contents| serde_json::from_str(&contents).ok())
+                .unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(SortSettings {
+            default: config.default,
+            remember_per_directory: config.remember_per_directory,
+            remembered,
+        })
+    }
+
+    // Remembered sorts belong to directories, otherwise a tab keeps its sort while navigating
+    pub fn for_directory(&self, current: SortOptions, path: &Path) -> SortOptions {
+        if self.remember_per_directory {
+            self.remembered.get(path).copied().unwrap_or(self.default)
+        } else {
+            current
+        }
+    }
+
+    // Keeps the sort picked in `path` when remembering is configured
+    pub fn remember(&mut self, path: &Path, options: SortOptions) -> Result<()> {
+        if !self.remember_per_directory {
+            return Ok(());
+        }
+        if options == self.default {
+            self.remembered.remove(path);
+        } else {
+            self.remembered.insert(path.to_path_buf(), options);
+        }
+
+        let path = remembered_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("[SortSettings.remember()] Failed to create state directory")?;
+        }
+        let contents = serde_json::to_string(&self.remembered)
+            .context("[SortSettings.remember()] Failed to serialize")?;
+        fs::write(&path, contents).context("[SortSettings.remember()] Failed to write sorts")
+    }
+}
+
+fn config_path() -> PathBuf {
+    helper_functions::xdg_directory("XDG_CONFIG_HOME", ".config")
+        .join("rstuifm")
+        .join("sort.toml")
+}
+
+fn remembered_path() -> PathBuf {
+    helper_functions::xdg_directory("XDG_STATE_HOME", ".local/state")
+        .join("rstuifm")
+        .join("sort.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::vfs::FileKind;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn metadata(len: u64, modified: u64) -> Metadata {
+        Metadata {
+            kind: FileKind::File,
+            len,
+            modified: Some(UNIX_EPOCH + Duration::from_secs(modified)),
+            accessed: None,
+            changed: None,
+            mode: None,
+            owner: None,
+            links: None,
+            blocks: None,
+            identity: None,
+        }
+    }
+
+    fn file<'a>(name: &'a str, metadata: &'a Metadata) -> SortItem<'a> {
+        SortItem {
+            name,
+            is_dir: false,
+            metadata: Some(metadata),
+        }
+    }
+
+    fn dir(name: &str) -> SortItem<'_> {
+        SortItem {
+            name,
+            is_dir: true,
+            metadata: None,
+        }
+    }
+
+    fn sorted<'a>(options: SortOptions, mut items: Vec<SortItem<'a>>) -> Vec<&'a str> {
+        items.sort_by(|a, b| options.compare(a, b));
+        items.into_iter().map(|item| item.name).collect()
+    }
+
+    fn natural_sorted(mut names: Vec<&str>, case_sensitive: bool) -> Vec<&str> {
+        names.sort_by(|a, b| natural_cmp(a, b, case_sensitive));
+        names
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(
+            natural_sorted(vec!["file10", "file2", "file1", "file02a"], false),
+            ["file1", "file2", "file02a", "file10"]
+        );
+        assert_eq!(natural_cmp("v1.10", "v1.9", false), Ordering::Greater);
+        // Longer than any integer type
+        assert_eq!(
+            natural_cmp("x123456789012345678901234567890", "x99", false),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn leading_zeros_compare_equal() {
+        assert_eq!(natural_cmp("a007", "a7", false), Ordering::Equal);
+        assert_eq!(natural_cmp("a007b", "a7c", false), Ordering::Less);
+    }
+
+    #[test]
+    fn case_folds_unless_case_sensitive() {
+        assert_eq!(natural_cmp("Readme", "readme", false), Ordering::Equal);
+        assert_eq!(
+            natural_sorted(vec!["b", "A", "a", "B"], true),
+            ["A", "B", "a", "b"]
+        );
+        assert_eq!(natural_cmp("Ärger", "ärger", false), Ordering::Equal);
+    }
+
+    #[test]
+    fn prefixes_come_first() {
+        assert_eq!(natural_cmp("file", "file1", false), Ordering::Less);
+        assert_eq!(natural_cmp("", "a", false), Ordering::Less);
+    }
+
+    #[test]
+    fn directories_come_first_unless_mixed_in() {
+        let small = metadata(1, 0);
+        let items = || vec![file("a", &small), dir("z"), file("b", &small), dir("c")];
+
+        assert_eq!(
+            sorted(SortOptions::default(), items()),
+            ["c", "z", "a", "b"]
+        );
+        let mixed = SortOptions {
+            directories_first: false,
+            ..SortOptions::default()
+        };
+        assert_eq!(sorted(mixed, items()), ["a", "b", "c", "z"]);
+    }
+
+    #[test]
+    fn sizes_and_times_sort_largest_and_newest_first() {
+        let (small, large) = (metadata(1, 300), metadata(100, 100));
+        let items = || vec![file("small", &small), file("large", &large)];
+
+        let by_size = SortOptions {
+            mode: SortMode::Size,
+            ..SortOptions::default()
+        };
+        assert_eq!(sorted(by_size, items()), ["large", "small"]);
+        let by_modified = SortOptions {
+            mode: SortMode::Modified,
+            ..SortOptions::default()
+        };
+        assert_eq!(sorted(by_modified, items()), ["small", "large"]);
+    }
+
+    #[test]
+    fn ties_are_ordered_by_name() {
+        let same = metadata(5, 5);
+        let by_size = SortOptions {
+            mode: SortMode::Size,
+            ..SortOptions::default()
+        };
+        assert_eq!(
+            sorted(
+                by_size,
+                vec![file("b10", &same), file("B2", &same), file("b2", &same)]
+            ),
+            ["B2", "b2", "b10"]
+        );
+    }
+
+    #[test]
+    fn extension_sort_puts_names_without_one_first() {
+        let any = metadata(0, 0);
+        let by_extension = SortOptions {
+            mode: SortMode::Extension,
+            ..SortOptions::default()
+        };
+        assert_eq!(
+            sorted(
+                by_extension,
+                vec![
+                    file("b.TXT", &any),
+                    file("Makefile", &any),
+                    file("a.rs", &any),
+                    file("c.txt", &any)
+                ]
+            ),
+            ["Makefile", "a.rs", "b.TXT", "c.txt"]
+        );
+    }
+
+    #[test]
+    fn reverse_keeps_directories_first() {
+        let any = metadata(0, 0);
+        let reversed = SortOptions {
+            reverse: true,
+            ..SortOptions::default()
+        };
+        assert_eq!(
+            sorted(
+                reversed,
+                vec![file("a", &any), dir("d"), file("B", &any), file("b", &any)]
+            ),
+            ["d", "b", "B", "a"]
+        );
+    }
+}
@@ -1,9 +1,25 @@
+use std::fmt;
 use std::path::PathBuf;
 use anyhow::{anyhow, Result};
 
+// Where a link points as written in it, relative targets start at the link's directory
+#[derive(Clone)]
+pub struct LinkTarget {
+    pub path: PathBuf,
+    // Nothing exists at the target
+    pub broken: bool,
+}
+
+impl LinkTarget {
+    // Shown after the link's name
+    pub fn suffix(&self) -> String {
+        format!(" -> {}", self.path.display())
+    }
+}
+
 #[derive(Clone)]
 pub struct Symlink {
-    _path: PathBuf,
+    pub path: PathBuf,
     name: String,
 }
 
@@ -15,12 +31,16 @@ impl Symlink {
             .to_str()
             .ok_or_else(|| anyhow!("[app_backend.Symlink.new()] failed to unwrap Option<&str>"))?
             .to_string();
-        Ok(Symlink { _path: path, name })
+        Ok(Symlink { path, name })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 }
 
-impl ToString for Symlink {
-    fn to_string(&self) -> String {
-        self.name.clone()
+impl fmt::Display for Symlink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
     }
 }
@@ -1,11 +1,17 @@
-use super::{dir_list::FileSystemItem, DirList};
+use super::{dir_list::FileSystemItem, sort::SortOptions, DirList};
 use crate::ui::widgets::{DirSelectionList, ThreePaneLayout};
 use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 pub struct Tab {
     pub working_directory: PathBuf,
     pub ui: ThreePaneLayout,
+    pub sort: SortOptions,
+    // Follows the app wide preference, applied to every listing the tab gets
+    pub show_hidden: bool,
+    // Marks of directories we navigated away from, restored when coming back
+    saved_marks: HashMap<PathBuf, HashSet<String>>,
 }
 
 pub struct Tabs {
@@ -32,30 +38,181 @@ impl Tabs {
     pub fn selected_tab_ref_mut(&mut self) -> &mut Tab {
         self.tabs_vec.get_mut(self.selected_index).unwrap()
     }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Tab> {
+        self.tabs_vec.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Tab> {
+        self.tabs_vec.iter_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tabs_vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tabs_vec.is_empty()
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    // Opens a tab right after the selected one and switches to it
+    pub fn open(&mut self, tab: Tab) {
+        self.selected_index += 1;
+        self.tabs_vec.insert(self.selected_index, tab);
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected_index = (self.selected_index + 1) % self.tabs_vec.len();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected_index = (self.selected_index + self.tabs_vec.len() - 1) % self.tabs_vec.len();
+    }
+
+    // The last tab cannot be closed
+    pub fn close_selected(&mut self) -> Result<()> {
+        if self.tabs_vec.len() == 1 {
+            return Err(anyhow!("Cannot close the last tab"));
+        }
+
+        self.tabs_vec.remove(self.selected_index);
+        self.selected_index = self.selected_index.min(self.tabs_vec.len() - 1);
+        Ok(())
+    }
 }
 
 impl Tab {
-    pub fn new(working_directory: PathBuf, dir_list: DirList) -> Tab {
+    pub fn new(
+        working_directory: PathBuf,
+        mut dir_list: DirList,
+        sort: SortOptions,
+        show_hidden: bool,
+    ) -> Tab {
+        dir_list.sort(sort);
+        dir_list.set_show_hidden(show_hidden);
         let ui = ThreePaneLayout::new(dir_list);
 
         Tab {
             working_directory,
             ui,
+            sort,
+            show_hidden,
+            saved_marks: HashMap::new(),
         }
     }
 
     pub fn selected_item(&self) -> Option<FileSystemItem> {
         match &self.ui.mid_pane {
-            Some(mid_pane) => {
-                let index = mid_pane.state;
-                mid_pane.items.get(index)
-            }
+            Some(mid_pane) => mid_pane.selected_item(),
             None => None,
         }
     }
 
-    pub fn select(&mut self, new_path: PathBuf, new_dir_list: DirList) {
+    pub fn select(&mut self, new_path: PathBuf, mut new_dir_list: DirList, sort: SortOptions) {
+        if let Some(mid_pane) = self.ui.mid_pane.take() {
+            if mid_pane.marked.is_empty() {
+                self.saved_marks.remove(&self.working_directory);
+            } else {
+                self.saved_marks
+                    .insert(self.working_directory.clone(), mid_pane.marked);
+            }
+        }
+
+        self.sort = sort;
+        new_dir_list.sort(sort);
+        new_dir_list.set_show_hidden(self.show_hidden);
+        let mut mid_pane = DirSelectionList::from(new_dir_list);
+        if let Some(marked) = self.saved_marks.remove(&new_path) {
+            mid_pane.marked = marked;
+            mid_pane.retain_existing_marks();
+        }
+
         self.working_directory = new_path;
-        self.ui.mid_pane = Some(DirSelectionList::from(new_dir_list));
+        self.ui.mid_pane = Some(mid_pane);
+    }
+
+    // Marked items if there are any, otherwise the item under the cursor
+    pub fn target_items(&self) -> Vec<FileSystemItem> {
+        let marked = self
+            .ui
+            .mid_pane
+            .as_ref()
+            .map(|mid_pane| mid_pane.marked_items())
+            .unwrap_or_default();
+
+        if marked.is_empty() {
+            self.selected_item().into_iter().collect()
+        } else {
+            marked
+        }
+    }
+
+    // Swaps in a reloaded DirList while keeping the cursor, marks and filter on the same items where
+    // possible
+    pub fn refresh(&mut self, mut new_dir_list: DirList) {
+        new_dir_list.sort(self.sort);
+        new_dir_list.set_show_hidden(self.show_hidden);
+        let selected_name = self.selected_item().map(|item| item.name().to_string());
+        let mid_pane = self
+            .ui
+            .mid_pane
+            .get_or_insert_with(|| DirSelectionList::from(DirList::default()));
+        let old_index = mid_pane.state;
+
+        mid_pane.set_items(new_dir_list);
+        let last_index = mid_pane.len().saturating_sub(1);
+        let index = selected_name
+            .and_then(|name| mid_pane.index_of(&name))
+            .unwrap_or_else(|| old_index.min(last_index));
+        mid_pane.select(index);
+        mid_pane.retain_existing_marks();
+        mid_pane.visual_anchor = mid_pane.visual_anchor.map(|anchor| anchor.min(last_index));
+    }
+
+    // Orders the listing anew, keeping the cursor on the same item. A visual range would cover
+    // other items afterwards, so it is dropped
+    pub fn set_sort(&mut self, sort: SortOptions) {
+        self.sort = sort;
+        let selected_name = self.selected_item().map(|item| item.name().to_string());
+        if let Some(mid_pane) = &mut self.ui.mid_pane {
+            mid_pane.sort(sort);
+            mid_pane.visual_anchor = None;
+            if let Some(index) = selected_name.and_then(|name| mid_pane.index_of(&name)) {
+                mid_pane.select(index);
+            }
+        }
+    }
+
+    // Shows or hides dotfiles, keeping the cursor on the same item. A hidden item under the cursor
+    // hands it to the next item still shown
+    pub fn set_show_hidden(&mut self, show_hidden: bool) {
+        self.show_hidden = show_hidden;
+        let selected_name = self.selected_item().map(|item| item.name().to_string());
+        if let Some(mid_pane) = &mut self.ui.mid_pane {
+            let shown_before = mid_pane
+                .names()
+                .take(mid_pane.state)
+                .filter(|name| show_hidden || !name.starts_with('.'))
+                .count();
+            mid_pane.set_show_hidden(show_hidden);
+            mid_pane.visual_anchor = None;
+            let last_index = mid_pane.len().saturating_sub(1);
+            let index = selected_name
+                .and_then(|name| mid_pane.index_of(&name))
+                .unwrap_or(shown_before.min(last_index));
+            mid_pane.select(index);
+        }
+    }
+
+    pub fn select_name(&mut self, name: &str) {
+        if let Some(mid_pane) = &mut self.ui.mid_pane {
+            if let Some(index) = mid_pane.index_of(name) {
+                mid_pane.select(index);
+            }
+        }
     }
 }
@@ -0,0 +1,316 @@
+// Home trash as described by the freedesktop.org Trash specification
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+use super::file_operations::{self, FileOperationError, NoProgress, OperationResult, Progress};
+use crate::helper_functions;
+
+const INFO_EXTENSION: &str = "trashinfo";
+
+#[derive(Clone)]
+pub struct TrashEntry {
+    // Name inside `files/`, the info file is `info/<name>.trashinfo`
+    pub name: String,
+    pub original_path: Option<PathBuf>,
+    pub deletion_date: Option<String>,
+}
+
+pub fn trash_directory() -> PathBuf {
+    helper_functions::xdg_directory("XDG_DATA_HOME", ".local/share").join("Trash")
+}
+
+pub fn files_directory() -> PathBuf {
+    files_directory_in(&trash_directory())
+}
+
+// The functions below take the trash directory as `root`, the public ones pass the home trash
+fn files_directory_in(root: &Path) -> PathBuf {
+    root.join("files")
+}
+
+fn info_directory_in(root: &Path) -> PathBuf {
+    root.join("info")
+}
+
+fn info_path(root: &Path, name: &str) -> PathBuf {
+    info_directory_in(root).join(format!("{}.{}", name, INFO_EXTENSION))
+}
+
+fn ensure_trash_directories(root: &Path) -> OperationResult<()> {
+    for directory in [files_directory_in(root), info_directory_in(root)] {
+        fs::create_dir_all(&directory).map_err(|e| FileOperationError::from_io(&directory, e))?;
+    }
+    Ok(())
+}
+
+// Creating the info file with create_new reserves the name, as the spec requires
+fn reserve_info_file(root: &Path, path: &Path) -> OperationResult<(String, fs::File)> {
+    let base_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| FileOperationError::InvalidName(path.display().to_string()))?;
+
+    for attempt in 1.. {
+        let name = if attempt == 1 {
+            base_name.clone()
+        } else {
+            format!("{}.{}", base_name, attempt)
+        };
+        if files_directory_in(root)
+            .join(&name)
+            .symlink_metadata()
+            .is_ok()
+        {
+            continue;
+        }
+
+        let info_path = info_path(root, &name);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) => return Ok((name, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(FileOperationError::from_io(&info_path, e)),
+        }
+    }
+    unreachable!()
+}
+
+// Returns the name the item got inside the trash
+pub fn trash(path: &Path, progress: &mut dyn Progress) -> OperationResult<String> {
+    trash_in(&trash_directory(), path, progress)
+}
+
+fn trash_in(root: &Path, path: &Path, progress: &mut dyn Progress) -> OperationResult<String> {
+    ensure_trash_directories(root)?;
+    let original_path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir()
+            .map(|directory| directory.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let (name, mut info_file) = reserve_info_file(root, &original_path)?;
+    let info = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(original_path.as_os_str().as_bytes()),
+        chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+    );
+
+    let result = info_file
+        .write_all(info.as_bytes())
+        .map_err(|e| FileOperationError::from_io(&info_path(root, &name), e))
+        .and_then(|_| {
+            file_operations::move_to(path, &files_directory_in(root).join(&name), progress)
+        });
+
+    match result {
+        Ok(()) => Ok(name),
+        Err(e) => {
+            let _ = fs::remove_file(info_path(root, &name));
+            Err(e)
+        }
+    }
+}
+
+pub fn restore(name: &str, progress: &mut dyn Progress) -> OperationResult<PathBuf> {
+    restore_in(&trash_directory(), name, progress)
+}
+
+fn restore_in(root: &Path, name: &str, progress: &mut dyn Progress) -> OperationResult<PathBuf> {
+    let entry = read_entry_in(root, name);
+    let original_path = entry
+        .original_path
+        .ok_or_else(|| FileOperationError::NotFound(info_path(root, name)))?;
+
+    if let Some(parent) = original_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| FileOperationError::from_io(parent, e))?;
+    }
+    file_operations::move_to(
+        &files_directory_in(root).join(name),
+        &original_path,
+        progress,
+    )?;
+    let _ = fs::remove_file(info_path(root, name));
+    Ok(original_path)
+}
+
+pub fn purge(name: &str, progress: &mut dyn Progress) -> OperationResult<()> {
+    let root = trash_directory();
+    let trashed_path = files_directory_in(&root).join(name);
+    if trashed_path.symlink_metadata().is_ok() {
+        file_operations::delete(&trashed_path, progress)?;
+    }
+    let info_path = info_path(&root, name);
+    if info_path.exists() {
+        file_operations::delete(&info_path, &mut NoProgress)?;
+    }
+    Ok(())
+}
+
+pub fn read_entry(name: &str) -> TrashEntry {
+    read_entry_in(&trash_directory(), name)
+}
+
+fn read_entry_in(root: &Path, name: &str) -> TrashEntry {
+    let mut entry = TrashEntry {
+        name: name.to_string(),
+        original_path: None,
+        deletion_date: None,
+    };
+
+    if let Ok(contents) = fs::read_to_string(info_path(root, name)) {
+        for line in contents.lines() {
+            if let Some(path) = line.strip_prefix("Path=") {
+                let path = PathBuf::from(OsString::from_vec(percent_decode(path)));
+                // Relative paths are relative to the directory containing the trash
+                entry.original_path = Some(match root.parent() {
+                    Some(top) if path.is_relative() => top.join(path),
+                    _ => path,
+                });
+            } else if let Some(date) = line.strip_prefix("DeletionDate=") {
+                entry.deletion_date = Some(date.replace('T', " "));
+            }
+        }
+    }
+    entry
+}
+
+// Paths are bytes, so names that are not UTF-8 survive the trip through the info file
+fn percent_encode(value: &[u8]) -> String {
+    value
+        .iter()
+        .map(|&byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn percent_encoding_round_trips() {
+        for path in [
+            "/home/u/plain.txt",
+            "/home/u/100% done",
+            "/home/u/%41 is not A",
+            "/home/u/Ünïcödé/日本語.txt",
+            "/home/u/a b#c?d",
+        ] {
+            let encoded = percent_encode(path.as_bytes());
+            assert!(encoded.is_ascii(), "{}", encoded);
+            assert!(!encoded.contains(' '), "{}", encoded);
+            assert_eq!(percent_decode(&encoded), path.as_bytes());
+        }
+    }
+
+    #[test]
+    fn percent_encoding_keeps_bytes_that_are_not_utf8() {
+        let path = b"/home/u/caf\xe9";
+        assert_eq!(percent_encode(path), "/home/u/caf%E9");
+        assert_eq!(percent_decode("/home/u/caf%E9"), path);
+    }
+
+    #[test]
+    fn percent_encoding_escapes_percent_and_non_ascii() {
+        assert_eq!(percent_encode(b"/a%b"), "/a%25b");
+        assert_eq!(percent_encode("/é".as_bytes()), "/%C3%A9");
+        // A stray `%` in a hand written info file is kept
+        assert_eq!(percent_decode("/50%"), b"/50%");
+        assert_eq!(percent_decode("/%zz"), b"/%zz");
+    }
+
+    #[test]
+    fn trashes_items_with_the_same_name_side_by_side() {
+        let home = tempfile::tempdir().unwrap();
+        let root = home.path().join("Trash");
+        let first = home.path().join("100% Ünïcödé");
+        let second = home.path().join("other").join("100% Ünïcödé");
+        fs::create_dir(home.path().join("other")).unwrap();
+        fs::write(&first, "first").unwrap();
+        fs::write(&second, "second").unwrap();
+
+        let first_name = trash_in(&root, &first, &mut NoProgress).unwrap();
+        let second_name = trash_in(&root, &second, &mut NoProgress).unwrap();
+        assert_eq!(first_name, "100% Ünïcödé");
+        assert_eq!(second_name, "100% Ünïcödé.2");
+        let contents =
+            |name: &str| fs::read_to_string(files_directory_in(&root).join(name)).unwrap();
+        assert_eq!(contents(&first_name), "first");
+        assert_eq!(contents(&second_name), "second");
+
+        let info = fs::read_to_string(info_path(&root, &second_name)).unwrap();
+        assert!(info.contains("%25%20%C3%9C"), "{}", info);
+        assert_eq!(
+            read_entry_in(&root, &first_name).original_path,
+            Some(first.clone())
+        );
+        assert_eq!(
+            read_entry_in(&root, &second_name).original_path,
+            Some(second.clone())
+        );
+
+        assert_eq!(
+            restore_in(&root, &second_name, &mut NoProgress).unwrap(),
+            second
+        );
+        assert_eq!(fs::read_to_string(&second).unwrap(), "second");
+        assert!(!info_path(&root, &second_name).exists());
+    }
+
+    #[test]
+    fn restores_names_that_are_not_utf8() {
+        let home = tempfile::tempdir().unwrap();
+        let root = home.path().join("Trash");
+        let path = home.path().join(OsStr::from_bytes(b"caf\xe9"));
+        fs::write(&path, "data").unwrap();
+
+        let name = trash_in(&root, &path, &mut NoProgress).unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            read_entry_in(&root, &name).original_path,
+            Some(path.clone())
+        );
+        assert_eq!(restore_in(&root, &name, &mut NoProgress).unwrap(), path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "data");
+    }
+}
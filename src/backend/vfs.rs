@@ -0,0 +1,322 @@
+// Every file system access of the backend goes through a Provider picked by path: sftp:// paths
+// use their connection, mounted prefixes their own provider, paths inside archives an ArchiveFs and
+// the rest LocalFs
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::{
+    self,
+    fs::{MetadataExt, PermissionsExt},
+};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::archive::{self, ArchiveFs};
+use super::sftp;
+
+// Archive indexes kept around, each one holds every entry name of its archive
+const ARCHIVE_CACHE_SIZE: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FileKind {
+    Directory,
+    File,
+    Symlink,
+    Other,
+}
+
+// What providers can tell about an item, fields a provider does not know are None
+#[derive(Clone, Debug)]
+pub struct Metadata {
+    pub kind: FileKind,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    // Status change time, providers without one leave it out
+    pub changed: Option<SystemTime>,
+    // Permission bits
+    pub mode: Option<u32>,
+    pub owner: Option<(u32, u32)>,
+    pub links: Option<u64>,
+    // 512 byte blocks allocated on disk
+    pub blocks: Option<u64>,
+    // Device and inode, these tell whether an item was replaced
+    pub identity: Option<(u64, u64)>,
+}
+
+impl Metadata {
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Directory
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == FileKind::Symlink
+    }
+}
+
+impl From<fs::Metadata> for Metadata {
+    fn from(metadata: fs::Metadata) -> Metadata {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_dir() {
+            FileKind::Directory
+        } else if file_type.is_file() {
+            FileKind::File
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else {
+            FileKind::Other
+        };
+
+        Metadata {
+            kind,
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            accessed: metadata.accessed().ok(),
+            changed: u64::try_from(metadata.ctime())
+                .ok()
+                .map(|seconds| UNIX_EPOCH + Duration::new(seconds, metadata.ctime_nsec() as u32)),
+            mode: Some(metadata.permissions().mode()),
+            owner: Some((metadata.uid(), metadata.gid())),
+            links: Some(metadata.nlink()),
+            blocks: Some(metadata.blocks()),
+            identity: Some((metadata.dev(), metadata.ino())),
+        }
+    }
+}
+
+pub trait Provider: Send + Sync {
+    // Full paths of the items in `path`
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata>;
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>>;
+
+    // Reads at most `length` bytes from `offset`, providers that can seek do better than this
+    fn read_range(&self, path: &Path, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+        let mut reader = self.open(path)?;
+        io::copy(&mut (&mut reader).take(offset), &mut io::sink())?;
+        let mut buffer = Vec::new();
+        reader.take(length).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    // Creates a new file, failing when `path` already exists
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>>;
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()>;
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    // Only local items can be handed to other programs
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+pub fn read_only_error() -> io::Error {
+    io::Error::from(io::ErrorKind::ReadOnlyFilesystem)
+}
+
+pub struct LocalFs;
+
+impl Provider for LocalFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        fs::metadata(path).map(Metadata::from)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        fs::symlink_metadata(path).map(Metadata::from)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+
+    fn read_range(&self, path: &Path, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buffer = Vec::new();
+        file.take(length).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        Ok(Box::new(file))
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        unix::fs::symlink(target, path)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    mounts: Vec<(PathBuf, Arc<dyn Provider>)>,
+    archives: HashMap<PathBuf, Arc<ArchiveFs>>,
+}
+
+static LOCAL: LazyLock<Arc<LocalFs>> = LazyLock::new(|| Arc::new(LocalFs));
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(Mutex::default);
+
+// Serves every path below `prefix` from `provider`, replacing an earlier mount at `prefix`
+pub fn mount(prefix: PathBuf, provider: Arc<dyn Provider>) {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.mounts.retain(|(mounted, _)| *mounted != prefix);
+    registry.mounts.push((prefix, provider));
+}
+
+pub fn unmount(prefix: &Path) {
+    REGISTRY
+        .lock()
+        .unwrap()
+        .mounts
+        .retain(|(mounted, _)| mounted != prefix);
+}
+
+// The longest mounted prefix wins, so mounts can be nested
+fn mounted(path: &Path) -> Option<Arc<dyn Provider>> {
+    REGISTRY
+        .lock()
+        .unwrap()
+        .mounts
+        .iter()
+        .filter(|(prefix, _)| path.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.components().count())
+        .map(|(_, provider)| provider.clone())
+}
+
+// Mount roots have no parent, what is above them belongs to another provider
+pub fn parent(path: &Path) -> Option<&Path> {
+    let is_mount_root = REGISTRY
+        .lock()
+        .unwrap()
+        .mounts
+        .iter()
+        .any(|(prefix, _)| prefix == path);
+    if is_mount_root {
+        None
+    } else {
+        path.parent()
+    }
+}
+
+// The indexed archive holding `path`, found without asking the filesystem
+fn opened_archive(path: &Path) -> Option<PathBuf> {
+    REGISTRY
+        .lock()
+        .unwrap()
+        .archives
+        .keys()
+        .find(|archive| path != archive.as_path() && path.starts_with(archive))
+        .cloned()
+}
+
+// Indexes are reused until the archive file changes, reading one happens outside the lock
+fn archive_provider(archive: &Path) -> io::Result<Arc<dyn Provider>> {
+    let modified = fs::metadata(archive)?.modified().ok();
+    if let Some(archive_fs) = REGISTRY.lock().unwrap().archives.get(archive) {
+        if archive_fs.modified() == modified {
+            return Ok(archive_fs.clone());
+        }
+    }
+
+    let archive_fs = Arc::new(ArchiveFs::open(archive)?);
+    let mut registry = REGISTRY.lock().unwrap();
+    if registry.archives.len() >= ARCHIVE_CACHE_SIZE {
+        registry.archives.clear();
+    }
+    registry
+        .archives
+        .insert(archive.to_path_buf(), archive_fs.clone());
+    Ok(archive_fs)
+}
+
+// The provider holding the item at `path`
+pub fn resolve(path: &Path) -> io::Result<Arc<dyn Provider>> {
+    if sftp::is_remote(path) {
+        return sftp::provider(path);
+    }
+    if let Some(provider) = mounted(path) {
+        return Ok(provider);
+    }
+    // An archive replaced since is looked up again below
+    if let Some(provider) = opened_archive(path).and_then(|archive| archive_provider(&archive).ok())
+    {
+        return Ok(provider);
+    }
+    match archive::split_virtual_path(path) {
+        Some((archive, _)) => archive_provider(&archive),
+        None => Ok(LOCAL.clone()),
+    }
+}
+
+// Like resolve, but an archive file resolves to its contents, which are listed like a directory
+pub fn resolve_directory(path: &Path) -> io::Result<Arc<dyn Provider>> {
+    if mounted(path).is_none() && archive::is_archive(path) {
+        archive_provider(path)
+    } else {
+        resolve(path)
+    }
+}
+
+pub fn metadata(path: &Path) -> io::Result<Metadata> {
+    resolve(path)?.metadata(path)
+}
+
+pub fn symlink_metadata(path: &Path) -> io::Result<Metadata> {
+    resolve(path)?.symlink_metadata(path)
+}
+
+pub fn exists(path: &Path) -> bool {
+    symlink_metadata(path).is_ok()
+}
@@ -0,0 +1,203 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam::channel::Sender;
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask, Watches};
+
+use super::events::RstuifmEvent;
+
+// Changes arrive in bursts, like a whole archive being extracted, so they are collected this long
+// before the panes reload
+const COALESCE_DELAY: Duration = Duration::from_millis(100);
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+// The kernel hands out one descriptor per directory, so paths leading to the same directory, like
+// through a link, share it
+type WatchedPaths = Arc<Mutex<HashMap<WatchDescriptor, HashSet<PathBuf>>>>;
+
+// Watches the directories shown in the panes, changes come back as app events naming the directory
+pub struct Watcher {
+    // None when inotify is not available, the panes then only update after the app's own changes
+    watches: Option<Watches>,
+    paths: WatchedPaths,
+    watched: HashMap<PathBuf, WatchDescriptor>,
+}
+
+impl Watcher {
+    pub fn new(event_tx: Sender<RstuifmEvent>) -> Watcher {
+        let paths = WatchedPaths::default();
+        let watches = Inotify::init().ok().map(|inotify| {
+            let watches = inotify.watches();
+            let thread_paths = paths.clone();
+            thread::spawn(move || read_events(inotify, thread_paths, event_tx));
+            watches
+        });
+
+        Watcher {
+            watches,
+            paths,
+            watched: HashMap::new(),
+        }
+    }
+
+    // Watches exactly `directories` from now on, returns the ones that stopped being watched
+    pub fn watch_only(&mut self, directories: &[PathBuf]) -> Vec<PathBuf> {
+        let watches = match &mut self.watches {
+            Some(watches) => watches,
+            None => return Vec::new(),
+        };
+
+        let unwatched: Vec<PathBuf> = self
+            .watched
+            .keys()
+            .filter(|path| !directories.contains(path))
+            .cloned()
+            .collect();
+        for path in &unwatched {
+            let descriptor = match self.watched.remove(path) {
+                Some(descriptor) => descriptor,
+                None => continue,
+            };
+            let mut paths = self.paths.lock().unwrap();
+            let still_used = paths.get_mut(&descriptor).is_some_and(|shared| {
+                shared.remove(path);
+                !shared.is_empty()
+            });
+            if !still_used {
+                paths.remove(&descriptor);
+                // Fails when the directory is gone, which ended the watch already
+                let _ = watches.remove(descriptor);
+            }
+        }
+
+        for path in directories {
+            // The kernel ends watches of deleted directories, one made again needs a new watch
+            let is_watched = self.watched.get(path).is_some_and(|descriptor| {
+                self.paths
+                    .lock()
+                    .unwrap()
+                    .get(descriptor)
+                    .is_some_and(|shared| shared.contains(path))
+            });
+            if is_watched {
+                continue;
+            }
+            if let Ok(descriptor) = watches.add(path, watch_mask()) {
+                self.paths
+                    .lock()
+                    .unwrap()
+                    .entry(descriptor.clone())
+                    .or_default()
+                    .insert(path.clone());
+                self.watched.insert(path.clone(), descriptor);
+            }
+        }
+
+        unwatched
+    }
+}
+
+fn watch_mask() -> WatchMask {
+    WatchMask::CREATE
+        | WatchMask::DELETE
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO
+        | WatchMask::CLOSE_WRITE
+        | WatchMask::ATTRIB
+        | WatchMask::DELETE_SELF
+        | WatchMask::MOVE_SELF
+        | WatchMask::ONLYDIR
+}
+
+fn read_events(mut inotify: Inotify, paths: WatchedPaths, event_tx: Sender<RstuifmEvent>) {
+    let mut buffer = [0; EVENT_BUFFER_SIZE];
+
+    loop {
+        let mut changed = HashSet::new();
+        match inotify.read_events_blocking(&mut buffer) {
+            Ok(events) => {
+                for event in events {
+                    collect_change(&event.wd, event.mask, &paths, &mut changed);
+                }
+            }
+            Err(_) => return,
+        }
+
+        thread::sleep(COALESCE_DELAY);
+        while let Ok(events) = inotify.read_events(&mut buffer) {
+            let mut any = false;
+            for event in events {
+                collect_change(&event.wd, event.mask, &paths, &mut changed);
+                any = true;
+            }
+            if !any {
+                break;
+            }
+        }
+
+        for path in changed {
+            if event_tx.send(RstuifmEvent::DirectoryChanged(path)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn collect_change(
+    descriptor: &WatchDescriptor,
+    mask: EventMask,
+    paths: &WatchedPaths,
+    changed: &mut HashSet<PathBuf>,
+) {
+    let mut paths = paths.lock().unwrap();
+    if mask.contains(EventMask::Q_OVERFLOW) {
+        // Events were dropped, so any watched directory may have changed
+        changed.extend(paths.values().flatten().cloned());
+    } else if mask.contains(EventMask::IGNORED) {
+        paths.remove(descriptor);
+    } else {
+        changed.extend(paths.get(descriptor).into_iter().flatten().cloned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam::channel;
+
+    const WAIT: Duration = Duration::from_millis(500);
+
+    fn changes(event_rx: &channel::Receiver<RstuifmEvent>) -> HashSet<PathBuf> {
+        let mut changes = HashSet::new();
+        while let Ok(RstuifmEvent::DirectoryChanged(path)) = event_rx.recv_timeout(WAIT) {
+            changes.insert(path);
+        }
+        changes
+    }
+
+    #[test]
+    fn paths_sharing_a_directory_share_its_watch() {
+        let directory = tempfile::tempdir().unwrap();
+        let real = directory.path().join("real");
+        std::fs::create_dir(&real).unwrap();
+        let link = directory.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let (event_tx, event_rx) = channel::unbounded();
+        let mut watcher = Watcher::new(event_tx);
+        watcher.watch_only(&[real.clone(), link.clone()]);
+        std::fs::write(real.join("first"), "").unwrap();
+        assert_eq!(
+            changes(&event_rx),
+            HashSet::from([real.clone(), link.clone()])
+        );
+
+        // Dropping one of the paths keeps the watch for the other
+        assert_eq!(watcher.watch_only(std::slice::from_ref(&real)), vec![link]);
+        std::fs::write(real.join("second"), "").unwrap();
+        assert_eq!(changes(&event_rx), HashSet::from([real]));
+    }
+}
@@ -1,5 +1,8 @@
+use std::env;
+use std::ffi::OsStr;
 use std::fs;
-use std::path::PathBuf;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 
 pub fn has_write_permissions(path: &PathBuf) -> bool {
     let metadata = fs::metadata(path).unwrap();
@@ -7,6 +10,143 @@ pub fn has_write_permissions(path: &PathBuf) -> bool {
     !permissions.readonly()
 }
 
-pub fn can_read_directory(path: &PathBuf) -> bool {
-    fs::read_dir(path).is_ok()
+// Resolves user input relative to `base`, expanding a leading `~`
+pub fn resolve_path(base: &Path, input: &str) -> PathBuf {
+    let input = input.trim();
+    let home = env::var_os("HOME").map(PathBuf::from);
+
+    match (input.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => base.join(input),
+    }
+}
+
+// Drops `.` and resolves `..` without looking at the file system. Everything else stays as written,
+// so prefixes like `sftp://host` survive where Path::components would merge their slashes
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut parts: Vec<&[u8]> = Vec::new();
+    for part in path.as_os_str().as_bytes().split(|&byte| byte == b'/') {
+        match part {
+            b"." => (),
+            b".." => match parts.last() {
+                // Above the root is the root
+                Some(&b"") if parts.len() == 1 => (),
+                Some(last) if !last.is_empty() && *last != b".." => {
+                    parts.pop();
+                }
+                _ => parts.push(part),
+            },
+            _ => parts.push(part),
+        }
+    }
+    PathBuf::from(OsStr::from_bytes(&parts.join(&b'/')))
+}
+
+// Base directory from an XDG variable such as XDG_STATE_HOME, or `default` under $HOME
+pub fn xdg_directory(variable: &str, default: &str) -> PathBuf {
+    env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(default)))
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+}
+
+// Text has no NUL bytes and decodes as UTF-8, apart from a character cut off at the end
+pub fn looks_like_text(bytes: &[u8]) -> bool {
+    if bytes.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+// Renders a whole st_mode like `ls -l`, for example `drwxr-xr-x`, `-rwsr-xr-x` or `drwxrwxrwt`
+pub fn format_mode(mode: u32) -> String {
+    let mut formatted = String::with_capacity(10);
+    formatted.push(match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o140000 => 's',
+        0o060000 => 'b',
+        0o020000 => 'c',
+        0o010000 => 'p',
+        _ => '-',
+    });
+    // setuid, setgid and sticky show in place of the execute bit they belong to
+    for (shift, special, set_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        formatted.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        formatted.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        formatted.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => set_char,
+            (false, true) => set_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_permissions_like_ls() {
+        assert_eq!(format_mode(0o100644), "-rw-r--r--");
+        assert_eq!(format_mode(0o100755), "-rwxr-xr-x");
+        assert_eq!(format_mode(0o040700), "drwx------");
+        assert_eq!(format_mode(0o120777), "lrwxrwxrwx");
+        assert_eq!(format_mode(0o100000), "----------");
+    }
+
+    #[test]
+    fn formats_every_file_type() {
+        let types = [
+            (0o140000, 's'),
+            (0o060000, 'b'),
+            (0o020000, 'c'),
+            (0o010000, 'p'),
+        ];
+        for (file_type, type_char) in types {
+            assert_eq!(
+                format_mode(file_type | 0o660).chars().next(),
+                Some(type_char)
+            );
+        }
+        // Permission bits alone, as some providers report them
+        assert_eq!(format_mode(0o644), "-rw-r--r--");
+    }
+
+    #[test]
+    fn special_bits_replace_the_execute_bit() {
+        assert_eq!(format_mode(0o104755), "-rwsr-xr-x");
+        assert_eq!(format_mode(0o104644), "-rwSr--r--");
+        assert_eq!(format_mode(0o102755), "-rwxr-sr-x");
+        assert_eq!(format_mode(0o102745), "-rwxr-Sr-x");
+        assert_eq!(format_mode(0o041777), "drwxrwxrwt");
+        assert_eq!(format_mode(0o041776), "drwxrwxrwT");
+        assert_eq!(format_mode(0o107777), "-rwsrwsrwt");
+    }
 }
@@ -13,17 +13,17 @@ pub fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
     let mut stdout = io::stdout();
     enable_raw_mode().context("[ui.setup_terminal()] Failed to enable crossterm raw mode")?;
     execute!(stdout, EnterAlternateScreen)?;
-    Ok(Terminal::new(CrosstermBackend::new(stdout))
-        .context("[ui.setup_terminal()] Failed to create new ratatui terminal")?)
+    Terminal::new(CrosstermBackend::new(stdout))
+        .context("[ui.setup_terminal()] Failed to create new ratatui terminal")
 }
 
 pub fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
     disable_raw_mode().context("[ui.restore_terminal()] Failed to disable raw mode")?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen,)
         .context("Failed to leave crossterm alternate screen")?;
-    Ok(terminal
+    terminal
         .show_cursor()
-        .context("Failed to show crossterm cursor")?)
+        .context("Failed to show crossterm cursor")
 }
 
 pub fn process_terminal_resize(
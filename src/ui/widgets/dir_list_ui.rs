@@ -1,6 +1,6 @@
 use ratatui::widgets::{Widget, List, Block, Borders};
 
-use crate::backend::{DirList, symlink::{self, Symlink}, Folder, File};
+use crate::backend::{Symlink, Folder, File};
 
 #[derive(Clone)]
 pub struct DirListUI {
@@ -8,7 +8,7 @@ pub struct DirListUI {
 }
 
 impl DirListUI {
-    pub fn from(folders: &Vec<Folder>, files: &Vec<File>, symlinks: &Vec<Symlink>) -> DirListUI {
+    pub fn from(folders: &[Folder], files: &[File], symlinks: &[Symlink]) -> DirListUI {
         let folders: Vec<String> = folders.iter().map(|x| x.to_string()).collect();
         let files: Vec<String> = files.iter().map(|x| x.to_string()).collect();
         let symlinks: Vec<String> = symlinks.iter().map(|x| x.to_string()).collect();
@@ -1,21 +1,54 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Modifier, Style},
-    widgets::{Block, Borders, List, ListState, Widget, StatefulWidget},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, StatefulWidget, Widget},
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::backend::{
+    columns::Column, dir_list::FileSystemItem, filter::Filter, sort::SortOptions,
+    symlink::LinkTarget, vfs::Metadata, DirList,
 };
 
-use crate::backend::DirList;
+// Columns are dropped before names get narrower than this
+const MIN_NAME_WIDTH: usize = 14;
 
 #[derive(Clone)]
 pub struct DirSelectionList {
+    // Index into the shown items, which are all items unless a filter narrows them
     pub state: usize,
     pub items: DirList,
+    // Marked entries are tracked by name so they survive reloading the DirList
+    pub marked: HashSet<String>,
+    // Start of the visual range, the other end is the cursor
+    pub visual_anchor: Option<usize>,
+    filter: Option<Filter>,
+    // Indices of the items matching the filter
+    shown: Vec<usize>,
+    // Item under the cursor when filtering started, it gets the cursor back once the filter is
+    // cleared
+    filter_origin: Option<String>,
+    // Details shown next to the names, set by the layout before rendering
+    pub columns: Vec<Column>,
 }
 
 impl DirSelectionList {
     pub fn from(items: DirList) -> DirSelectionList {
-        DirSelectionList { state: 0, items }
+        DirSelectionList {
+            state: 0,
+            items,
+            marked: HashSet::new(),
+            visual_anchor: None,
+            filter: None,
+            shown: Vec::new(),
+            filter_origin: None,
+            columns: Vec::new(),
+        }
     }
 
     pub fn select(&mut self, index: usize) {
@@ -23,7 +56,7 @@ impl DirSelectionList {
     }
 
     pub fn select_next(&mut self) {
-        if self.items.len() != 0 && self.state >= self.items.len() - 1 {
+        if !self.is_empty() && self.state >= self.len() - 1 {
             self.select(0);
         } else {
             self.select(self.state + 1);
@@ -33,42 +66,398 @@ impl DirSelectionList {
     pub fn select_previous(&mut self) {
         if self.state != 0 {
             self.select(self.state - 1);
-        } else {
-            self.select(self.items.len() - 1)
+        } else if !self.is_empty() {
+            self.select(self.len() - 1)
+        }
+    }
+
+    // Number of shown items
+    pub fn len(&self) -> usize {
+        match self.filter {
+            Some(_) => self.shown.len(),
+            None => self.items.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn item_index(&self, index: usize) -> Option<usize> {
+        match self.filter {
+            Some(_) => self.shown.get(index).copied(),
+            None => (index < self.items.len()).then_some(index),
+        }
+    }
+
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.item_index(index)
+            .and_then(|item_index| self.items.name(item_index))
+    }
+
+    // Names of the shown items in display order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        (0..self.len()).filter_map(|index| self.name(index))
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names().position(|item_name| item_name == name)
+    }
+
+    pub fn get(&self, index: usize) -> Option<FileSystemItem> {
+        self.item_index(index)
+            .and_then(|item_index| self.items.get(item_index))
+    }
+
+    pub fn metadata(&self, index: usize) -> Option<&Metadata> {
+        self.item_index(index)
+            .and_then(|item_index| self.items.metadata(item_index))
+    }
+
+    pub fn link(&self, index: usize) -> Option<&LinkTarget> {
+        self.item_index(index)
+            .and_then(|item_index| self.items.link(item_index))
+    }
+
+    pub fn selected_item(&self) -> Option<FileSystemItem> {
+        self.get(self.state)
+    }
+
+    fn selected_name(&self) -> Option<&str> {
+        self.name(self.state)
+    }
+
+    // Swaps in a reloaded listing, the filter is applied to it again
+    pub fn set_items(&mut self, items: DirList) {
+        self.items = items;
+        self.apply_filter();
+    }
+
+    pub fn sort(&mut self, sort: SortOptions) {
+        self.items.sort(sort);
+        self.apply_filter();
+    }
+
+    pub fn set_show_hidden(&mut self, show_hidden: bool) {
+        self.items.set_show_hidden(show_hidden);
+        self.apply_filter();
+    }
+
+    pub fn filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    // Narrows the shown items, the cursor stays on its item while that still matches. None shows
+    // everything again
+    pub fn set_filter(&mut self, filter: Option<Filter>) {
+        if self.filter_origin.is_none() {
+            self.filter_origin = self.selected_name().map(String::from);
+        }
+        let selected_name = self.selected_name().map(String::from);
+        self.filter = filter;
+        self.apply_filter();
+        self.visual_anchor = None;
+        self.state = selected_name
+            .and_then(|name| self.index_of(&name))
+            .unwrap_or(0);
+    }
+
+    // Shows all items again, with the cursor back where it was before filtering
+    pub fn clear_filter(&mut self) {
+        let origin = self.filter_origin.take();
+        let selected_name = self.selected_name().map(String::from);
+        self.filter = None;
+        self.shown.clear();
+        self.visual_anchor = None;
+        self.state = origin
+            .or(selected_name)
+            .and_then(|name| self.index_of(&name))
+            .unwrap_or(0);
+    }
+
+    fn apply_filter(&mut self) {
+        self.shown = match &self.filter {
+            Some(filter) => (0..self.items.len())
+                .filter(|&index| {
+                    self.items
+                        .name(index)
+                        .is_some_and(|name| filter.matches(name).is_some())
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let last_index = self.len().saturating_sub(1);
+        self.state = self.state.min(last_index);
+    }
+
+    pub fn toggle_mark(&mut self) {
+        if let Some(name) = self.selected_name().map(String::from) {
+            if !self.marked.remove(&name) {
+                self.marked.insert(name);
+            }
+        }
+    }
+
+    pub fn invert_marks(&mut self) {
+        let inverted: Vec<String> = self.names().map(String::from).collect();
+        for name in inverted {
+            if !self.marked.remove(&name) {
+                self.marked.insert(name);
+            }
         }
     }
+
+    pub fn mark_all(&mut self) {
+        let names: Vec<String> = self.names().map(String::from).collect();
+        self.marked.extend(names);
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    // Returns how many entries were newly marked
+    pub fn mark_matching(&mut self, pattern: &glob::Pattern) -> usize {
+        let matching: Vec<String> = self
+            .names()
+            .filter(|name| pattern.matches(name))
+            .map(String::from)
+            .collect();
+
+        matching
+            .into_iter()
+            .filter(|name| self.marked.insert(name.clone()))
+            .count()
+    }
+
+    // Drops marks of entries that no longer exist, a list still loading may not have them yet
+    pub fn retain_existing_marks(&mut self) {
+        if self.items.is_loading() {
+            return;
+        }
+        let names: HashSet<&str> = self.items.names().collect();
+        self.marked.retain(|name| names.contains(name.as_str()));
+    }
+
+    pub fn is_visual(&self) -> bool {
+        self.visual_anchor.is_some()
+    }
+
+    pub fn start_visual(&mut self) {
+        if !self.is_empty() {
+            self.visual_anchor = Some(self.state);
+        }
+    }
+
+    // Leaves visual mode, keeping the range marked when `commit` is set
+    pub fn end_visual(&mut self, commit: bool) {
+        if commit {
+            let names: Vec<String> = self
+                .names()
+                .enumerate()
+                .filter(|(index, _)| self.in_visual_range(*index))
+                .map(|(_, name)| name.to_string())
+                .collect();
+            self.marked.extend(names);
+        }
+        self.visual_anchor = None;
+    }
+
+    fn in_visual_range(&self, index: usize) -> bool {
+        match self.visual_anchor {
+            Some(anchor) => index >= anchor.min(self.state) && index <= anchor.max(self.state),
+            None => false,
+        }
+    }
+
+    pub fn is_marked(&self, index: usize, name: &str) -> bool {
+        self.marked.contains(name) || self.in_visual_range(index)
+    }
+
+    // Marks of items hidden by the filter are kept but not counted or acted on
+    pub fn marked_count(&self) -> usize {
+        self.names()
+            .enumerate()
+            .filter(|(index, name)| self.is_marked(*index, name))
+            .count()
+    }
+
+    // Marked entries in display order, including the visual range
+    pub fn marked_items(&self) -> Vec<FileSystemItem> {
+        self.names()
+            .enumerate()
+            .filter(|(index, name)| self.is_marked(*index, name))
+            .filter_map(|(index, _)| self.get(index))
+            .collect()
+    }
+}
+
+// Splits `name` so the parts in `ranges` get `style`
+fn highlight(name: &str, ranges: &[Range<usize>], style: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for range in ranges {
+        if range.start > end {
+            spans.push(Span::raw(name[end..range.start].to_string()));
+        }
+        spans.push(Span::styled(name[range.clone()].to_string(), style));
+        end = range.end;
+    }
+    if end < name.len() {
+        spans.push(Span::raw(name[end..].to_string()));
+    }
+    spans
+}
+
+// Cuts `name` to `width` cells, ending it with an ellipsis when something was left out. Returns
+// the bytes of `name` kept and the cells taken
+fn truncate(name: &str, width: usize) -> (usize, usize) {
+    if name.width() <= width {
+        return (name.len(), name.width());
+    }
+    let mut kept = 0;
+    let mut used = 0;
+    for c in name.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width + 1 > width {
+            break;
+        }
+        kept += c.len_utf8();
+        used += char_width;
+    }
+    (kept, used + 1)
 }
 
 impl Widget for DirSelectionList {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        // Turn DirList into a list of strings
-        let folders: Vec<String> = self.items.folders().iter().map(|x| x.to_string()).collect();
-        let files: Vec<String> = self.items.files().iter().map(|x| x.to_string()).collect();
-        let symlinks: Vec<String> = self
-            .items
-            .symlinks()
-            .iter()
-            .map(|x| x.to_string())
+        let marked_style = Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD);
+        let match_style = Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        let link_style = Style::default().fg(Color::DarkGray);
+        let broken_style = Style::default().fg(Color::Red);
+
+        let mut block = Block::default().borders(Borders::ALL);
+        if self.items.is_loading() {
+            block = block.title(" loading… ");
+        }
+        if let Some(filter) = &self.filter {
+            block = block.title(format!(
+                " {} {}/{} ",
+                filter.pattern(),
+                self.len(),
+                self.items.len()
+            ));
+        }
+        let inner = block.inner(area);
+
+        // Only the rows on screen are built, scrolled like the List would to show the cursor
+        let height = (inner.height as usize).max(1);
+        let offset = (self.state + 1).saturating_sub(height);
+        let visible = offset..self.len().min(offset + height);
+
+        let cells: Vec<Vec<String>> = visible
+            .clone()
+            .map(|index| match self.metadata(index) {
+                Some(metadata) => self
+                    .columns
+                    .iter()
+                    .map(|column| column.cell(metadata))
+                    .collect(),
+                None => vec![String::new(); self.columns.len()],
+            })
+            .collect();
+        let mut widths: Vec<usize> = (0..self.columns.len())
+            .map(|column| {
+                cells
+                    .iter()
+                    .map(|row| row[column].width())
+                    .max()
+                    .unwrap_or(0)
+            })
             .collect();
+        // Columns give way from the end of the list, each takes a space before it
+        let mut columns_width: usize = widths.iter().map(|width| width + 1).sum();
+        while !widths.is_empty() && columns_width + MIN_NAME_WIDTH > inner.width as usize {
+            columns_width -= widths.pop().unwrap_or(0) + 1;
+        }
+        let name_width = (inner.width as usize).saturating_sub(columns_width);
+
+        let items = visible
+            .zip(cells)
+            .filter_map(|(index, row)| {
+                let name = self.name(index)?;
+                let (kept, mut shown_width) = truncate(name, name_width);
+                let ranges: Vec<Range<usize>> = self
+                    .filter
+                    .as_ref()
+                    .and_then(|filter| filter.matches(name))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|range| range.start < kept)
+                    .map(|range| range.start..range.end.min(kept))
+                    .collect();
+                let mut spans = highlight(&name[..kept], &ranges, match_style);
+                if kept < name.len() {
+                    spans.push(Span::raw("…"));
+                } else if let Some(link) = self.link(index) {
+                    // The target gets what room the name leaves
+                    let suffix = link.suffix();
+                    let link_style = if link.broken {
+                        broken_style
+                    } else {
+                        link_style
+                    };
+                    let room = name_width.saturating_sub(shown_width);
+                    if room > 0 {
+                        let (suffix_kept, suffix_width) = truncate(&suffix, room);
+                        spans.push(Span::styled(suffix[..suffix_kept].to_string(), link_style));
+                        if suffix_kept < suffix.len() {
+                            spans.push(Span::styled("…", link_style));
+                        }
+                        shown_width += suffix_width;
+                    }
+                }
+                if self.link(index).is_some_and(|link| link.broken) {
+                    for span in &mut spans {
+                        span.style = broken_style.patch(span.style);
+                    }
+                }
+
+                if !widths.is_empty() {
+                    spans.push(Span::raw(
+                        " ".repeat(name_width.saturating_sub(shown_width)),
+                    ));
+                }
+                for ((column, width), cell) in self.columns.iter().zip(&widths).zip(&row) {
+                    let padding = " ".repeat(width - cell.width());
+                    spans.push(Span::raw(if column.is_right_aligned() {
+                        format!(" {}{}", padding, cell)
+                    } else {
+                        format!(" {}{}", cell, padding)
+                    }));
+                }
+
+                let item = ListItem::new(Line::from(spans));
+                if self.is_marked(index, name) {
+                    Some(item.style(marked_style))
+                } else {
+                    Some(item)
+                }
+            })
+            .collect::<Vec<ListItem>>();
 
-        let items = folders
-            .iter()
-            .chain(files.iter())
-            .chain(symlinks.iter())
-            .map(|x| x.as_str().to_string())
-            .collect::<Vec<String>>();
-
-        let list = List::new(items)
-            .block(Block::default().borders(Borders::ALL))
-            .highlight_style(
-                Style::default()
-                    .add_modifier(Modifier::BOLD)
-                    //TODO: maybe get colors from the terminal colors?
-                    .bg(ratatui::style::Color::Blue)
-                    .fg(ratatui::style::Color::Rgb(0, 0, 0)),
-            );
+        let list = List::new(items).block(block).highlight_style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                //TODO: maybe get colors from the terminal colors?
+                .bg(ratatui::style::Color::Blue)
+                .fg(ratatui::style::Color::Rgb(0, 0, 0)),
+        );
         let mut list_state = ListState::default();
-        list_state.select(Some(self.state));
+        list_state.select(Some(self.state - offset));
         StatefulWidget::render(list, area, buf, &mut list_state)
     }
 }
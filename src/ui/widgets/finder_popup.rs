@@ -0,0 +1,56 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, StatefulWidget, Widget},
+};
+
+use crate::backend::finder::FinderMatch;
+
+// Results of the fuzzy finder over the panes, the query is typed in the status line
+#[derive(Clone)]
+pub struct FinderPopup {
+    pub title: String,
+    pub matches: Vec<FinderMatch>,
+    pub selected: usize,
+}
+
+// Styles the chars at `indices`, which are sorted
+fn highlight(found: &FinderMatch, style: Style) -> Line<'static> {
+    let mut indices = found.indices.iter().peekable();
+    let spans: Vec<Span> = found
+        .path
+        .chars()
+        .enumerate()
+        .map(|(index, c)| {
+            if indices.next_if_eq(&&(index as u32)).is_some() {
+                Span::styled(c.to_string(), style)
+            } else {
+                Span::raw(c.to_string())
+            }
+        })
+        .collect();
+    Line::from(spans)
+}
+
+impl Widget for FinderPopup {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let match_style = Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|found| ListItem::new(highlight(found, match_style)))
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(self.title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        let mut list_state = ListState::default().with_selected(Some(self.selected));
+        StatefulWidget::render(list, area, buf, &mut list_state);
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{
+        Block, Borders, Clear, List, ListItem, ListState, Paragraph, StatefulWidget, Widget,
+    },
+};
+
+use crate::backend::grep::{GrepContext, GrepHit};
+
+// Hits of a content search on the left, the lines around the selected one on the right
+#[derive(Clone)]
+pub struct GrepView {
+    pub title: String,
+    pub root: PathBuf,
+    // Just the hits that fit on screen, `selected` indexes into them
+    pub hits: Vec<GrepHit>,
+    pub selected: usize,
+    pub context: Option<Arc<GrepContext>>,
+}
+
+// "path:line: text" with the matches in `text` styled
+fn hit_line(hit: &GrepHit, root: &Path, style: Style) -> Line<'static> {
+    let path = hit.path.strip_prefix(root).unwrap_or(&hit.path);
+    let mut spans = vec![
+        Span::styled(
+            path.display().to_string(),
+            Style::default().fg(Color::Magenta),
+        ),
+        Span::raw(":"),
+        Span::styled(hit.line.to_string(), Style::default().fg(Color::Green)),
+        Span::raw(": "),
+    ];
+    let mut end = 0;
+    for range in &hit.matches {
+        if range.start > end {
+            spans.push(Span::raw(hit.text[end..range.start].to_string()));
+        }
+        spans.push(Span::styled(hit.text[range.clone()].to_string(), style));
+        end = range.end;
+    }
+    if end < hit.text.len() {
+        spans.push(Span::raw(hit.text[end..].to_string()));
+    }
+    Line::from(spans)
+}
+
+impl Widget for GrepView {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+
+        let match_style = Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        let items: Vec<ListItem> = self
+            .hits
+            .iter()
+            .map(|hit| ListItem::new(hit_line(hit, &self.root, match_style)))
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(self.title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut list_state = ListState::default();
+        if !self.hits.is_empty() {
+            list_state.select(Some(self.selected));
+        }
+        StatefulWidget::render(list, columns[0], buf, &mut list_state);
+
+        let selected_hit = self.hits.get(self.selected);
+        let block = Block::default().borders(Borders::ALL).title(
+            selected_hit
+                .map(|hit| format!(" {} ", hit.path.display()))
+                .unwrap_or_default(),
+        );
+        let lines: Vec<Line> = match (&self.context, selected_hit) {
+            (Some(context), Some(hit)) => {
+                let last_line = context.first_line + context.lines.len();
+                let width = last_line.to_string().len();
+                context
+                    .lines
+                    .iter()
+                    .enumerate()
+                    .map(|(index, text)| {
+                        let line = context.first_line + index;
+                        let number = Span::styled(
+                            format!("{:>width$} ", line),
+                            Style::default().fg(Color::DarkGray),
+                        );
+                        if line == hit.line {
+                            Line::from(vec![
+                                number,
+                                Span::styled(
+                                    text.clone(),
+                                    Style::default()
+                                        .fg(Color::Yellow)
+                                        .add_modifier(Modifier::BOLD),
+                                ),
+                            ])
+                        } else {
+                            Line::from(vec![number, Span::raw(text.clone())])
+                        }
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        Paragraph::new(lines).block(block).render(columns[1], buf);
+    }
+}
@@ -0,0 +1,95 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Clear, LineGauge, Paragraph, Widget},
+};
+
+use crate::backend::jobs::{JobInfo, JobState};
+use crate::helper_functions::format_bytes;
+
+pub fn job_label(job: &JobInfo) -> String {
+    let state = match job.state {
+        JobState::Queued => "queued",
+        JobState::Running => "running",
+        JobState::Paused => "paused",
+        JobState::Cancelling => "cancelling",
+    };
+    let progress = &job.progress;
+
+    if progress.bytes_total > 0 {
+        format!(
+            "{} [{}] {}/{}",
+            job.description,
+            state,
+            format_bytes(progress.bytes_done),
+            format_bytes(progress.bytes_total)
+        )
+    } else {
+        format!(
+            "{} [{}] {}/{} items",
+            job.description, state, progress.items_done, progress.items_total
+        )
+    }
+}
+
+// One line summary shown next to the status line while the panel is closed
+pub fn jobs_summary(jobs: &[JobInfo]) -> String {
+    match jobs {
+        [] => String::new(),
+        [job] => format!("{:.0}% {}", job.progress.ratio() * 100.0, job.description),
+        [job, rest @ ..] => format!(
+            "{:.0}% {} (+{} jobs)",
+            job.progress.ratio() * 100.0,
+            job.description,
+            rest.len()
+        ),
+    }
+}
+
+#[derive(Clone)]
+pub struct JobPanel {
+    pub jobs: Vec<JobInfo>,
+    pub selected: usize,
+}
+
+impl JobPanel {
+    pub fn height(&self) -> u16 {
+        self.jobs.len().max(1) as u16 + 2
+    }
+}
+
+impl Widget for JobPanel {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Jobs (p: pause/resume, c: cancel, Esc: close) ");
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        if self.jobs.is_empty() {
+            Paragraph::new("No running jobs").render(inner, buf);
+            return;
+        }
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(1); self.jobs.len()])
+            .split(inner);
+
+        for (index, (job, row)) in self.jobs.iter().zip(rows.iter()).enumerate() {
+            let mut label_style = Style::default();
+            if index == self.selected {
+                label_style = label_style.add_modifier(Modifier::REVERSED);
+            }
+
+            LineGauge::default()
+                .ratio(job.progress.ratio())
+                .label(job_label(job))
+                .style(label_style)
+                .gauge_style(Style::default().fg(Color::Green))
+                .render(*row, buf);
+        }
+    }
+}
@@ -2,9 +2,24 @@ mod dir_selection_list;
 mod three_pane_layout;
 mod right_pane;
 mod dir_list_ui;
+mod status_line;
+mod job_panel;
+mod trash_view;
+mod rename_preview;
+mod open_with_popup;
+mod finder_popup;
+mod grep_view;
 
 pub use dir_selection_list::DirSelectionList;
 pub use three_pane_layout::ThreePaneLayout;
 pub use three_pane_layout::ThreePaneLayoutState;
+pub use three_pane_layout::right_pane_inner_area;
 pub use right_pane::RightPane;
 pub use dir_list_ui::DirListUI;
+pub use status_line::StatusLine;
+pub use job_panel::JobPanel;
+pub use trash_view::TrashView;
+pub use rename_preview::RenamePreview;
+pub use open_with_popup::OpenWithPopup;
+pub use finder_popup::FinderPopup;
+pub use grep_view::GrepView;
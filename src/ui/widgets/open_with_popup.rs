@@ -0,0 +1,54 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, StatefulWidget, Widget},
+};
+
+// Small centered menu listing the programs a file can be opened with
+#[derive(Clone)]
+pub struct OpenWithPopup {
+    pub title: String,
+    pub labels: Vec<String>,
+    pub selected: usize,
+}
+
+impl OpenWithPopup {
+    fn area(&self, area: Rect) -> Rect {
+        let longest = self
+            .labels
+            .iter()
+            .map(|label| label.chars().count())
+            .chain([self.title.chars().count()])
+            .max()
+            .unwrap_or(0) as u16;
+        let width = (longest + 6).min(area.width);
+        let height = (self.labels.len() as u16 + 2).min(area.height);
+
+        Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+impl Widget for OpenWithPopup {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = self.area(area);
+        Clear.render(area, buf);
+
+        let items: Vec<ListItem> = self
+            .labels
+            .iter()
+            .map(|label| ListItem::new(label.clone()))
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(self.title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        let mut list_state = ListState::default().with_selected(Some(self.selected));
+        StatefulWidget::render(list, area, buf, &mut list_state);
+    }
+}
@@ -0,0 +1,38 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Widget},
+};
+
+// Lists pending bulk renames while the user confirms them
+#[derive(Clone)]
+pub struct RenamePreview {
+    pub renames: Vec<(String, String)>,
+}
+
+impl Widget for RenamePreview {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let lines: Vec<Line> = self
+            .renames
+            .iter()
+            .map(|(name, new_name)| {
+                Line::from(vec![
+                    Span::raw(name.clone()),
+                    Span::styled(" -> ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(new_name.clone(), Style::default().fg(Color::Green)),
+                ])
+            })
+            .collect();
+
+        Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                " Rename {} items (y: apply, n: cancel) ",
+                self.renames.len()
+            )))
+            .render(area, buf);
+    }
+}
@@ -1,14 +1,20 @@
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout},
-    widgets::{Paragraph, Widget},
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    widgets::{Paragraph, Widget, Wrap},
 };
 
-use crate::backend::DirList;
+use crate::backend::{file_info::FileInfo, preview::FilePreview, DirList};
 
 #[derive(Clone)]
 pub enum RightPane {
     DirList(Option<DirList>),
+    File(FilePreview),
+    Info(FileInfo),
+    // A preview still being read in the background
+    Loading,
     PermissionDenied,
+    Error(String),
 }
 
 impl Widget for RightPane {
@@ -19,22 +25,24 @@ impl Widget for RightPane {
                     dir_list.render(area, buf);
                 }
             }
-            RightPane::PermissionDenied => {
-                let paragraph = Paragraph::new("Permission Denied").alignment(Alignment::Center);
-                let chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints(
-                        [
-                            Constraint::Ratio(1, 10),
-                            Constraint::Ratio(1, 10),
-                            Constraint::Ratio(8, 10),
-                        ]
-                        .as_ref(),
-                    )
-                    .split(area);
-
-                paragraph.render(chunks[1], buf);
-            }
+            RightPane::File(preview) => preview.render(area, buf),
+            RightPane::Info(info) => info.render(area, buf),
+            RightPane::Loading => render_message("Loading…", area, buf),
+            RightPane::PermissionDenied => render_message("Permission Denied", area, buf),
+            RightPane::Error(message) => render_message(&message, area, buf),
         }
     }
 }
+
+// Centered a little below the top of the pane
+fn render_message(message: &str, area: Rect, buf: &mut Buffer) {
+    let paragraph = Paragraph::new(message)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Ratio(1, 10), Constraint::Ratio(9, 10)].as_ref())
+        .split(area);
+
+    paragraph.render(chunks[1], buf);
+}
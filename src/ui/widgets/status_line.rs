@@ -0,0 +1,56 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Paragraph, Widget},
+};
+
+#[derive(Clone)]
+pub enum StatusLine {
+    Empty,
+    Message(String),
+    Error(String),
+    Prompt {
+        label: String,
+        input: String,
+        cursor: usize,
+    },
+}
+
+impl Widget for StatusLine {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let line = match self {
+            StatusLine::Empty => Line::default(),
+            StatusLine::Message(message) => Line::from(message),
+            StatusLine::Error(error) => Line::from(Span::styled(
+                error,
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )),
+            StatusLine::Prompt {
+                label,
+                input,
+                cursor,
+            } => {
+                let before: String = input.chars().take(cursor).collect();
+                let under_cursor: String = input
+                    .chars()
+                    .nth(cursor)
+                    .map_or(' '.to_string(), String::from);
+                let after: String = input.chars().skip(cursor + 1).collect();
+
+                Line::from(vec![
+                    Span::styled(label, Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(before),
+                    Span::styled(
+                        under_cursor,
+                        Style::default().add_modifier(Modifier::REVERSED),
+                    ),
+                    Span::raw(after),
+                ])
+            }
+        };
+
+        Paragraph::new(line).render(area, buf);
+    }
+}
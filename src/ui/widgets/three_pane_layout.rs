@@ -1,45 +1,117 @@
+use std::rc::Rc;
+
 use ratatui::{
     buffer::Buffer,
-    layout::{Constraint, Direction, Layout, Rect},
-    widgets::{Block, Borders, StatefulWidget, Widget},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Paragraph, StatefulWidget, Tabs, Widget},
 };
 
-use crate::backend::DirList;
+use crate::backend::{columns::Column, jobs::JobInfo, DirList};
 
-use super::{DirSelectionList, RightPane};
+use super::{
+    job_panel::jobs_summary, DirSelectionList, FinderPopup, GrepView, JobPanel, OpenWithPopup,
+    RenamePreview, RightPane, StatusLine, TrashView,
+};
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ThreePaneLayout {
     pub mid_pane: Option<DirSelectionList>,
-    pub dot_files_enabled: bool,
 }
 
 pub struct ThreePaneLayoutState {
     left_pane: Option<DirList>,
     right_pane: RightPane,
+    status_line: StatusLine,
+    tab_titles: Vec<String>,
+    selected_tab: usize,
+    jobs: Vec<JobInfo>,
+    // Selected job while the job panel is open
+    job_panel: Option<usize>,
+    trash_view: Option<TrashView>,
+    rename_preview: Option<RenamePreview>,
+    open_with: Option<OpenWithPopup>,
+    finder: Option<FinderPopup>,
+    grep_view: Option<GrepView>,
+    columns: Vec<Column>,
 }
 
 impl ThreePaneLayoutState {
-    pub fn new(left_pane: Option<DirList>, right_pane: RightPane) -> ThreePaneLayoutState {
+    pub fn new(
+        left_pane: Option<DirList>,
+        right_pane: RightPane,
+        status_line: StatusLine,
+    ) -> ThreePaneLayoutState {
         ThreePaneLayoutState {
             left_pane,
             right_pane,
+            status_line,
+            tab_titles: Vec::new(),
+            selected_tab: 0,
+            jobs: Vec::new(),
+            job_panel: None,
+            trash_view: None,
+            rename_preview: None,
+            open_with: None,
+            finder: None,
+            grep_view: None,
+            columns: Vec::new(),
         }
     }
+
+    pub fn with_trash_view(mut self, trash_view: Option<TrashView>) -> ThreePaneLayoutState {
+        self.trash_view = trash_view;
+        self
+    }
+
+    pub fn with_rename_preview(
+        mut self,
+        rename_preview: Option<RenamePreview>,
+    ) -> ThreePaneLayoutState {
+        self.rename_preview = rename_preview;
+        self
+    }
+
+    pub fn with_open_with(mut self, open_with: Option<OpenWithPopup>) -> ThreePaneLayoutState {
+        self.open_with = open_with;
+        self
+    }
+
+    pub fn with_finder(mut self, finder: Option<FinderPopup>) -> ThreePaneLayoutState {
+        self.finder = finder;
+        self
+    }
+
+    pub fn with_columns(mut self, columns: Vec<Column>) -> ThreePaneLayoutState {
+        self.columns = columns;
+        self
+    }
+
+    pub fn with_grep_view(mut self, grep_view: Option<GrepView>) -> ThreePaneLayoutState {
+        self.grep_view = grep_view;
+        self
+    }
+
+    pub fn with_jobs(mut self, jobs: Vec<JobInfo>, job_panel: Option<usize>) -> ThreePaneLayoutState {
+        self.jobs = jobs;
+        self.job_panel = job_panel;
+        self
+    }
+
+    pub fn with_tabs(mut self, tab_titles: Vec<String>, selected_tab: usize) -> ThreePaneLayoutState {
+        self.tab_titles = tab_titles;
+        self.selected_tab = selected_tab;
+        self
+    }
 }
 
 impl ThreePaneLayout {
     pub fn new(dir_list: DirList) -> ThreePaneLayout {
         ThreePaneLayout {
             mid_pane: Some(DirSelectionList::from(dir_list)),
-            dot_files_enabled: true
         }
     }
 
-    pub fn default() -> ThreePaneLayout {
-        ThreePaneLayout { mid_pane: None , dot_files_enabled: true}
-    }
-
     pub fn select_next(&mut self) {
         if let Some(mid_pane) = &mut self.mid_pane {
             mid_pane.select_next();
@@ -53,29 +125,66 @@ impl ThreePaneLayout {
     }
 }
 
+// Splits the screen into rows (tab bar, panes, status line) and the three panes
+fn split_screen(area: Rect, show_tab_bar: bool) -> (Rc<[Rect]>, Rc<[Rect]>) {
+    // The tab bar only takes up space once there is more than one tab
+    let tab_bar_height = if show_tab_bar { 1 } else { 0 };
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(tab_bar_height),
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(20),
+                Constraint::Percentage(30),
+                Constraint::Percentage(50),
+            ]
+            .as_ref(),
+        )
+        .split(rows[1]);
+
+    (rows, chunks)
+}
+
+// Space inside the right pane's border, previews size their reads to it
+pub fn right_pane_inner_area(area: Rect, show_tab_bar: bool) -> Rect {
+    let (_, chunks) = split_screen(area, show_tab_bar);
+    Block::default().borders(Borders::ALL).inner(chunks[2])
+}
+
 impl StatefulWidget for ThreePaneLayout {
     type State = ThreePaneLayoutState;
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
         let block = Block::default().borders(Borders::ALL);
 
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(
-                [
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(30),
-                    Constraint::Percentage(50),
-                ]
-                .as_ref(),
-            )
-            .split(area);
+        let show_tab_bar = state.tab_titles.len() > 1;
+        let (rows, chunks) = split_screen(area, show_tab_bar);
+
+        if show_tab_bar {
+            Tabs::new(state.tab_titles.clone())
+                .select(state.selected_tab)
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                .render(rows[0], buf);
+        }
 
         for chunk in chunks.iter() {
             block.clone().render(*chunk, buf);
         }
 
-        if let Some(mid_pane) = self.mid_pane {
-            mid_pane.clone().render(chunks[1], buf);
+        if let Some(mut mid_pane) = self.mid_pane {
+            mid_pane.columns = state.columns.clone();
+            mid_pane.render(chunks[1], buf);
         }
 
         let left_pane = state.left_pane.clone();
@@ -86,5 +195,59 @@ impl StatefulWidget for ThreePaneLayout {
         }
 
         right_pane.render(chunks[2], buf);
+
+        if let Some(trash_view) = state.trash_view.clone() {
+            trash_view.render(rows[1], buf);
+        }
+
+        if let Some(grep_view) = state.grep_view.clone() {
+            grep_view.render(rows[1], buf);
+        }
+
+        if let Some(rename_preview) = state.rename_preview.clone() {
+            rename_preview.render(rows[1], buf);
+        }
+
+        if let Some(open_with) = state.open_with.clone() {
+            open_with.render(rows[1], buf);
+        }
+
+        if let Some(finder) = state.finder.clone() {
+            finder.render(rows[1], buf);
+        }
+
+        if let Some(selected) = state.job_panel {
+            let panel = JobPanel {
+                jobs: state.jobs.clone(),
+                selected,
+            };
+            let height = panel.height().min(rows[1].height);
+            let panel_area = Rect {
+                y: rows[1].y + rows[1].height - height,
+                height,
+                ..rows[1]
+            };
+            panel.render(panel_area, buf);
+        }
+
+        let summary = if state.job_panel.is_none() {
+            jobs_summary(&state.jobs)
+        } else {
+            String::new()
+        };
+        let status_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(summary.chars().count().min(rows[2].width as usize / 2) as u16),
+                ]
+                .as_ref(),
+            )
+            .split(rows[2]);
+        state.status_line.clone().render(status_chunks[0], buf);
+        Paragraph::new(summary)
+            .alignment(Alignment::Right)
+            .render(status_chunks[1], buf);
     }
 }
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Rect},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Clear, Row, StatefulWidget, Table, TableState, Widget},
+};
+
+use crate::backend::{trash::TrashEntry, DirList};
+
+use super::DirSelectionList;
+
+// Lists the trash `files/` directory, with the details of each item taken from its info file
+#[derive(Clone)]
+pub struct TrashView {
+    pub list: DirSelectionList,
+    pub entries: HashMap<String, TrashEntry>,
+}
+
+impl TrashView {
+    pub fn new(dir_list: DirList, entries: HashMap<String, TrashEntry>) -> TrashView {
+        TrashView {
+            list: DirSelectionList::from(dir_list),
+            entries,
+        }
+    }
+
+    // Marked entries if there are any, otherwise the entry under the cursor
+    pub fn target_names(&self) -> Vec<String> {
+        let marked = self.list.marked_items();
+        if marked.is_empty() {
+            self.list
+                .items
+                .get(self.list.state)
+                .map(|item| vec![item.name().to_string()])
+                .unwrap_or_default()
+        } else {
+            marked.iter().map(|item| item.name().to_string()).collect()
+        }
+    }
+}
+
+impl Widget for TrashView {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let marked_style = Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD);
+
+        let rows: Vec<Row> = self
+            .list
+            .items
+            .names()
+            .enumerate()
+            .map(|(index, name)| {
+                let entry = self.entries.get(name);
+                let original_path = entry
+                    .and_then(|entry| entry.original_path.as_ref())
+                    .map_or(String::from("?"), |path| path.display().to_string());
+                let deletion_date = entry
+                    .and_then(|entry| entry.deletion_date.clone())
+                    .unwrap_or_else(|| String::from("?"));
+
+                let row = Row::new(vec![name.to_string(), original_path, deletion_date]);
+                if self.list.is_marked(index, name) {
+                    row.style(marked_style)
+                } else {
+                    row
+                }
+            })
+            .collect();
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(30),
+                Constraint::Percentage(50),
+                Constraint::Length(19),
+            ],
+        )
+        .header(
+            Row::new(vec!["Name", "Original path", "Deleted"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Trash (r: restore, D: purge, Esc: close) "),
+        )
+        .highlight_style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .bg(Color::Blue)
+                .fg(Color::Rgb(0, 0, 0)),
+        );
+
+        let mut table_state = TableState::default();
+        if !self.list.items.is_empty() {
+            table_state.select(Some(self.list.state));
+        }
+        StatefulWidget::render(table, area, buf, &mut table_state);
+    }
+}